@@ -0,0 +1,126 @@
+pub mod png;
+pub mod video;
+
+use crate::context::Context;
+use std::path::{Path, PathBuf};
+
+/// Reads back what was drawn on the `context` framebuffer, as RGBA8 pixels with the top row first.
+pub fn read_framebuffer(context: &Context) -> Vec<u8> {
+    let (width, height) = context.dimensions;
+    if let Some(color) = context.framebuffer.color() {
+        // The offscreen framebuffer of a headless context, its texture also starts from the bottom row.
+        let data: Vec<f32> = color.data();
+        let pixels: Vec<u8> = data.iter().map(|value| (value.clamp(0.0, 1.0) * 255.0).round() as u8).collect();
+        return pixels.chunks(width * 4).rev().flatten().copied().collect();
+    }
+    let mut pixels = vec![0u8; width * height * 4];
+    //FIXME: How to expose it on the GPU API?
+    // Ref: https://www.khronos.org/registry/OpenGL-Refpages/gl4/html/glReadPixels.xhtml
+    unsafe {
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+        gl::ReadPixels(0, 0, width as i32, height as i32, gl::RGBA, gl::UNSIGNED_BYTE, pixels.as_mut_ptr() as *mut _);
+    }
+    // OpenGL reads from the bottom row.
+    pixels.chunks(width * 4).rev().flatten().copied().collect()
+}
+
+/// Maps the first three components of `data` from `range` to RGB8 pixels with the top row first. Single component fields are gray.
+/// Without a range, the minimum and the maximum of the data are used.
+pub fn field_to_rgba8(data: &[f32], dimensions: (usize, usize), range: Option<(f32, f32)>) -> Vec<u8> {
+    let cells = dimensions.0 * dimensions.1;
+    let components = data.len() / cells.max(1);
+    let (minimum, maximum) = range.unwrap_or_else(|| {
+        data.iter().fold((std::f32::INFINITY, std::f32::NEG_INFINITY), |(minimum, maximum), value| (minimum.min(*value), maximum.max(*value)))
+    });
+    let extent = (maximum - minimum).max(std::f32::MIN_POSITIVE);
+    let byte = |value: f32| (((value - minimum) / extent).clamp(0.0, 1.0) * 255.0).round() as u8;
+    let mut pixels = Vec::with_capacity(cells * 4);
+    for row in data.chunks(dimensions.0 * components.max(1)).rev() {
+        for cell in row.chunks(components.max(1)) {
+            match cell.len() {
+                1 => pixels.extend_from_slice(&[byte(cell[0]), byte(cell[0]), byte(cell[0]), 255]),
+                2 => pixels.extend_from_slice(&[byte(cell[0]), byte(cell[1]), 0, 255]),
+                _ => pixels.extend_from_slice(&[byte(cell[0]), byte(cell[1]), byte(cell[2]), 255])
+            }
+        }
+    }
+    pixels
+}
+
+/// Saves a field texture as a PNG, through `field_to_rgba8`.
+pub fn save_field(path: impl AsRef<Path>, field: &gpu::Texture2D, range: Option<(f32, f32)>) -> std::io::Result<()> {
+    let dimensions = field.dimensions();
+    let data: Vec<f32> = field.data();
+    png::save(path, dimensions, &field_to_rgba8(&data, dimensions, range))
+}
+
+/// Saves the presented frames to numbered PNG files.
+pub struct FrameCapture {
+    pub directory: PathBuf,
+    pub prefix: String,
+    /// Captures every `interval` frames when set.
+    pub interval: Option<usize>,
+    frames: usize,
+    screenshots: usize
+}
+
+impl FrameCapture {
+    pub fn new(directory: impl Into<PathBuf>, prefix: &str) -> Self {
+        let directory = directory.into();
+        let prefix = prefix.to_string();
+        let interval = None;
+        let frames = 0;
+        let screenshots = 0;
+        Self { directory, prefix, interval, frames, screenshots }
+    }
+
+    fn save(&self, context: &Context, name: String) -> std::io::Result<PathBuf> {
+        std::fs::create_dir_all(&self.directory)?;
+        let path = self.directory.join(name);
+        png::save(&path, context.dimensions, &read_framebuffer(context))?;
+        Ok(path)
+    }
+
+    /// Saves the current frame as `<prefix>_screenshot_<n>.png`.
+    pub fn screenshot(&mut self, context: &Context) -> std::io::Result<PathBuf> {
+        let name = format!("{}_screenshot_{:04}.png", self.prefix, self.screenshots);
+        self.screenshots += 1;
+        self.save(context, name)
+    }
+
+    /// Counts a presented frame, and saves it as `<prefix>_<frame>.png` on every `interval` frames.
+    /// Call it before `Context::present`, while the back buffer still holds the frame.
+    pub fn frame(&mut self, context: &Context) -> std::io::Result<Option<PathBuf>> {
+        let frame = self.frames;
+        self.frames += 1;
+        match self.interval {
+            Some(interval) if interval > 0 && frame % interval == 0 => {
+                self.save(context, format!("{}_{:06}.png", self.prefix, frame)).map(Some)
+            },
+            _ => Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn scalar_field_to_gray() {
+        // Bottom row first, like the textures.
+        let data = vec![0.0, 1.0, /**/ 2.0, 4.0];
+        let pixels = field_to_rgba8(&data, (2, 2), None);
+        assert_eq!(pixels, vec![
+            128, 128, 128, 255, /**/ 255, 255, 255, 255,
+            0, 0, 0, 255, /**/ 64, 64, 64, 255
+        ]);
+    }
+
+    #[test]
+    fn vector_field_to_red_green() {
+        let data = vec![-1.0, 1.0, /**/ 1.0, -1.0];
+        let pixels = field_to_rgba8(&data, (2, 1), Some((-1.0, 1.0)));
+        assert_eq!(pixels, vec![0, 255, 0, 255, /**/ 255, 0, 0, 255]);
+    }
+}
@@ -0,0 +1,231 @@
+//! A minimal PNG encoder for 8-bit RGBA images, and a decoder for the non-interlaced 8 and 16-bit images.
+
+use std::io::Write;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+const COLOR_TYPE_GRAY: u8 = 0;
+const COLOR_TYPE_RGB: u8 = 2;
+const COLOR_TYPE_PALETTE: u8 = 3;
+const COLOR_TYPE_GRAY_ALPHA: u8 = 4;
+const COLOR_TYPE_RGBA: u8 = 6;
+const FILTER_NONE: u8 = 0;
+const FILTER_SUB: u8 = 1;
+const FILTER_UP: u8 = 2;
+const FILTER_AVERAGE: u8 = 3;
+const FILTER_PAETH: u8 = 4;
+
+/// CRC-32 as used by PNG and zip.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0 .. 8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+fn write_chunk(writer: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> std::io::Result<()> {
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    let mut checked = Vec::with_capacity(data.len() + 4);
+    checked.extend_from_slice(kind);
+    checked.extend_from_slice(data);
+    writer.write_all(&checked)?;
+    writer.write_all(&crc32(&checked).to_be_bytes())
+}
+
+/// Encodes RGBA8 `pixels`, top row first.
+pub fn encode(writer: &mut impl Write, dimensions: (usize, usize), pixels: &[u8]) -> std::io::Result<()> {
+    assert_eq!(pixels.len(), dimensions.0 * dimensions.1 * 4, "The pixels must be RGBA8.");
+    writer.write_all(&SIGNATURE)?;
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(dimensions.0 as u32).to_be_bytes());
+    header.extend_from_slice(&(dimensions.1 as u32).to_be_bytes());
+    // Bit depth, colour type, compression, filter and interlace methods.
+    header.extend_from_slice(&[8, COLOR_TYPE_RGBA, 0, 0, 0]);
+    write_chunk(writer, b"IHDR", &header)?;
+
+    // Every scanline starts with its filter type.
+    let row = dimensions.0 * 4;
+    let mut scanlines = Vec::with_capacity((row + 1) * dimensions.1);
+    for line in pixels.chunks(row.max(1)) {
+        scanlines.push(FILTER_NONE);
+        scanlines.extend_from_slice(line);
+    }
+    write_chunk(writer, b"IDAT", &miniz_oxide::deflate::compress_to_vec_zlib(&scanlines, 6))?;
+    write_chunk(writer, b"IEND", &[])
+}
+
+pub fn save(path: impl AsRef<std::path::Path>, dimensions: (usize, usize), pixels: &[u8]) -> std::io::Result<()> {
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+    encode(&mut writer, dimensions, pixels)?;
+    writer.flush()
+}
+
+fn invalid(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// A decoded image, top row first.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecodedImage {
+    pub dimensions: (usize, usize),
+    pub channels: usize,
+    /// The samples normalized to [0, 1], interleaved.
+    pub data: Vec<f32>
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc { a } else if pb <= pc { b } else { c }
+}
+
+/// Reverses the scanline filters in place. `stride` is the bytes per pixel.
+fn unfilter(scanlines: &[u8], row: usize, stride: usize, height: usize) -> std::io::Result<Vec<u8>> {
+    if scanlines.len() < (row + 1) * height {
+        return Err(invalid("The image data is truncated."));
+    }
+    let mut pixels = vec![0u8; row * height];
+    for y in 0 .. height {
+        let filter = scanlines[y * (row + 1)];
+        let line = &scanlines[y * (row + 1) + 1 .. (y + 1) * (row + 1)];
+        for x in 0 .. row {
+            let a = if x >= stride { pixels[y * row + x - stride] } else { 0 };
+            let b = if y > 0 { pixels[(y - 1) * row + x] } else { 0 };
+            let c = if x >= stride && y > 0 { pixels[(y - 1) * row + x - stride] } else { 0 };
+            let predictor = match filter {
+                FILTER_NONE    => 0,
+                FILTER_SUB     => a,
+                FILTER_UP      => b,
+                FILTER_AVERAGE => ((a as u16 + b as u16) / 2) as u8,
+                FILTER_PAETH   => paeth(a, b, c),
+                _              => return Err(invalid("Unknown scanline filter."))
+            };
+            pixels[y * row + x] = line[x].wrapping_add(predictor);
+        }
+    }
+    Ok(pixels)
+}
+
+/// Decodes 8 or 16-bit grayscale, RGB, palette (8-bit) and alpha images, without interlacing.
+pub fn decode(bytes: &[u8]) -> std::io::Result<DecodedImage> {
+    if bytes.len() < 8 || bytes[.. 8] != SIGNATURE {
+        return Err(invalid("Not a PNG file."));
+    }
+    let mut header = None;
+    let mut palette = Vec::new();
+    let mut compressed = Vec::new();
+    let mut offset = 8;
+    while offset + 12 <= bytes.len() {
+        let length = u32::from_be_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]]) as usize;
+        let kind = &bytes[offset + 4 .. offset + 8];
+        let data = bytes.get(offset + 8 .. offset + 8 + length).ok_or_else(|| invalid("A chunk is truncated."))?;
+        match kind {
+            b"IHDR" if data.len() == 13 => header = Some(data.to_vec()),
+            b"PLTE" => palette = data.to_vec(),
+            b"IDAT" => compressed.extend_from_slice(data),
+            b"IEND" => break,
+            _ => ()
+        }
+        offset += 12 + length;
+    }
+    let header = header.ok_or_else(|| invalid("The IHDR chunk is missing."))?;
+    let width = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let height = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    let (depth, color_type, interlace) = (header[8], header[9], header[12]);
+    if interlace != 0 {
+        return Err(invalid("Interlaced images aren't supported."));
+    }
+    let channels = match color_type {
+        COLOR_TYPE_GRAY       => 1,
+        COLOR_TYPE_RGB        => 3,
+        COLOR_TYPE_PALETTE    => 1,
+        COLOR_TYPE_GRAY_ALPHA => 2,
+        COLOR_TYPE_RGBA       => 4,
+        _                     => return Err(invalid("Unknown colour type."))
+    };
+    if !(depth == 8 || (depth == 16 && color_type != COLOR_TYPE_PALETTE)) {
+        return Err(invalid("Only 8 and 16-bit images are supported."));
+    }
+    let sample_size = depth as usize / 8;
+    let stride = channels * sample_size;
+    let scanlines = miniz_oxide::inflate::decompress_to_vec_zlib(&compressed).map_err(|_| invalid("The image data is corrupted."))?;
+    let pixels = unfilter(&scanlines, width * stride, stride, height)?;
+    let data = if color_type == COLOR_TYPE_PALETTE {
+        let mut data = Vec::with_capacity(width * height * 3);
+        for index in pixels {
+            let color = palette.get(index as usize * 3 .. index as usize * 3 + 3).ok_or_else(|| invalid("A palette index is out of range."))?;
+            data.extend(color.iter().map(|value| *value as f32 / 255.0));
+        }
+        return Ok(DecodedImage { dimensions: (width, height), channels: 3, data });
+    } else if sample_size == 2 {
+        pixels.chunks(2).map(|sample| u16::from_be_bytes([sample[0], sample[1]]) as f32 / 65535.0).collect()
+    } else {
+        pixels.iter().map(|sample| *sample as f32 / 255.0).collect()
+    };
+    Ok(DecodedImage { dimensions: (width, height), channels, data })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn crc() {
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn encoding() {
+        let pixels = vec![
+            255, 0, 0, 255, /**/ 0, 255, 0, 255,
+            0, 0, 255, 255, /**/ 0, 0, 0, 0
+        ];
+        let mut data = Vec::new();
+        encode(&mut data, (2, 2), &pixels).unwrap();
+        assert_eq!(&data[.. 8], &SIGNATURE);
+        assert_eq!(&data[12 .. 16], b"IHDR");
+        assert_eq!(&data[16 .. 24], &[0, 0, 0, 2, 0, 0, 0, 2]);
+        assert_eq!(&data[data.len() - 12 ..], &[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]);
+
+        // The image data is the filtered scanlines.
+        let idat = 8 + 25;
+        let length = u32::from_be_bytes([data[idat], data[idat + 1], data[idat + 2], data[idat + 3]]) as usize;
+        assert_eq!(&data[idat + 4 .. idat + 8], b"IDAT");
+        let scanlines = miniz_oxide::inflate::decompress_to_vec_zlib(&data[idat + 8 .. idat + 8 + length]).unwrap();
+        let mut expected = vec![0];
+        expected.extend_from_slice(&pixels[.. 8]);
+        expected.push(0);
+        expected.extend_from_slice(&pixels[8 ..]);
+        assert_eq!(scanlines, expected);
+    }
+
+    #[test]
+    fn decoding() {
+        let pixels = vec![
+            255, 0, 0, 255, /**/ 0, 255, 0, 255,
+            0, 0, 255, 255, /**/ 0, 0, 0, 0
+        ];
+        let mut data = Vec::new();
+        encode(&mut data, (2, 2), &pixels).unwrap();
+        let image = decode(&data).unwrap();
+        assert_eq!(image.dimensions, (2, 2));
+        assert_eq!(image.channels, 4);
+        assert_eq!(image.data, pixels.iter().map(|value| *value as f32 / 255.0).collect::<Vec<f32>>());
+    }
+
+    #[test]
+    fn filters() {
+        // Two gray rows of two pixels: Sub then Paeth.
+        let scanlines = vec![FILTER_SUB, 10, 5, /**/ FILTER_PAETH, 1, 1];
+        // Row 0: 10, 15. Row 1: 10 + 1 (above), 15 + 1 (above, since a = 11, b = 15, c = 10 gives p = 16, closest to b).
+        assert_eq!(unfilter(&scanlines, 2, 1, 2).unwrap(), vec![10, 15, 11, 16]);
+        let average = vec![FILTER_NONE, 10, 20, /**/ FILTER_AVERAGE, 0, 0];
+        assert_eq!(unfilter(&average, 2, 1, 2).unwrap(), vec![10, 20, 5, 12]);
+    }
+}
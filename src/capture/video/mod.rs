@@ -0,0 +1,181 @@
+//! Video export of the presented frames, as Y4M files or raw RGB piped to an encoder.
+
+use super::read_framebuffer;
+use crate::context::Context;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+
+/// Frames per second, as a fraction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameRate {
+    pub numerator: u32,
+    pub denominator: u32
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+impl FrameRate {
+    /// One frame every `steps_per_frame` simulation steps of `delta_time`, so the video plays at the simulated speed.
+    pub fn from_time_step(delta_time: f32, steps_per_frame: usize) -> Self {
+        let frame_time = delta_time as f64 * steps_per_frame.max(1) as f64;
+        let rate = 1.0 / frame_time;
+        // Time steps like 1/60 give integer rates, which the encoders handle best.
+        if (rate - rate.round()).abs() < 1e-3 {
+            return Self { numerator: rate.round() as u32, denominator: 1 };
+        }
+        let numerator = 1_000_000;
+        let denominator = (frame_time * 1_000_000.0).round().max(1.0) as u32;
+        let divisor = gcd(numerator, denominator);
+        Self { numerator: numerator / divisor, denominator: denominator / divisor }
+    }
+}
+
+fn y4m_header(dimensions: (usize, usize), frame_rate: FrameRate) -> String {
+    format!("YUV4MPEG2 W{} H{} F{}:{} Ip A1:1 C444\n", dimensions.0, dimensions.1, frame_rate.numerator, frame_rate.denominator)
+}
+
+/// Converts RGBA8 pixels to the planar limited range BT.601 Y, U and V planes of Y4M's C444.
+fn rgba_to_yuv444(pixels: &[u8]) -> Vec<u8> {
+    let count = pixels.len() / 4;
+    let mut planes = vec![0u8; count * 3];
+    for (index, pixel) in pixels.chunks(4).enumerate() {
+        let (r, g, b) = (pixel[0] as f32, pixel[1] as f32, pixel[2] as f32);
+        let y = 16.0 + (65.481 * r + 128.553 * g + 24.966 * b) / 255.0;
+        let u = 128.0 + (-37.797 * r - 74.203 * g + 112.0 * b) / 255.0;
+        let v = 128.0 + (112.0 * r - 93.786 * g - 18.214 * b) / 255.0;
+        planes[index]             = y.round() as u8;
+        planes[count + index]     = u.round() as u8;
+        planes[count * 2 + index] = v.round() as u8;
+    }
+    planes
+}
+
+fn rgba_to_rgb(pixels: &[u8]) -> Vec<u8> {
+    pixels.chunks(4).flat_map(|pixel| pixel[.. 3].to_vec()).collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VideoFormat {
+    Y4m,
+    /// Packed 8-bit RGB frames without any header, like ffmpeg's `rawvideo` with `rgb24`.
+    RawRgb
+}
+
+/// Streams frames of a fixed size to a file or to a child process.
+pub struct VideoWriter {
+    writer: Box<dyn Write>,
+    child: Option<Child>,
+    format: VideoFormat,
+    dimensions: (usize, usize),
+    pub frames: usize
+}
+
+impl VideoWriter {
+    /// Writes Y4M to `writer`.
+    pub fn y4m(mut writer: Box<dyn Write>, dimensions: (usize, usize), frame_rate: FrameRate) -> std::io::Result<Self> {
+        writer.write_all(y4m_header(dimensions, frame_rate).as_bytes())?;
+        Ok(Self { writer, child: None, format: VideoFormat::Y4m, dimensions, frames: 0 })
+    }
+
+    pub fn y4m_file(path: impl AsRef<Path>, dimensions: (usize, usize), frame_rate: FrameRate) -> std::io::Result<Self> {
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        Self::y4m(Box::new(file), dimensions, frame_rate)
+    }
+
+    /// Spawns `command` and writes the frames to its standard input, as `format`.
+    pub fn pipe(command: &mut Command, format: VideoFormat, dimensions: (usize, usize), frame_rate: FrameRate) -> std::io::Result<Self> {
+        let mut child = command.stdin(Stdio::piped()).spawn()?;
+        let stdin = child.stdin.take().expect("The standard input is piped.");
+        let writer: Box<dyn Write> = Box::new(stdin);
+        let mut video = match format {
+            VideoFormat::Y4m    => Self::y4m(writer, dimensions, frame_rate)?,
+            VideoFormat::RawRgb => Self { writer, child: None, format, dimensions, frames: 0 }
+        };
+        video.child = Some(child);
+        Ok(video)
+    }
+
+    /// Encodes to `output` with the `ffmpeg` on the path.
+    pub fn ffmpeg(output: impl AsRef<Path>, dimensions: (usize, usize), frame_rate: FrameRate) -> std::io::Result<Self> {
+        let mut command = Command::new("ffmpeg");
+        command
+            .args(["-y", "-loglevel", "error", "-f", "rawvideo", "-pix_fmt", "rgb24"])
+            .args(["-s", &format!("{}x{}", dimensions.0, dimensions.1)])
+            .args(["-r", &format!("{}/{}", frame_rate.numerator, frame_rate.denominator)])
+            .args(["-i", "-", "-pix_fmt", "yuv420p"])
+            .arg(output.as_ref());
+        Self::pipe(&mut command, VideoFormat::RawRgb, dimensions, frame_rate)
+    }
+
+    /// Writes RGBA8 `pixels`, top row first.
+    pub fn write_frame(&mut self, pixels: &[u8]) -> std::io::Result<()> {
+        assert_eq!(pixels.len(), self.dimensions.0 * self.dimensions.1 * 4, "The frame must be RGBA8 with the video dimensions.");
+        match self.format {
+            VideoFormat::Y4m => {
+                self.writer.write_all(b"FRAME\n")?;
+                self.writer.write_all(&rgba_to_yuv444(pixels))?;
+            },
+            VideoFormat::RawRgb => self.writer.write_all(&rgba_to_rgb(pixels))?
+        }
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Writes what was drawn on the default framebuffer. Call it before `Context::present`.
+    /// Fails with `ErrorKind::InvalidInput` if the window was resized since the recording started.
+    pub fn capture(&mut self, context: &Context) -> std::io::Result<()> {
+        if context.dimensions != self.dimensions {
+            let message = format!("The window was resized to {}x{} during the {}x{} recording.", context.dimensions.0, context.dimensions.1, self.dimensions.0, self.dimensions.1);
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, message));
+        }
+        self.write_frame(&read_framebuffer(context))
+    }
+
+    /// Flushes the frames and waits for the child process, if any.
+    pub fn finish(self) -> std::io::Result<()> {
+        let Self { mut writer, child, .. } = self;
+        writer.flush()?;
+        // Closing the standard input ends the child's stream.
+        drop(writer);
+        if let Some(mut child) = child {
+            let status = child.wait()?;
+            if !status.success() {
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, format!("The encoder exited with {}.", status)));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn frame_rates() {
+        assert_eq!(FrameRate::from_time_step(1.0 / 60.0, 1), FrameRate { numerator: 60, denominator: 1 });
+        assert_eq!(FrameRate::from_time_step(1.0 / 60.0, 2), FrameRate { numerator: 30, denominator: 1 });
+        assert_eq!(FrameRate::from_time_step(0.04, 1), FrameRate { numerator: 25, denominator: 1 });
+        assert_eq!(FrameRate::from_time_step(0.3, 1), FrameRate { numerator: 10, denominator: 3 });
+    }
+
+    #[test]
+    fn y4m() {
+        assert_eq!(y4m_header((4, 2), FrameRate { numerator: 30, denominator: 1 }), "YUV4MPEG2 W4 H2 F30:1 Ip A1:1 C444\n");
+        let pixels = vec![255, 255, 255, 255, /**/ 0, 0, 0, 255];
+        assert_eq!(rgba_to_yuv444(&pixels), vec![235, 16, /**/ 128, 128, /**/ 128, 128]);
+        assert_eq!(rgba_to_rgb(&pixels), vec![255, 255, 255, 0, 0, 0]);
+    }
+
+    #[test]
+    fn resized_window() {
+        let context = Context::headless((4, 4)).expect("Couldn't create the headless Context.");
+        let mut video = VideoWriter::y4m(Box::new(std::io::sink()), (2, 2), FrameRate { numerator: 30, denominator: 1 }).unwrap();
+        let error = video.capture(&context).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        assert_eq!(video.frames, 0);
+    }
+}
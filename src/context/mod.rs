@@ -1,6 +1,10 @@
+use crate::input::Input;
+
 pub struct Context {
     pub context: gpu::Context,
-    pub dimensions: (usize, usize)
+    pub dimensions: (usize, usize),
+    /// Where the frames are drawn: the window, or an offscreen color texture for the headless contexts.
+    pub framebuffer: gpu::Framebuffer
 }
 
 impl Context {
@@ -8,11 +12,55 @@ impl Context {
         let display = gpu::ContextDisplay::Window("Fluid".to_string(), dimensions.0, dimensions.1);
         let context = gpu::ContextBuilder::new().with_display(display).build();
         context.make_current().ok();
+        let framebuffer = gpu::Framebuffer::default(&context);
+
+        Self { context, dimensions, framebuffer }
+    }
+
+    /// A context without a window, for batch renders. The frames are drawn on an offscreen framebuffer of `dimensions`
+    /// pixels and read back with `capture::read_framebuffer`.
+    pub fn headless(dimensions: (usize, usize)) -> Result<Self, String> {
+        let display = gpu::ContextDisplay::None;
+        let context = gpu::ContextBuilder::new().with_display(display).build();
+        context.make_current().map_err(|error| format!("Couldn't make the headless context current: {:?}", error))?;
+        let format = gpu::TextureFormat::new(gpu::ColorFormat::components(4), gpu::Type::F32);
+        let color = gpu::Texture2D::allocate(&context, dimensions, &format);
+        let framebuffer = gpu::Framebuffer::new(&context, Some(color), None, None)?;
+        // Without a window there is no resize to set the viewport.
+        unsafe {
+            gl::Viewport(0, 0, dimensions.0 as i32, dimensions.1 as i32);
+        }
 
-        Self { context, dimensions }
+        Ok(Self { context, dimensions, framebuffer })
+    }
+
+    /// Runs the event loop, forwarding the window events to `input`. Returns false when the window is closed.
+    pub fn run(&mut self, input: &mut Input) -> bool {
+        input.clear();
+        let mut resized = None;
+        let running = self.context.run_with(|event| {
+            if let glutin::WindowEvent::Resized(size) = event {
+                resized = Some((size.width as usize, size.height as usize));
+            }
+            input.handle(event)
+        });
+        if let Some(dimensions) = resized {
+            self.resize(dimensions);
+        }
+        running
+    }
+
+    /// Follows the window size, the `Presenter` fits the field to it.
+    fn resize(&mut self, dimensions: (usize, usize)) {
+        self.dimensions = dimensions;
+        //FIXME: How to expose it on the GPU API?
+        // Ref: https://www.khronos.org/registry/OpenGL-Refpages/gl4/html/glViewport.xhtml
+        unsafe {
+            gl::Viewport(0, 0, dimensions.0 as i32, dimensions.1 as i32);
+        }
     }
 
     pub fn present(&mut self) {
         self.context.swap_buffers().ok();
     }
-}
\ No newline at end of file
+}
@@ -16,4 +16,22 @@ impl Field {
         let field = gpu::Texture2D::from_data(&context.context, size, &format, data, &format);
         Self { field }
     }
+
+    /// Evaluates `value` for every cell coordinate, bottom row first, and uploads its `dimension` components.
+    pub fn from_fn<V: AsRef<[f32]>>(context: &Context, size: (usize, usize), dimension: usize, value: impl Fn(usize, usize) -> V) -> Self {
+        Self::from_data(context, size, dimension, &Self::data_from_fn(size, dimension, value))
+    }
+
+    /// The interleaved data of `from_fn`, to fill a field on the CPU.
+    pub fn data_from_fn<V: AsRef<[f32]>>(size: (usize, usize), dimension: usize, value: impl Fn(usize, usize) -> V) -> Vec<f32> {
+        let mut data = Vec::with_capacity(size.0 * size.1 * dimension);
+        for y in 0 .. size.1 {
+            for x in 0 .. size.0 {
+                let value = value(x, y);
+                assert_eq!(value.as_ref().len(), dimension, "Every cell must have {} components.", dimension);
+                data.extend_from_slice(value.as_ref());
+            }
+        }
+        data
+    }
 }
\ No newline at end of file
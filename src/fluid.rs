@@ -1,12 +1,41 @@
 use crate::Context;
+use crate::field::Field;
+
+/// How the per cell viscosity responds to the flow.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ViscosityModel {
+    /// The viscosity field is kept as it was set.
+    Newtonian,
+    /// `consistency * strain_rate^(flow_index - 1)`. Shear-thinning if `flow_index < 1`, shear-thickening if `flow_index > 1`.
+    PowerLaw { consistency: f32, flow_index: f32, min_viscosity: f32, max_viscosity: f32 },
+    /// `plastic_viscosity + yield_stress / strain_rate`, capped at `max_viscosity` where the fluid behaves like a solid.
+    Bingham { plastic_viscosity: f32, yield_stress: f32, max_viscosity: f32 }
+}
 
 pub struct Fluid {
     pub velocity_field: gpu::Texture2D,
     pub previous_velocity_field: gpu::Texture2D,
     pub density_field: gpu::Texture2D,
     pub previous_density_field: gpu::Texture2D,
+    /// A passive scalar like the density, added by the `EmitterKind::Source` emitters.
+    pub temperature_field: gpu::Texture2D,
+    pub previous_temperature_field: gpu::Texture2D,
+    pub obstacle_field: gpu::Texture2D,
     pub viscosity: f32,
     pub diffusion: f32,
+    /// Exponential decay rate of the density, per second.
+    pub density_dissipation: f32,
+    /// Exponential decay rate of the velocity, per second.
+    pub velocity_dissipation: f32,
+    /// Exponential decay rate of the temperature, per second.
+    pub temperature_dissipation: f32,
+    /// Optional per cell decay rate added to all the dissipations. See `simulator::sponge_mask`.
+    pub damping_mask: Option<gpu::Texture2D>,
+    /// Optional per cell viscosity. When it's set, it's used instead of `viscosity`.
+    pub viscosity_field: Option<gpu::Texture2D>,
+    pub viscosity_model: ViscosityModel,
+    /// Signed distance to the liquid surface, negative inside the liquid. When it's set, the simulator runs in liquid mode.
+    pub level_set_field: Option<gpu::Texture2D>,
     pub dimensions: (usize, usize)
 }
 
@@ -23,8 +52,38 @@ impl Fluid {
         let density_field_format = gpu::TextureFormat::new(color_format, component_type);
         let density_field = gpu::Texture2D::allocate(&context.context, dimensions, &density_field_format);
         let previous_density_field = gpu::Texture2D::allocate(&context.context, dimensions, &density_field_format);
+        let temperature_field = gpu::Texture2D::allocate(&context.context, dimensions, &density_field_format);
+        let previous_temperature_field = gpu::Texture2D::allocate(&context.context, dimensions, &density_field_format);
+        let obstacle_field = gpu::Texture2D::allocate(&context.context, dimensions, &density_field_format);
+
+        let density_dissipation = 0.0;
+        let velocity_dissipation = 0.0;
+        let temperature_dissipation = 0.0;
+        let damping_mask = None;
+        let viscosity_field = None;
+        let viscosity_model = ViscosityModel::Newtonian;
+        let level_set_field = None;
+
+        Self { velocity_field, previous_velocity_field, density_field, previous_density_field, temperature_field, previous_temperature_field, obstacle_field, diffusion, viscosity, density_dissipation, velocity_dissipation, temperature_dissipation, damping_mask, viscosity_field, viscosity_model, level_set_field, dimensions }
+    }
+
+    /// Sets the per cell viscosity by evaluating `viscosity` for every cell coordinate.
+    pub fn set_viscosity_field(&mut self, context: &Context, viscosity: impl Fn(usize, usize) -> f32) {
+        self.viscosity_field = Some(Field::from_fn(context, self.dimensions, 1, |x, y| [viscosity(x, y)]).field);
+    }
+
+    /// Sets a strain rate dependent `model`. The viscosity field is recomputed from the velocity on every step.
+    pub fn set_viscosity_model(&mut self, context: &Context, model: ViscosityModel) {
+        if self.viscosity_field.is_none() {
+            let viscosity = self.viscosity;
+            self.set_viscosity_field(context, |_, _| viscosity);
+        }
+        self.viscosity_model = model;
+    }
 
-        Self { velocity_field, previous_velocity_field, density_field, previous_density_field, diffusion, viscosity, dimensions }
+    /// Switches to liquid mode, evaluating the signed distance `level_set` for every cell coordinate.
+    pub fn set_level_set(&mut self, context: &Context, level_set: impl Fn(usize, usize) -> f32) {
+        self.level_set_field = Some(Field::from_fn(context, self.dimensions, 1, |x, y| [level_set(x, y)]).field);
     }
 
     pub fn inner_volume(&self) -> f32 {
@@ -0,0 +1,10 @@
+#version 460
+
+layout(r32f, location = 0) uniform image2D field;
+
+layout(local_size_x = 1, local_size_y = 1, local_size_z = 1 ) in;
+
+void main() {
+    ivec2 coord = ivec2(gl_GlobalInvocationID.xy);
+    imageStore(field, coord, vec4(0.0));
+}
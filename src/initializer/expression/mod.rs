@@ -0,0 +1,170 @@
+//! Initializers from user GLSL expressions, wrapped into a compute shader at runtime.
+//! The expressions see the cell position `p`, the normalized position `uv` and the field `resolution`.
+
+use crate::Context;
+use std::fmt;
+
+const FIELD_LOCATION         : usize = 0;
+const RESOLUTION_LOCATION    : usize = 1;
+const FIRST_UNIFORM_LOCATION : usize = 2;
+
+const RESERVED_NAMES: [&str; 6] = ["p", "uv", "resolution", "field", "coord", "main"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExpressionKind {
+    Scalar,
+    Vector
+}
+
+impl ExpressionKind {
+    /// The image format, the value type and the padding to a `vec4`.
+    fn format(&self) -> (&'static str, &'static str, &'static str) {
+        match self {
+            ExpressionKind::Scalar => ("r32f", "float", "vec3(0.0)"),
+            ExpressionKind::Vector => ("rg32f", "vec2", "vec2(0.0)")
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Uniform {
+    Float(f32),
+    Vec2(f32, f32)
+}
+
+impl Uniform {
+    fn glsl_type(&self) -> &'static str {
+        match self {
+            Uniform::Float(_)   => "float",
+            Uniform::Vec2(_, _) => "vec2"
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExpressionError {
+    /// A uniform name that isn't a GLSL identifier, is reserved or is declared twice.
+    InvalidUniform(String),
+    /// The GLSL compiler log. Its line numbers count from the first line of the expression.
+    Compilation { expression: String, log: String }
+}
+
+impl fmt::Display for ExpressionError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExpressionError::InvalidUniform(name) => write!(formatter, "Invalid uniform name {:?}, reserved names are {}.", name, RESERVED_NAMES.join(", ")),
+            ExpressionError::Compilation { expression, log } => {
+                writeln!(formatter, "Couldn't compile the expression:")?;
+                for (index, line) in expression.lines().enumerate() {
+                    writeln!(formatter, "{:4} | {}", index + 1, line)?;
+                }
+                write!(formatter, "{}", log.trim_end())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ExpressionError {}
+
+fn is_identifier(name: &str) -> bool {
+    let mut characters = name.chars();
+    match characters.next() {
+        Some(first) if first.is_ascii_alphabetic() || first == '_' => characters.all(|character| character.is_ascii_alphanumeric() || character == '_'),
+        _ => false
+    }
+}
+
+/// The compute shader storing `expression` for every cell, with the `uniforms` declared from `FIRST_UNIFORM_LOCATION` on.
+pub fn shader_source(kind: ExpressionKind, expression: &str, uniforms: &[(&str, Uniform)]) -> Result<String, ExpressionError> {
+    for (index, (name, _)) in uniforms.iter().enumerate() {
+        // GLSL reserves the gl_ prefix and names with double underscores.
+        if !is_identifier(name) || RESERVED_NAMES.contains(name) || name.starts_with("gl_") || name.contains("__") || uniforms[.. index].iter().any(|(other, _)| other == name) {
+            return Err(ExpressionError::InvalidUniform(name.to_string()));
+        }
+    }
+    let (image_format, value_type, padding) = kind.format();
+    let mut source = String::from("#version 460\n\n");
+    source += &format!("layout({}, location = {}) uniform image2D field;\n", image_format, FIELD_LOCATION);
+    source += &format!("layout(location = {}) uniform vec2 resolution;\n", RESOLUTION_LOCATION);
+    for (index, (name, uniform)) in uniforms.iter().enumerate() {
+        source += &format!("layout(location = {}) uniform {} {};\n", FIRST_UNIFORM_LOCATION + index, uniform.glsl_type(), name);
+    }
+    source += "\nlayout(local_size_x = 1, local_size_y = 1, local_size_z = 1 ) in;\n\n";
+    source += "void main() {\n";
+    source += "    ivec2 coord = ivec2(gl_GlobalInvocationID.xy);\n";
+    source += "    vec2 p = vec2(coord);\n";
+    source += "    vec2 uv = p / max(resolution - 1.0, vec2(1.0));\n";
+    source += &format!("    {} value = (\n#line 1\n{}\n    );\n", value_type, expression);
+    source += &format!("    imageStore(field, coord, vec4(value, {}));\n", padding);
+    source += "}\n";
+    Ok(source)
+}
+
+/// A compiled expression with its uniform values.
+pub struct ExpressionProgram {
+    program: gpu::ComputeProgram,
+    uniforms: Vec<(String, Uniform)>
+}
+
+impl ExpressionProgram {
+    pub fn new(context: &Context, kind: ExpressionKind, expression: &str, uniforms: &[(&str, Uniform)]) -> Result<Self, ExpressionError> {
+        let source = shader_source(kind, expression, uniforms)?;
+        let compilation_error = |log: String| ExpressionError::Compilation { expression: expression.to_string(), log };
+        let shader = gpu::ComputeShader::new(&context.context, &source).map_err(|error| compilation_error(format!("{:?}", error)))?;
+        let program = gpu::ComputeProgram::new(&context.context, &shader).map_err(|error| compilation_error(format!("{:?}", error)))?;
+        let uniforms = uniforms.iter().map(|(name, uniform)| (name.to_string(), *uniform)).collect();
+        Ok(Self { program, uniforms })
+    }
+
+    /// Changes the value of the uniform `name` without recompiling. It must keep its type.
+    pub fn set_uniform(&mut self, name: &str, uniform: Uniform) -> Result<(), ExpressionError> {
+        match self.uniforms.iter_mut().find(|(other, value)| other == name && value.glsl_type() == uniform.glsl_type()) {
+            Some((_, value)) => {
+                *value = uniform;
+                Ok(())
+            },
+            None => Err(ExpressionError::InvalidUniform(name.to_string()))
+        }
+    }
+
+    pub fn fill(&self, field: &gpu::Texture2D) {
+        let dimensions = field.dimensions();
+        self.program.bind_image_2d(field, FIELD_LOCATION);
+        self.program.bind_vec2((dimensions.0 as f32, dimensions.1 as f32), RESOLUTION_LOCATION);
+        for (index, (_, uniform)) in self.uniforms.iter().enumerate() {
+            match *uniform {
+                Uniform::Float(value)   => self.program.bind_f32(value, FIRST_UNIFORM_LOCATION + index),
+                Uniform::Vec2(x, y)   => self.program.bind_vec2((x, y), FIRST_UNIFORM_LOCATION + index)
+            }
+        }
+        self.program.compute((dimensions.0, dimensions.1, 1));
+        //FIXME: How to expose it on the GPU API?
+        // Ref: https://www.khronos.org/registry/OpenGL-Refpages/gl4/html/glMemoryBarrier.xhtml
+        unsafe {
+            gl::MemoryBarrier(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn source() {
+        let source = shader_source(ExpressionKind::Scalar, "exp(-dot(p-c,p-c)/r)", &[("c", Uniform::Vec2(128.0, 128.0)), ("r", Uniform::Float(64.0))]).unwrap();
+        assert!(source.contains("layout(r32f, location = 0) uniform image2D field;"));
+        assert!(source.contains("layout(location = 2) uniform vec2 c;\nlayout(location = 3) uniform float r;"));
+        assert!(source.contains("float value = (\n#line 1\nexp(-dot(p-c,p-c)/r)\n    );"));
+        let source = shader_source(ExpressionKind::Vector, "vec2(-p.y, p.x)", &[]).unwrap();
+        assert!(source.contains("rg32f") && source.contains("vec4(value, vec2(0.0))"));
+    }
+
+    #[test]
+    fn invalid_uniforms() {
+        for name in &["p", "2r", "gl_r", "a__b", "", "r-1"] {
+            assert_eq!(shader_source(ExpressionKind::Scalar, "r", &[(name, Uniform::Float(1.0))]), Err(ExpressionError::InvalidUniform(name.to_string())));
+        }
+        assert!(shader_source(ExpressionKind::Scalar, "r", &[("r", Uniform::Float(1.0)), ("r", Uniform::Float(2.0))]).is_err());
+    }
+}
@@ -0,0 +1,167 @@
+//! Images used as initial conditions: PNG, and binary PGM and PPM.
+
+use crate::capture::png;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+/// Which part of the image a scalar field is read from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Channel {
+    Red,
+    Green,
+    Blue,
+    Alpha,
+    /// Rec. 709 luma of the colour channels.
+    Luminance
+}
+
+/// An image with samples normalized to [0, 1], top row first.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    pub dimensions: (usize, usize),
+    pub channels: usize,
+    pub data: Vec<f32>
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message.to_string())
+}
+
+/// Decodes binary PGM (P5) and PPM (P6) with 8 or 16-bit samples.
+fn decode_pnm(bytes: &[u8]) -> std::io::Result<Image> {
+    let channels = match &bytes[.. 2] {
+        b"P5" => 1,
+        b"P6" => 3,
+        _ => return Err(invalid("Only binary PGM and PPM are supported."))
+    };
+    // The header is the magic, the width, the height and the maximum value, separated by whitespace and comments.
+    let mut fields = Vec::new();
+    let mut offset = 2;
+    while fields.len() < 3 {
+        while offset < bytes.len() && (bytes[offset].is_ascii_whitespace() || bytes[offset] == b'#') {
+            if bytes[offset] == b'#' {
+                while offset < bytes.len() && bytes[offset] != b'\n' { offset += 1; }
+            } else {
+                offset += 1;
+            }
+        }
+        let start = offset;
+        while offset < bytes.len() && bytes[offset].is_ascii_digit() { offset += 1; }
+        let field = std::str::from_utf8(&bytes[start .. offset]).ok().and_then(|field| field.parse::<usize>().ok());
+        fields.push(field.ok_or_else(|| invalid("The PNM header is malformed."))?);
+    }
+    // A single whitespace separates the header from the samples.
+    offset += 1;
+    let (width, height, maximum) = (fields[0], fields[1], fields[2]);
+    let count = width * height * channels;
+    let data = if maximum < 256 {
+        bytes.get(offset .. offset + count).ok_or_else(|| invalid("The PNM data is truncated."))?
+            .iter().map(|sample| *sample as f32 / maximum as f32).collect()
+    } else {
+        bytes.get(offset .. offset + count * 2).ok_or_else(|| invalid("The PNM data is truncated."))?
+            .chunks(2).map(|sample| u16::from_be_bytes([sample[0], sample[1]]) as f32 / maximum as f32).collect()
+    };
+    Ok(Image { dimensions: (width, height), channels, data })
+}
+
+impl Image {
+    pub fn decode(bytes: &[u8]) -> std::io::Result<Self> {
+        if bytes.starts_with(b"\x89PNG") {
+            let image = png::decode(bytes)?;
+            Ok(Self { dimensions: image.dimensions, channels: image.channels, data: image.data })
+        } else if bytes.len() > 2 {
+            decode_pnm(bytes)
+        } else {
+            Err(invalid("Unknown image format."))
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Self::decode(&std::fs::read(path)?)
+    }
+
+    /// The value of `channel` at a pixel. Missing channels read as the gray value, or one for the alpha.
+    fn sample(&self, x: usize, y: usize, channel: Channel) -> f32 {
+        let pixel = &self.data[(y * self.dimensions.0 + x) * self.channels ..][.. self.channels];
+        let has_alpha = self.channels == 2 || self.channels == 4;
+        let color = |index: usize| if self.channels >= 3 { pixel[index] } else { pixel[0] };
+        match channel {
+            Channel::Red       => color(0),
+            Channel::Green     => color(1),
+            Channel::Blue      => color(2),
+            Channel::Alpha     => if has_alpha { pixel[self.channels - 1] } else { 1.0 },
+            Channel::Luminance => 0.2126 * color(0) + 0.7152 * color(1) + 0.0722 * color(2)
+        }
+    }
+
+    /// The channels of an encoded velocity: red and green, or gray and alpha for the two channel images.
+    pub fn velocity_channels(&self) -> (Channel, Channel) {
+        if self.channels == 2 { (Channel::Red, Channel::Alpha) } else { (Channel::Red, Channel::Green) }
+    }
+
+    /// Bilinearly resamples `channel` to a field of `dimensions`, bottom row first like the textures.
+    pub fn resample(&self, dimensions: (usize, usize), channel: Channel) -> Vec<f32> {
+        let (width, height) = self.dimensions;
+        let mut field = Vec::with_capacity(dimensions.0 * dimensions.1);
+        for y in 0 .. dimensions.1 {
+            // The field's bottom row is the image's last row.
+            let v = (1.0 - (y as f32 + 0.5) / dimensions.1 as f32) * height as f32 - 0.5;
+            let v = v.clamp(0.0, (height - 1) as f32);
+            for x in 0 .. dimensions.0 {
+                let u = ((x as f32 + 0.5) / dimensions.0 as f32 * width as f32 - 0.5).clamp(0.0, (width - 1) as f32);
+                let (x0, y0) = (u.floor() as usize, v.floor() as usize);
+                let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+                let (tx, ty) = (u - x0 as f32, v - y0 as f32);
+                let top = self.sample(x0, y0, channel) * (1.0 - tx) + self.sample(x1, y0, channel) * tx;
+                let bottom = self.sample(x0, y1, channel) * (1.0 - tx) + self.sample(x1, y1, channel) * tx;
+                field.push(top * (1.0 - ty) + bottom * ty);
+            }
+        }
+        field
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn pgm() {
+        let image = Image::decode(b"P5\n# comment\n2 2\n255\n\x00\xff\x33\x66").unwrap();
+        assert_eq!(image.dimensions, (2, 2));
+        assert_eq!(image.data, vec![0.0, 1.0, 0.2, 0.4]);
+    }
+
+    #[test]
+    fn resampling_flips_and_interpolates() {
+        // Top row 0 and 1, bottom row 2 and 3.
+        let image = Image { dimensions: (2, 2), channels: 1, data: vec![0.0, 1.0, 2.0, 3.0] };
+        assert_eq!(image.resample((2, 2), Channel::Red), vec![2.0, 3.0, 0.0, 1.0]);
+        let field = image.resample((4, 1), Channel::Luminance);
+        // The single row samples the middle of the image: 1.0 to 2.0, interpolated along x and clamped on the sides.
+        let expected = [1.0, 1.25, 1.75, 2.0];
+        for (value, expected) in field.iter().zip(&expected) {
+            assert!((value - expected).abs() < 1e-5, "{:?}", field);
+        }
+    }
+
+    #[test]
+    fn channels() {
+        let image = Image { dimensions: (1, 1), channels: 4, data: vec![0.1, 0.2, 0.3, 0.5] };
+        assert_eq!(image.resample((1, 1), Channel::Blue), vec![0.3]);
+        assert_eq!(image.resample((1, 1), Channel::Alpha), vec![0.5]);
+        let gray = Image { dimensions: (1, 1), channels: 1, data: vec![0.25] };
+        assert_eq!(gray.resample((1, 1), Channel::Green), vec![0.25]);
+        assert_eq!(gray.resample((1, 1), Channel::Alpha), vec![1.0]);
+    }
+
+    #[test]
+    fn velocity_channels() {
+        let gray_alpha = Image { dimensions: (1, 1), channels: 2, data: vec![0.25, 0.75] };
+        let (x, y) = gray_alpha.velocity_channels();
+        assert_eq!((gray_alpha.resample((1, 1), x), gray_alpha.resample((1, 1), y)), (vec![0.25], vec![0.75]));
+        let rgb = Image { dimensions: (1, 1), channels: 3, data: vec![0.25, 0.5, 0.75] };
+        let (x, y) = rgb.velocity_channels();
+        assert_eq!((rgb.resample((1, 1), x), rgb.resample((1, 1), y)), (vec![0.25], vec![0.5]));
+    }
+}
@@ -1,9 +1,19 @@
+mod expression;
+mod image;
+mod presets;
+
 use crate::Context;
+use crate::field::Field;
 use crate::fluid::Fluid;
 
+pub use expression::{ExpressionError, ExpressionKind, ExpressionProgram, Uniform};
+pub use image::{Image, Channel};
+pub use presets::{Preset, PRESET_NAMES};
+
 pub struct Initializer {
     pub scalar: gpu::ComputeProgram,
-    pub vector: gpu::ComputeProgram
+    pub vector: gpu::ComputeProgram,
+    pub clear_scalar: gpu::ComputeProgram
 }
 
 impl Initializer {
@@ -12,7 +22,9 @@ impl Initializer {
         let scalar = gpu::ComputeProgram::new(&context.context, &compute_shader).expect("Couldn't create ComputeProgram.");
         let compute_shader = gpu::ComputeShader::new(&context.context, include_str!("initialize_vector.glsl")).expect("Couldn't create ComputeShader.");
         let vector = gpu::ComputeProgram::new(&context.context, &compute_shader).expect("Couldn't create ComputeProgram.");
-        Self { scalar, vector }
+        let compute_shader = gpu::ComputeShader::new(&context.context, include_str!("clear_scalar.glsl")).expect("Couldn't create ComputeShader.");
+        let clear_scalar = gpu::ComputeProgram::new(&context.context, &compute_shader).expect("Couldn't create ComputeProgram.");
+        Self { scalar, vector, clear_scalar }
     }
 
     pub fn initialize(&mut self, fluid: &mut Fluid) {
@@ -20,6 +32,38 @@ impl Initializer {
         self.initialize_vector_field(&fluid.velocity_field);
         self.initialize_scalar_field(&fluid.previous_density_field);
         self.initialize_vector_field(&fluid.previous_velocity_field);
+        self.clear_scalar_field(&fluid.temperature_field);
+        self.clear_scalar_field(&fluid.previous_temperature_field);
+        self.clear_scalar_field(&fluid.obstacle_field);
+    }
+
+    /// Sets the density and the velocity from `preset` and clears the temperature and the obstacles.
+    pub fn apply_preset(&mut self, context: &Context, fluid: &mut Fluid, preset: &Preset) {
+        let (density, velocity) = preset.fields(fluid.dimensions);
+        fluid.density_field = Field::from_data(context, fluid.dimensions, 1, &density).field;
+        fluid.previous_density_field = Field::from_data(context, fluid.dimensions, 1, &density).field;
+        fluid.velocity_field = Field::from_data(context, fluid.dimensions, 2, &velocity).field;
+        fluid.previous_velocity_field = Field::from_data(context, fluid.dimensions, 2, &velocity).field;
+        self.clear_scalar_field(&fluid.temperature_field);
+        self.clear_scalar_field(&fluid.previous_temperature_field);
+        self.clear_scalar_field(&fluid.obstacle_field);
+    }
+
+    /// Sets the density from a GLSL float `expression` of the cell position `p`, `uv`, `resolution` and the `uniforms`.
+    /// For example `exp(-dot(p - c, p - c) / r)` with the uniforms `c` and `r`.
+    pub fn density_from_expression(&mut self, context: &Context, fluid: &mut Fluid, expression: &str, uniforms: &[(&str, Uniform)]) -> Result<(), ExpressionError> {
+        let program = ExpressionProgram::new(context, ExpressionKind::Scalar, expression, uniforms)?;
+        program.fill(&fluid.density_field);
+        program.fill(&fluid.previous_density_field);
+        Ok(())
+    }
+
+    /// Sets the velocity from a GLSL vec2 `expression`, like `density_from_expression`.
+    pub fn velocity_from_expression(&mut self, context: &Context, fluid: &mut Fluid, expression: &str, uniforms: &[(&str, Uniform)]) -> Result<(), ExpressionError> {
+        let program = ExpressionProgram::new(context, ExpressionKind::Vector, expression, uniforms)?;
+        program.fill(&fluid.velocity_field);
+        program.fill(&fluid.previous_velocity_field);
+        Ok(())
     }
 
     pub fn initialize_scalar_field(&mut self, field: &gpu::Texture2D) {
@@ -45,4 +89,39 @@ impl Initializer {
             gl::MemoryBarrier(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
         }
     }
-}
\ No newline at end of file
+
+    pub fn clear_scalar_field(&mut self, field: &gpu::Texture2D) {
+        let dimensions = field.dimensions();
+        let dimensions = (dimensions.0, dimensions.1, 1);
+        self.clear_scalar.bind_image_2d(field, 0);
+        self.clear_scalar.compute(dimensions);
+        //FIXME: How to expose it on the GPU API?
+        // Ref: https://www.khronos.org/registry/OpenGL-Refpages/gl4/html/glMemoryBarrier.xhtml
+        unsafe {
+            gl::MemoryBarrier(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+        }
+    }
+
+    /// Sets the density from `channel` of `image`, resampled to the field.
+    pub fn density_from_image(&mut self, context: &Context, fluid: &mut Fluid, image: &Image, channel: Channel) {
+        let data = image.resample(fluid.dimensions, channel);
+        fluid.density_field = Field::from_data(context, fluid.dimensions, 1, &data).field;
+        fluid.previous_density_field = Field::from_data(context, fluid.dimensions, 1, &data).field;
+    }
+
+    /// Sets obstacles where `channel` of `image` is over `threshold`.
+    pub fn obstacles_from_image(&mut self, context: &Context, fluid: &mut Fluid, image: &Image, channel: Channel, threshold: f32) {
+        let data: Vec<f32> = image.resample(fluid.dimensions, channel).iter().map(|value| if *value > threshold { 1.0 } else { 0.0 }).collect();
+        fluid.obstacle_field = Field::from_data(context, fluid.dimensions, 1, &data).field;
+    }
+
+    /// Decodes the `Image::velocity_channels` of `image` as the velocity, mapping [0, 1] to [-scale, scale].
+    pub fn velocity_from_image(&mut self, context: &Context, fluid: &mut Fluid, image: &Image, scale: f32) {
+        let (x_channel, y_channel) = image.velocity_channels();
+        let x = image.resample(fluid.dimensions, x_channel);
+        let y = image.resample(fluid.dimensions, y_channel);
+        let data: Vec<f32> = x.iter().zip(&y).flat_map(|(x, y)| vec![(x * 2.0 - 1.0) * scale, (y * 2.0 - 1.0) * scale]).collect();
+        fluid.velocity_field = Field::from_data(context, fluid.dimensions, 2, &data).field;
+        fluid.previous_velocity_field = Field::from_data(context, fluid.dimensions, 2, &data).field;
+    }
+}
@@ -0,0 +1,241 @@
+//! Named initial conditions. Positions are in cells, velocities in cells per second.
+
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Preset {
+    /// The dye checkerboard and wavy flow of `initialize_scalar.glsl` and `initialize_vector.glsl`.
+    Checkerboard { size: f32, speed: f32 },
+    TaylorGreen { amplitude: f32, wavelength: f32 },
+    /// A `tanh` shear layer through the middle, with a sinusoidal vertical perturbation.
+    KelvinHelmholtz { speed: f32, thickness: f32, perturbation: f32, wavelength: f32 },
+    /// Heavy dye over light dye, with a perturbed interface. It needs a downward `Force` on the dye to develop.
+    RayleighTaylor { perturbation: f32, wavelength: f32 },
+    /// A fluid at rest with the top row moving. The lid has to be kept moving, by a force or an emitter.
+    LidDrivenCavity { lid_speed: f32 },
+    /// Two Lamb-Oseen vortices of opposite circulation, side by side so they travel upwards: clockwise on the right.
+    VortexPair { circulation: f32, core_radius: f32, separation: f32 },
+    LambOseen { circulation: f32, core_radius: f32 },
+    Uniform { velocity_x: f32, velocity_y: f32 }
+}
+
+pub const PRESET_NAMES: [&str; 8] = ["checkerboard", "taylor_green", "kelvin_helmholtz", "rayleigh_taylor", "lid_driven_cavity", "vortex_pair", "lamb_oseen", "uniform"];
+
+/// The azimuthal velocity of a Lamb-Oseen vortex centered on `center`.
+fn lamb_oseen(position: (f32, f32), center: (f32, f32), circulation: f32, core_radius: f32) -> (f32, f32) {
+    let (dx, dy) = (position.0 - center.0, position.1 - center.1);
+    let radius_squared = dx * dx + dy * dy;
+    if radius_squared == 0.0 {
+        return (0.0, 0.0);
+    }
+    // v = circulation / (2 pi r) * (1 - exp(-r^2 / rc^2)), along (-dy, dx) / r.
+    let factor = circulation / (2.0 * PI * radius_squared) * (1.0 - (-radius_squared / (core_radius * core_radius)).exp());
+    (-dy * factor, dx * factor)
+}
+
+fn blob(position: (f32, f32), center: (f32, f32), radius: f32) -> f32 {
+    let (dx, dy) = (position.0 - center.0, position.1 - center.1);
+    (-(dx * dx + dy * dy) / (radius * radius)).exp()
+}
+
+// GLSL's sign, which is zero at zero, unlike f32::signum.
+fn sign(value: f32) -> f32 {
+    if value > 0.0 { 1.0 } else if value < 0.0 { -1.0 } else { 0.0 }
+}
+
+fn stripes(coordinate: f32, period: f32) -> f32 {
+    if (coordinate / period).floor() as i32 % 2 == 0 { 1.0 } else { 0.0 }
+}
+
+impl Preset {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Preset::Checkerboard { .. }    => "checkerboard",
+            Preset::TaylorGreen { .. }     => "taylor_green",
+            Preset::KelvinHelmholtz { .. } => "kelvin_helmholtz",
+            Preset::RayleighTaylor { .. }  => "rayleigh_taylor",
+            Preset::LidDrivenCavity { .. } => "lid_driven_cavity",
+            Preset::VortexPair { .. }      => "vortex_pair",
+            Preset::LambOseen { .. }       => "lamb_oseen",
+            Preset::Uniform { .. }         => "uniform"
+        }
+    }
+
+    /// The preset called `name`, with its default parameters.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "checkerboard"      => Some(Preset::Checkerboard { size: 32.0, speed: 100.0 }),
+            "taylor_green"      => Some(Preset::TaylorGreen { amplitude: 50.0, wavelength: 128.0 }),
+            "kelvin_helmholtz"  => Some(Preset::KelvinHelmholtz { speed: 50.0, thickness: 4.0, perturbation: 0.05, wavelength: 64.0 }),
+            "rayleigh_taylor"   => Some(Preset::RayleighTaylor { perturbation: 4.0, wavelength: 64.0 }),
+            "lid_driven_cavity" => Some(Preset::LidDrivenCavity { lid_speed: 100.0 }),
+            "vortex_pair"       => Some(Preset::VortexPair { circulation: 2000.0, core_radius: 8.0, separation: 32.0 }),
+            "lamb_oseen"        => Some(Preset::LambOseen { circulation: 2000.0, core_radius: 16.0 }),
+            "uniform"           => Some(Preset::Uniform { velocity_x: 50.0, velocity_y: 0.0 }),
+            _                   => None
+        }
+    }
+
+    /// The parameter names and values, in declaration order.
+    pub fn parameters(&self) -> Vec<(&'static str, f32)> {
+        match *self {
+            Preset::Checkerboard { size, speed } => vec![("size", size), ("speed", speed)],
+            Preset::TaylorGreen { amplitude, wavelength } => vec![("amplitude", amplitude), ("wavelength", wavelength)],
+            Preset::KelvinHelmholtz { speed, thickness, perturbation, wavelength } => vec![("speed", speed), ("thickness", thickness), ("perturbation", perturbation), ("wavelength", wavelength)],
+            Preset::RayleighTaylor { perturbation, wavelength } => vec![("perturbation", perturbation), ("wavelength", wavelength)],
+            Preset::LidDrivenCavity { lid_speed } => vec![("lid_speed", lid_speed)],
+            Preset::VortexPair { circulation, core_radius, separation } => vec![("circulation", circulation), ("core_radius", core_radius), ("separation", separation)],
+            Preset::LambOseen { circulation, core_radius } => vec![("circulation", circulation), ("core_radius", core_radius)],
+            Preset::Uniform { velocity_x, velocity_y } => vec![("velocity_x", velocity_x), ("velocity_y", velocity_y)]
+        }
+    }
+
+    /// The preset called `name`, with the `parameters` overriding its defaults. Unknown names and parameters are errors.
+    pub fn parse(name: &str, parameters: &HashMap<String, f32>) -> Result<Self, String> {
+        let preset = Self::from_name(name).ok_or_else(|| format!("Unknown preset {}, expected one of {}.", name, PRESET_NAMES.join(", ")))?;
+        let defaults = preset.parameters();
+        for key in parameters.keys() {
+            if !defaults.iter().any(|(name, _)| name == key) {
+                let names: Vec<&str> = defaults.iter().map(|(name, _)| *name).collect();
+                return Err(format!("Unknown parameter {} for the {} preset, expected one of {}.", key, preset.name(), names.join(", ")));
+            }
+        }
+        let value = |key: &str| parameters.get(key).copied().unwrap_or_else(|| defaults.iter().find(|(name, _)| *name == key).expect("The parameter has a default.").1);
+        Ok(match preset {
+            Preset::Checkerboard { .. }    => Preset::Checkerboard { size: value("size"), speed: value("speed") },
+            Preset::TaylorGreen { .. }     => Preset::TaylorGreen { amplitude: value("amplitude"), wavelength: value("wavelength") },
+            Preset::KelvinHelmholtz { .. } => Preset::KelvinHelmholtz { speed: value("speed"), thickness: value("thickness"), perturbation: value("perturbation"), wavelength: value("wavelength") },
+            Preset::RayleighTaylor { .. }  => Preset::RayleighTaylor { perturbation: value("perturbation"), wavelength: value("wavelength") },
+            Preset::LidDrivenCavity { .. } => Preset::LidDrivenCavity { lid_speed: value("lid_speed") },
+            Preset::VortexPair { .. }      => Preset::VortexPair { circulation: value("circulation"), core_radius: value("core_radius"), separation: value("separation") },
+            Preset::LambOseen { .. }       => Preset::LambOseen { circulation: value("circulation"), core_radius: value("core_radius") },
+            Preset::Uniform { .. }         => Preset::Uniform { velocity_x: value("velocity_x"), velocity_y: value("velocity_y") }
+        })
+    }
+
+    /// The dye and the velocity at the cell `(x, y)` of a field of `dimensions`.
+    pub fn evaluate(&self, dimensions: (usize, usize), x: usize, y: usize) -> (f32, (f32, f32)) {
+        let position = (x as f32, y as f32);
+        let center = ((dimensions.0 - 1) as f32 * 0.5, (dimensions.1 - 1) as f32 * 0.5);
+        match *self {
+            Preset::Checkerboard { size, speed } => {
+                let square = |value: f32| sign((value * PI).sin());
+                let density = (square(position.0 / size) * square(position.1 / size)).max(0.0);
+                (density, (speed, (position.0 / 10.0 * 2.0).sin() * speed))
+            },
+            Preset::TaylorGreen { amplitude, wavelength } => {
+                let k = 2.0 * PI / wavelength;
+                let (kx, ky) = (k * position.0, k * position.1);
+                let density = 0.5 + 0.5 * kx.cos() * ky.cos();
+                (density, (amplitude * kx.sin() * ky.cos(), -amplitude * kx.cos() * ky.sin()))
+            },
+            Preset::KelvinHelmholtz { speed, thickness, perturbation, wavelength } => {
+                let layer = (position.1 - center.1) / thickness;
+                let density = 0.5 + 0.5 * layer.tanh();
+                let vertical = perturbation * speed * (2.0 * PI * position.0 / wavelength).sin() * (-layer * layer).exp();
+                (density, (speed * layer.tanh(), vertical))
+            },
+            Preset::RayleighTaylor { perturbation, wavelength } => {
+                let interface = center.1 + perturbation * (2.0 * PI * position.0 / wavelength).cos();
+                (0.5 + 0.5 * (position.1 - interface).tanh(), (0.0, 0.0))
+            },
+            Preset::LidDrivenCavity { lid_speed } => {
+                // The top row is the boundary, so the lid is the row under it.
+                let velocity = if y + 2 == dimensions.1 { (lid_speed, 0.0) } else { (0.0, 0.0) };
+                (stripes(position.1, 16.0), velocity)
+            },
+            Preset::VortexPair { circulation, core_radius, separation } => {
+                let left = (center.0 - separation * 0.5, center.1);
+                let right = (center.0 + separation * 0.5, center.1);
+                let (a, b) = (lamb_oseen(position, left, circulation, core_radius), lamb_oseen(position, right, -circulation, core_radius));
+                (blob(position, left, core_radius) + blob(position, right, core_radius), (a.0 + b.0, a.1 + b.1))
+            },
+            Preset::LambOseen { circulation, core_radius } => {
+                (blob(position, center, core_radius), lamb_oseen(position, center, circulation, core_radius))
+            },
+            Preset::Uniform { velocity_x, velocity_y } => (stripes(position.0, 16.0), (velocity_x, velocity_y))
+        }
+    }
+
+    /// The dye and the interleaved velocity, bottom row first like the textures.
+    pub fn fields(&self, dimensions: (usize, usize)) -> (Vec<f32>, Vec<f32>) {
+        let mut density = Vec::with_capacity(dimensions.0 * dimensions.1);
+        let mut velocity = Vec::with_capacity(dimensions.0 * dimensions.1 * 2);
+        for y in 0 .. dimensions.1 {
+            for x in 0 .. dimensions.0 {
+                let (value, (velocity_x, velocity_y)) = self.evaluate(dimensions, x, y);
+                density.push(value);
+                velocity.push(velocity_x);
+                velocity.push(velocity_y);
+            }
+        }
+        (density, velocity)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::numpy::divergence;
+
+    #[test]
+    fn names() {
+        for name in PRESET_NAMES.iter() {
+            assert_eq!(Preset::from_name(name).unwrap().name(), *name);
+        }
+        assert!(Preset::from_name("smoke").is_none());
+    }
+
+    #[test]
+    fn parameters() {
+        let mut parameters = HashMap::new();
+        parameters.insert("core_radius".to_string(), 4.0);
+        assert_eq!(Preset::parse("lamb_oseen", &parameters), Ok(Preset::LambOseen { circulation: 2000.0, core_radius: 4.0 }));
+        parameters.insert("speed".to_string(), 1.0);
+        assert!(Preset::parse("lamb_oseen", &parameters).is_err());
+        assert!(Preset::parse("smoke", &HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn vortices_are_divergence_free() {
+        let dimensions = (64, 64);
+        for preset in &[Preset::from_name("taylor_green").unwrap(), Preset::from_name("lamb_oseen").unwrap(), Preset::from_name("vortex_pair").unwrap()] {
+            let (_, velocity) = preset.fields(dimensions);
+            let divergence = divergence(&velocity, dimensions);
+            let maximum_speed = velocity.iter().fold(0.0f32, |maximum, value| maximum.max(value.abs()));
+            // Only the interior, the clamped borders aren't central differences.
+            for y in 1 .. dimensions.1 - 1 {
+                for x in 1 .. dimensions.0 - 1 {
+                    assert!(divergence[y * dimensions.0 + x].abs() < maximum_speed * 0.05, "{} at {:?}", preset.name(), (x, y));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn checkerboard_matches_the_shader() {
+        let preset = Preset::Checkerboard { size: 4.0, speed: 1.0 };
+        // GLSL's sign is zero on the first row and column, so they have no dye.
+        assert_eq!(preset.evaluate((16, 16), 0, 2).0, 0.0);
+        assert_eq!(preset.evaluate((16, 16), 2, 0).0, 0.0);
+        assert_eq!(preset.evaluate((16, 16), 2, 2).0, 1.0);
+        assert_eq!(preset.evaluate((16, 16), 6, 2).0, 0.0);
+        assert_eq!(preset.evaluate((16, 16), 6, 6).0, 1.0);
+    }
+
+    #[test]
+    fn vortex_pair_travels_upwards() {
+        let dimensions = (65, 65);
+        let (_, (velocity_x, velocity_y)) = Preset::from_name("vortex_pair").unwrap().evaluate(dimensions, 32, 32);
+        assert!(velocity_x.abs() < 1e-4 && velocity_y > 0.0);
+    }
+
+    #[test]
+    fn lamb_oseen_profile() {
+        // Far from the core it's a point vortex, circulation / (2 pi r).
+        let velocity = lamb_oseen((100.0, 0.0), (0.0, 0.0), 2.0 * PI, 1.0);
+        assert!(velocity.0.abs() < 1e-6 && (velocity.1 - 0.01).abs() < 1e-6);
+        assert_eq!(lamb_oseen((0.0, 0.0), (0.0, 0.0), 1.0, 1.0), (0.0, 0.0));
+    }
+}
@@ -0,0 +1,153 @@
+use glutin::{ElementState, MouseButton, VirtualKeyCode, WindowEvent};
+
+/// Discrete commands triggered by the key bindings.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Reset,
+    TogglePause,
+    Step,
+    NextDisplayMode,
+    Screenshot
+}
+
+impl Action {
+    fn from_key(key: VirtualKeyCode) -> Option<Self> {
+        match key {
+            VirtualKeyCode::R     => Some(Action::Reset),
+            VirtualKeyCode::Space => Some(Action::TogglePause),
+            VirtualKeyCode::S     => Some(Action::Step),
+            VirtualKeyCode::D     => Some(Action::NextDisplayMode),
+            VirtualKeyCode::P     => Some(Action::Screenshot),
+            _                     => None
+        }
+    }
+}
+
+/// A pointer movement while a button is held, in window coordinates.
+#[derive(Clone, Copy, Debug)]
+pub struct Drag {
+    pub from: (f32, f32),
+    pub to: (f32, f32)
+}
+
+impl Drag {
+    pub fn delta(&self) -> (f32, f32) {
+        (self.to.0 - self.from.0, self.to.1 - self.from.1)
+    }
+}
+
+/// Collects the window events of a frame.
+pub struct Input {
+    pointer: (f32, f32),
+    left_pressed: bool,
+    right_pressed: bool,
+    pub left_drags: Vec<Drag>,
+    pub right_drags: Vec<Drag>,
+    pub actions: Vec<Action>
+}
+
+impl Input {
+    pub fn new() -> Self {
+        let pointer = (0.0, 0.0);
+        let left_pressed = false;
+        let right_pressed = false;
+        let left_drags = Vec::new();
+        let right_drags = Vec::new();
+        let actions = Vec::new();
+        Self { pointer, left_pressed, right_pressed, left_drags, right_drags, actions }
+    }
+
+    /// Forgets the drags and actions of the previous frame.
+    pub fn clear(&mut self) {
+        self.left_drags.clear();
+        self.right_drags.clear();
+        self.actions.clear();
+    }
+
+    pub fn handle(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                let pointer = (position.x as f32, position.y as f32);
+                let drag = Drag { from: self.pointer, to: pointer };
+                if self.left_pressed  { self.left_drags.push(drag); }
+                if self.right_pressed { self.right_drags.push(drag); }
+                self.pointer = pointer;
+            },
+            WindowEvent::MouseInput { state, button, .. } => {
+                let pressed = *state == ElementState::Pressed;
+                match button {
+                    MouseButton::Left  => self.left_pressed = pressed,
+                    MouseButton::Right => self.right_pressed = pressed,
+                    _                  => ()
+                }
+            },
+            WindowEvent::KeyboardInput { input, .. } if input.state == ElementState::Pressed => {
+                if let Some(action) = input.virtual_keycode.and_then(Action::from_key) {
+                    self.actions.push(action);
+                }
+            },
+            _ => ()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use glutin::{DeviceId, KeyboardInput, ModifiersState};
+    use glutin::dpi::LogicalPosition;
+
+    fn device_id() -> DeviceId {
+        unsafe { DeviceId::dummy() }
+    }
+
+    fn move_to(input: &mut Input, x: f64, y: f64) {
+        input.handle(&WindowEvent::CursorMoved { device_id: device_id(), position: LogicalPosition::new(x, y), modifiers: ModifiersState::default() });
+    }
+
+    fn press(input: &mut Input, button: MouseButton, state: ElementState) {
+        input.handle(&WindowEvent::MouseInput { device_id: device_id(), state, button, modifiers: ModifiersState::default() });
+    }
+
+    fn key(input: &mut Input, key: VirtualKeyCode, state: ElementState) {
+        let keyboard_input = KeyboardInput { scancode: 0, state, virtual_keycode: Some(key), modifiers: ModifiersState::default() };
+        input.handle(&WindowEvent::KeyboardInput { device_id: device_id(), input: keyboard_input });
+    }
+
+    #[test]
+    fn key_bindings() {
+        assert_eq!(Action::from_key(VirtualKeyCode::R), Some(Action::Reset));
+        assert_eq!(Action::from_key(VirtualKeyCode::Space), Some(Action::TogglePause));
+        assert_eq!(Action::from_key(VirtualKeyCode::S), Some(Action::Step));
+        assert_eq!(Action::from_key(VirtualKeyCode::D), Some(Action::NextDisplayMode));
+        assert_eq!(Action::from_key(VirtualKeyCode::P), Some(Action::Screenshot));
+        assert_eq!(Action::from_key(VirtualKeyCode::Q), None);
+
+        let mut input = Input::new();
+        key(&mut input, VirtualKeyCode::Space, ElementState::Pressed);
+        key(&mut input, VirtualKeyCode::Space, ElementState::Released);
+        key(&mut input, VirtualKeyCode::Q, ElementState::Pressed);
+        assert_eq!(input.actions, vec![Action::TogglePause]);
+    }
+
+    #[test]
+    fn drags() {
+        let mut input = Input::new();
+        move_to(&mut input, 1.0, 2.0);
+        press(&mut input, MouseButton::Left, ElementState::Pressed);
+        move_to(&mut input, 4.0, 6.0);
+        press(&mut input, MouseButton::Right, ElementState::Pressed);
+        move_to(&mut input, 5.0, 6.0);
+        press(&mut input, MouseButton::Left, ElementState::Released);
+        move_to(&mut input, 5.0, 8.0);
+        assert_eq!(input.left_drags.iter().map(Drag::delta).collect::<Vec<_>>(), vec![(3.0, 4.0), (1.0, 0.0)]);
+        assert_eq!(input.right_drags.iter().map(|drag| (drag.from, drag.to)).collect::<Vec<_>>(), vec![((4.0, 6.0), (5.0, 6.0)), ((5.0, 6.0), (5.0, 8.0))]);
+
+        // The drags are per frame, but the pressed buttons and the pointer carry over.
+        input.clear();
+        assert!(input.left_drags.is_empty() && input.right_drags.is_empty() && input.actions.is_empty());
+        move_to(&mut input, 6.0, 8.0);
+        assert!(input.left_drags.is_empty());
+        assert_eq!(input.right_drags.iter().map(Drag::delta).collect::<Vec<_>>(), vec![(1.0, 0.0)]);
+    }
+}
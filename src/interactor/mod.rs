@@ -1,26 +1,187 @@
 use crate::context::Context;
 use crate::fluid::Fluid;
 
+/// The brush profile of a splat.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Falloff {
+    Gaussian,
+    Hard
+}
+
+impl Falloff {
+    fn id(&self) -> f32 {
+        match self {
+            Falloff::Gaussian => 0.0,
+            Falloff::Hard     => 1.0
+        }
+    }
+}
+
+/// A force and dye injection centered at `position`, in cell coordinates.
+#[derive(Clone, Copy, Debug)]
+pub struct Splat {
+    pub position: (f32, f32),
+    pub radius: f32,
+    pub falloff: Falloff,
+    pub velocity: (f32, f32),
+    pub dye_amount: f32
+}
+
+impl Splat {
+    pub fn new(position: (f32, f32), radius: f32, falloff: Falloff, velocity: (f32, f32), dye_amount: f32) -> Self {
+        Self { position, radius, falloff, velocity, dye_amount }
+    }
+}
+
 pub struct Interactor {
-    compute_program : gpu::ComputeProgram
+    splat_program : gpu::ComputeProgram,
+    obstacle_program : gpu::ComputeProgram
 }
 
 impl Interactor {
     pub fn new(context: &Context) -> Self {
-        let compute_shader = gpu::ComputeShader::new(&context.context, include_str!("compute.glsl")).expect("Couldn't create compute_shader.");
-        let compute_program = gpu::ComputeProgram::new(&context.context, &compute_shader).expect("Couldn't create compute_program.");
-        Self { compute_program }
-    }
-
-    pub fn interact(&self, fluid: &mut Fluid) {
-        const VELOCITY_FIELD_LOCATION : usize = 0;
-        const DENSITY_FIELD_LOCATION  : usize = 1;
-        const POSITION_LOCATION       : usize = 2;
-        let position   = (fluid.dimensions.0 as i32 / 2, fluid.dimensions.1 as i32 / 2);
-        let dimensions = (32, 32, 1);
-        self.compute_program.bind_image_2d(&fluid.velocity_field, VELOCITY_FIELD_LOCATION);
-        self.compute_program.bind_image_2d(&fluid.density_field, DENSITY_FIELD_LOCATION);
-        self.compute_program.bind_ivec2(position, POSITION_LOCATION);
-        self.compute_program.compute(dimensions);
-    }
-}
\ No newline at end of file
+        let splat_shader = gpu::ComputeShader::new(&context.context, include_str!("splat.glsl")).expect("Couldn't create splat_shader.");
+        let splat_program = gpu::ComputeProgram::new(&context.context, &splat_shader).expect("Couldn't create splat_program.");
+        let obstacle_shader = gpu::ComputeShader::new(&context.context, include_str!("obstacle.glsl")).expect("Couldn't create obstacle_shader.");
+        let obstacle_program = gpu::ComputeProgram::new(&context.context, &obstacle_shader).expect("Couldn't create obstacle_program.");
+        Self { splat_program, obstacle_program }
+    }
+
+    /// Adds `velocity` and `dye_amount`, weighted by the brush, around `position`.
+    pub fn splat(&self, context: &Context, fluid: &mut Fluid, position: (f32, f32), radius: f32, falloff: Falloff, velocity: (f32, f32), dye_amount: f32) {
+        self.splat_batch(context, fluid, &[Splat::new(position, radius, falloff, velocity, dye_amount)]);
+    }
+
+    /// Applies all the `splats` in a single dispatch. The splats without a positive radius cover no cell, they're skipped.
+    pub fn splat_batch(&self, context: &Context, fluid: &mut Fluid, splats: &[Splat]) {
+        const VELOCITY_FIELD_LOCATION   : usize = 0;
+        const DENSITY_FIELD_LOCATION    : usize = 1;
+        const SPLATS_LOCATION           : usize = 2;
+        const NUMBER_OF_SPLATS_LOCATION : usize = 3;
+        let splats: Vec<&Splat> = splats.iter().filter(|splat| splat.radius > 0.0).collect();
+        if splats.is_empty() {
+            return;
+        }
+
+        // The splats are laid out as two RGBA rows, one column per splat.
+        let mut data = Vec::with_capacity(splats.len() * 8);
+        for splat in &splats {
+            data.extend_from_slice(&[splat.position.0, splat.position.1, splat.radius, splat.falloff.id()]);
+        }
+        for splat in &splats {
+            data.extend_from_slice(&[splat.velocity.0, splat.velocity.1, splat.dye_amount, 0.0]);
+        }
+        let format = gpu::TextureFormat::new(gpu::ColorFormat::components(4), gpu::Type::F32);
+        let splats_texture = gpu::Texture2D::from_data(&context.context, (splats.len(), 2), &format, &data, &format);
+
+        let dimensions = (fluid.dimensions.0, fluid.dimensions.1, 1);
+        self.splat_program.bind_image_2d(&fluid.velocity_field, VELOCITY_FIELD_LOCATION);
+        self.splat_program.bind_image_2d(&fluid.density_field, DENSITY_FIELD_LOCATION);
+        self.splat_program.bind_image_2d(&splats_texture, SPLATS_LOCATION);
+        self.splat_program.bind_i32(splats.len() as i32, NUMBER_OF_SPLATS_LOCATION);
+        self.splat_program.compute(dimensions);
+        //FIXME: How to expose it on the GPU API?
+        // Ref: https://www.khronos.org/registry/OpenGL-Refpages/gl4/html/glMemoryBarrier.xhtml
+        unsafe {
+            gl::MemoryBarrier(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+        }
+    }
+
+    /// Splats along the segment `from` -> `to`, spacing the splats by half a `radius`.
+    /// The injected velocity is the segment length divided by `delta_time`.
+    pub fn stroke(&self, context: &Context, fluid: &mut Fluid, from: (f32, f32), to: (f32, f32), radius: f32, delta_time: f32, dye_amount: f32) {
+        let delta = (to.0 - from.0, to.1 - from.1);
+        let length = (delta.0 * delta.0 + delta.1 * delta.1).sqrt();
+        let velocity = (delta.0 / delta_time.max(std::f32::EPSILON), delta.1 / delta_time.max(std::f32::EPSILON));
+        let steps = (length / (radius * 0.5).max(1.0)).ceil().max(1.0) as usize;
+        // The dye and the velocity are split among the splats so the stroke amount doesn't depend on the spacing.
+        let weight = 1.0 / steps as f32;
+        let splats: Vec<Splat> = (1 ..= steps).map(|step| {
+            let t = step as f32 * weight;
+            let position = (from.0 + delta.0 * t, from.1 + delta.1 * t);
+            Splat::new(position, radius, Falloff::Gaussian, (velocity.0 * weight, velocity.1 * weight), dye_amount * weight)
+        }).collect();
+        self.splat_batch(context, fluid, &splats);
+    }
+
+    /// Marks the cells within `radius` of the segment `from` -> `to` as obstacles.
+    pub fn paint_obstacle(&self, fluid: &mut Fluid, from: (f32, f32), to: (f32, f32), radius: f32) {
+        const OBSTACLE_FIELD_LOCATION : usize = 0;
+        const FROM_LOCATION           : usize = 1;
+        const TO_LOCATION             : usize = 2;
+        const RADIUS_LOCATION         : usize = 3;
+        let dimensions = (fluid.dimensions.0, fluid.dimensions.1, 1);
+        self.obstacle_program.bind_image_2d(&fluid.obstacle_field, OBSTACLE_FIELD_LOCATION);
+        self.obstacle_program.bind_vec2(from, FROM_LOCATION);
+        self.obstacle_program.bind_vec2(to, TO_LOCATION);
+        self.obstacle_program.bind_f32(radius, RADIUS_LOCATION);
+        self.obstacle_program.compute(dimensions);
+        //FIXME: How to expose it on the GPU API?
+        // Ref: https://www.khronos.org/registry/OpenGL-Refpages/gl4/html/glMemoryBarrier.xhtml
+        unsafe {
+            gl::MemoryBarrier(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::context::Context;
+    use crate::fluid::Fluid;
+    use crate::interactor::{Interactor, Falloff, Splat};
+
+    fn initialize(dimensions: (usize, usize)) -> (Context, Interactor, Fluid) {
+        let context = Context::new(dimensions);
+        let interactor = Interactor::new(&context);
+        let mut fluid = Fluid::new(&context, dimensions, 0.0, 0.0);
+        let format = gpu::TextureFormat::new(gpu::ColorFormat::R, gpu::Type::F32);
+        let zero = vec![0.0; dimensions.0 * dimensions.1];
+        fluid.density_field = gpu::Texture2D::from_data(&context.context, dimensions, &format, &zero, &format);
+        let format = gpu::TextureFormat::new(gpu::ColorFormat::RG, gpu::Type::F32);
+        let zero = vec![0.0; dimensions.0 * dimensions.1 * 2];
+        fluid.velocity_field = gpu::Texture2D::from_data(&context.context, dimensions, &format, &zero, &format);
+        (context, interactor, fluid)
+    }
+
+    #[test]
+    fn hard_splat() {
+        let dimensions = (5, 5);
+        let (context, interactor, mut fluid) = initialize(dimensions);
+
+        interactor.splat(&context, &mut fluid, (2.0, 2.0), 1.0, Falloff::Hard, (0.0, 0.0), 1.0);
+        let expected_data = vec![
+            0.0, 0.0, 0.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0, 0.0,
+            0.0, 1.0, 1.0, 1.0, 0.0,
+            0.0, 0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 0.0, 0.0, 0.0
+        ];
+        assert_eq!(fluid.density_field.data() as Vec<f32>, expected_data);
+    }
+
+    #[test]
+    fn splats_accumulate() {
+        let dimensions = (5, 5);
+        let (context, interactor, mut fluid) = initialize(dimensions);
+
+        let splat = Splat::new((0.0, 0.0), 0.5, Falloff::Hard, (1.0, -1.0), 0.5);
+        interactor.splat_batch(&context, &mut fluid, &[splat, splat]);
+        interactor.splat_batch(&context, &mut fluid, &[splat]);
+
+        let density = fluid.density_field.data() as Vec<f32>;
+        assert_eq!(density[0], 1.5);
+        assert_eq!(density[1], 0.0);
+        let velocity = fluid.velocity_field.data() as Vec<f32>;
+        assert_eq!(&velocity[0..2], &[3.0, -3.0]);
+    }
+
+    #[test]
+    fn empty_splats_are_skipped() {
+        let dimensions = (5, 5);
+        let (context, interactor, mut fluid) = initialize(dimensions);
+
+        interactor.splat(&context, &mut fluid, (2.0, 2.0), 0.0, Falloff::Gaussian, (1.0, 1.0), 1.0);
+        assert_eq!(fluid.density_field.data() as Vec<f32>, vec![0.0; dimensions.0 * dimensions.1]);
+        assert_eq!(fluid.velocity_field.data() as Vec<f32>, vec![0.0; dimensions.0 * dimensions.1 * 2]);
+    }
+}
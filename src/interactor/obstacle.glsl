@@ -0,0 +1,23 @@
+#version 450
+
+layout(r32f, location = 0) uniform image2D obstacleField;
+layout(location = 1) uniform vec2 from;
+layout(location = 2) uniform vec2 to;
+layout(location = 3) uniform float radius;
+
+layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+// Distance from point to the segment [from, to].
+float segmentDistance(vec2 point) {
+    vec2 segment = to - from;
+    float lengthSquared = max(dot(segment, segment), 1e-6);
+    float t = clamp(dot(point - from, segment) / lengthSquared, 0.0, 1.0);
+    return distance(point, from + segment * t);
+}
+
+void main() {
+    ivec2 coordinate = ivec2(gl_GlobalInvocationID.xy);
+    if (segmentDistance(vec2(coordinate)) <= radius) {
+        imageStore(obstacleField, coordinate, vec4(1.0));
+    }
+}
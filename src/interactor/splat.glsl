@@ -0,0 +1,36 @@
+#version 450
+
+layout(rg32f, location = 0) uniform image2D velocityField;
+layout(r32f, location = 1) uniform image2D densityField;
+// Each splat takes one column: (x, y, radius, falloff) on row 0 and (vx, vy, dye, 0) on row 1.
+layout(rgba32f, location = 2) uniform image2D splats;
+layout(location = 3) uniform int numberOfSplats;
+
+layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+#define GAUSSIAN_FALLOFF 0
+#define HARD_FALLOFF 1
+
+float weight(float distanceSquared, float radius, int falloff) {
+    float radiusSquared = radius * radius;
+    if (falloff == HARD_FALLOFF) return distanceSquared <= radiusSquared ? 1.0 : 0.0;
+    return exp(-distanceSquared / radiusSquared);
+}
+
+void main() {
+    ivec2 coordinate = ivec2(gl_GlobalInvocationID.xy);
+    vec2 position = vec2(coordinate);
+
+    vec2 velocity = imageLoad(velocityField, coordinate).xy;
+    float density = imageLoad(densityField, coordinate).x;
+    for (int i = 0; i < numberOfSplats; i++) {
+        vec4 shape = imageLoad(splats, ivec2(i, 0));
+        vec4 amount = imageLoad(splats, ivec2(i, 1));
+        vec2 delta = position - shape.xy;
+        float w = weight(dot(delta, delta), shape.z, int(shape.w));
+        velocity += amount.xy * w;
+        density += amount.z * w;
+    }
+    imageStore(velocityField, coordinate, vec4(velocity, 0.0, 0.0));
+    imageStore(densityField, coordinate, vec4(density));
+}
@@ -6,43 +6,101 @@ mod presenter;
 mod simulator;
 mod interactor;
 mod velocity_debugger;
+mod input;
+mod tracer;
+mod streamline;
+mod capture;
+mod snapshot;
+mod numpy;
+mod vtk;
+mod scene;
 
 use field::Field;
 use context::Context;
-use initializer::Initializer;
-use presenter::Presenter;
-use simulator::Simulator;
-use fluid::Fluid;
-use interactor::Interactor;
+use initializer::{Preset, PRESET_NAMES};
+use presenter::DisplayMode;
 use velocity_debugger::VelocityDebugger;
+use input::{Input, Action};
+use scene::{Scene, Simulation, InitialCondition};
 use std::time::Instant;
 
 fn main() {
-    let dimensions = (256, 256);
-    let mut context = Context::new(dimensions);
-
-    let mut initializer = Initializer::new(&context);
-    let mut presenter = Presenter::new(&context);
-    let mut simulator = Simulator::new(&context, dimensions);
-    let interactor = Interactor::new(&context);
+    // The first argument is either a scene file or the name of a preset, the default is the checkerboard shaders.
+    let scene = match std::env::args().nth(1) {
+        Some(path) if path.ends_with(".toml") => Scene::load(&path).unwrap_or_else(|error| panic!("Couldn't load {}: {}", path, error)),
+        Some(name) => {
+            let preset = Preset::from_name(&name).unwrap_or_else(|| panic!("Unknown preset {}, expected one of {}.", name, PRESET_NAMES.join(", ")));
+            Scene { initial: InitialCondition::Preset(preset), ..Scene::default() }
+        },
+        None => Scene::default()
+    };
+    let Simulation { mut context, mut fluid, mut simulator, mut initializer, interactor, mut presenter, mut output } = scene.build(false).unwrap_or_else(|error| panic!("Couldn't build the scene: {}", error));
     let velocity_debugger = VelocityDebugger::new(&context);
 
-    let diffusion = 1.0;
-    let viscosity = 0.0000001;
-    let mut fluid = Fluid::new(&context, dimensions, diffusion, viscosity);
-
-    initializer.initialize(&mut fluid);
-
-    let delta_time = 0.001;
+    let brush_radius = 8.0;
+    let dye_amount = 1.0;
+    let mut input = Input::new();
+    let mut paused = false;
+    let mut screenshot = false;
     let mut then = Instant::now();
-    while context.context.run() {
+    while context.run(&mut input) {
         let now = Instant::now();
         let delta_time = (now - then).as_secs_f32();
         then = now;
-        // interactor.interact(&mut fluid);
-        simulator.simulate(&mut fluid, delta_time);
-        presenter.present(&context, &fluid);
-        velocity_debugger.debug(&fluid.velocity_field);
+
+        let mut step = !paused;
+        for &action in &input.actions {
+            match action {
+                Action::Reset           => {
+                    if let Err(error) = scene.initialize(&context, &mut initializer, &mut simulator, &mut fluid) {
+                        eprintln!("Couldn't reset the scene: {}", error);
+                    }
+                },
+                Action::TogglePause     => paused = !paused,
+                Action::Step            => step = true,
+                Action::NextDisplayMode => presenter.display_mode = presenter.display_mode.next_available(simulator.pressure_field(&fluid).is_some(), fluid.level_set_field.is_some()),
+                Action::Screenshot      => screenshot = true
+            }
+        }
+        for drag in &input.left_drags {
+            let from = presenter.window_to_field(&context, drag.from, fluid.dimensions);
+            let to = presenter.window_to_field(&context, drag.to, fluid.dimensions);
+            interactor.stroke(&context, &mut fluid, from, to, brush_radius, delta_time, dye_amount);
+        }
+        for drag in &input.right_drags {
+            let from = presenter.window_to_field(&context, drag.from, fluid.dimensions);
+            let to = presenter.window_to_field(&context, drag.to, fluid.dimensions);
+            interactor.paint_obstacle(&mut fluid, from, to, brush_radius);
+        }
+
+        if step {
+            // Single steps while paused use a fixed time step instead of the paused wall clock time.
+            let delta_time = match scene.solver.time_step {
+                Some(time_step) => time_step,
+                None            => if paused { 1.0 / 60.0 } else { delta_time }
+            };
+            simulator.simulate(&mut fluid, delta_time);
+            if let Err(error) = output.write(&fluid, &simulator) {
+                eprintln!("Couldn't write the output: {}", error);
+            }
+        }
+        presenter.present(&context, &fluid, simulator.pressure_field(&fluid));
+        if presenter.display_mode == DisplayMode::VelocityDirection {
+            velocity_debugger.debug(&context, &presenter, &fluid.velocity_field);
+        }
+        if screenshot {
+            match output.capture.screenshot(&context) {
+                Ok(path) => println!("Saved {}", path.display()),
+                Err(error) => eprintln!("Couldn't save the screenshot: {}", error)
+            }
+            screenshot = false;
+        }
+        if let Err(error) = output.capture.frame(&context) {
+            eprintln!("Couldn't capture the frame: {}", error);
+        }
         context.present();
+        if output.is_finished(simulator.time) {
+            break;
+        }
     }
 }
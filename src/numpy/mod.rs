@@ -0,0 +1,360 @@
+//! NumPy `.npy` and `.npz` export and `.npy` import of the fields.
+//!
+//! The arrays are `float32` in C order, indexed `[y, x]` or `[y, x, component]`, with row 0 at the bottom like the textures.
+
+use crate::capture::png::crc32;
+use crate::context::Context;
+use std::io::{Error, ErrorKind, Read, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 6] = b"\x93NUMPY";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Array {
+    pub shape: Vec<usize>,
+    pub data: Vec<f32>
+}
+
+fn invalid(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Reads `length` bytes. The lengths come from the file, so the buffer only grows with the bytes actually read.
+fn read_bytes(reader: &mut impl Read, length: usize) -> std::io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    reader.take(length as u64).read_to_end(&mut bytes)?;
+    if bytes.len() < length {
+        return Err(invalid(format!("The .npy file is truncated, {} bytes are missing.", length - bytes.len())));
+    }
+    Ok(bytes)
+}
+
+impl Array {
+    pub fn new(shape: Vec<usize>, data: Vec<f32>) -> Self {
+        assert_eq!(shape.iter().product::<usize>(), data.len(), "The data doesn't match the shape.");
+        Self { shape, data }
+    }
+
+    /// A field of `components` per cell, like `gpu::Texture2D::data` or the CPU backend grids.
+    pub fn from_field(data: Vec<f32>, dimensions: (usize, usize), components: usize) -> Self {
+        let mut shape = vec![dimensions.1, dimensions.0];
+        if components > 1 {
+            shape.push(components);
+        }
+        Self::new(shape, data)
+    }
+
+    pub fn from_texture(texture: &gpu::Texture2D) -> Self {
+        let dimensions = texture.dimensions();
+        let data: Vec<f32> = texture.data();
+        let components = data.len() / (dimensions.0 * dimensions.1).max(1);
+        Self::from_field(data, dimensions, components)
+    }
+
+    /// One component of an interleaved field, like the x velocity.
+    pub fn component(data: &[f32], dimensions: (usize, usize), components: usize, component: usize) -> Self {
+        Self::from_field(data.iter().skip(component).step_by(components).copied().collect(), dimensions, 1)
+    }
+
+    pub fn dimensions(&self) -> (usize, usize) {
+        (self.shape.get(1).copied().unwrap_or(1), self.shape.first().copied().unwrap_or(1))
+    }
+
+    pub fn components(&self) -> usize {
+        self.shape.get(2).copied().unwrap_or(1)
+    }
+
+    /// Uploads a `[y, x]` or `[y, x, component]` array, to set the initial conditions.
+    pub fn to_texture(&self, context: &Context) -> std::io::Result<gpu::Texture2D> {
+        if self.shape.len() < 2 || self.shape.len() > 3 || self.components() > 4 {
+            return Err(invalid(format!("The shape {:?} isn't a field.", self.shape)));
+        }
+        let format = gpu::TextureFormat::new(gpu::ColorFormat::components(self.components()), gpu::Type::F32);
+        Ok(gpu::Texture2D::from_data(&context.context, self.dimensions(), &format, &self.data, &format))
+    }
+
+    pub fn write_npy(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        let shape = match self.shape.len() {
+            1 => format!("({},)", self.shape[0]),
+            _ => format!("({})", self.shape.iter().map(|size| size.to_string()).collect::<Vec<_>>().join(", "))
+        };
+        let mut header = format!("{{'descr': '<f4', 'fortran_order': False, 'shape': {}, }}", shape);
+        // The data starts aligned on 64 bytes, after the magic, the version and the header length.
+        let preamble = MAGIC.len() + 2 + 2;
+        let padding = (64 - (preamble + header.len() + 1) % 64) % 64;
+        header.push_str(&" ".repeat(padding));
+        header.push('\n');
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[1, 0])?;
+        writer.write_all(&(header.len() as u16).to_le_bytes())?;
+        writer.write_all(header.as_bytes())?;
+        for value in &self.data {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Reads little-endian `float32` or `float64` arrays in C order.
+    pub fn read_npy(reader: &mut impl Read) -> std::io::Result<Self> {
+        let mut preamble = [0; 8];
+        reader.read_exact(&mut preamble)?;
+        if &preamble[.. 6] != MAGIC {
+            return Err(invalid("Not a .npy file.".to_string()));
+        }
+        let header_length = match preamble[6] {
+            1 => {
+                let mut length = [0; 2];
+                reader.read_exact(&mut length)?;
+                u16::from_le_bytes(length) as usize
+            },
+            2 | 3 => {
+                let mut length = [0; 4];
+                reader.read_exact(&mut length)?;
+                u32::from_le_bytes(length) as usize
+            },
+            version => return Err(invalid(format!("Unsupported .npy version {}.", version)))
+        };
+        let header = read_bytes(reader, header_length)?;
+        let header = String::from_utf8_lossy(&header);
+        let value = |key: &str| -> std::io::Result<String> {
+            let start = header.find(&format!("'{}':", key)).ok_or_else(|| invalid(format!("The header has no {}.", key)))? + key.len() + 3;
+            Ok(header[start ..].trim_start().to_string())
+        };
+        if value("fortran_order")?.starts_with("True") {
+            return Err(invalid("Fortran ordered arrays aren't supported.".to_string()));
+        }
+        let descr = value("descr")?;
+        let size: usize = match descr.get(.. 5) {
+            Some("'<f4'") => 4,
+            Some("'<f8'") => 8,
+            _ => return Err(invalid(format!("Unsupported dtype {}, expected '<f4' or '<f8'.", descr.split(',').next().unwrap_or(""))))
+        };
+        let shape = value("shape")?;
+        let end = match shape.find(')') {
+            Some(end) if shape.starts_with('(') => end,
+            _ => return Err(invalid("The shape isn't a tuple.".to_string()))
+        };
+        let shape = &shape[1 .. end];
+        let shape = shape.split(',').map(str::trim).filter(|size| !size.is_empty())
+            .map(|size| size.parse::<usize>().map_err(|_| invalid(format!("Invalid shape size {}.", size))))
+            .collect::<std::io::Result<Vec<usize>>>()?;
+        let length = shape.iter().try_fold(size, |length, size| length.checked_mul(*size)).ok_or_else(|| invalid(format!("The shape {:?} is too large.", shape)))?;
+        let bytes = read_bytes(reader, length)?;
+        let data = match size {
+            4 => bytes.chunks(4).map(|value| f32::from_le_bytes([value[0], value[1], value[2], value[3]])).collect(),
+            _ => bytes.chunks(8).map(|value| f64::from_le_bytes([value[0], value[1], value[2], value[3], value[4], value[5], value[6], value[7]]) as f32).collect()
+        };
+        Ok(Self { shape, data })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.write_npy(&mut writer)?;
+        writer.flush()
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Self::read_npy(&mut std::io::BufReader::new(std::fs::File::open(path)?))
+    }
+}
+
+/// Writes the `arrays` as `<name>.npy` entries of a deflated zip, like `numpy.savez_compressed`.
+pub fn write_npz(writer: &mut impl Write, arrays: &[(&str, &Array)]) -> std::io::Result<()> {
+    // The zip timestamps are set to 1980-01-01, the earliest DOS date.
+    const DOS_DATE: u16 = 0x21;
+    const DEFLATE: u16 = 8;
+    const VERSION: u16 = 20;
+    let mut offset = 0;
+    let mut central_directory = Vec::new();
+    for (name, array) in arrays {
+        let name = format!("{}.npy", name);
+        let mut npy = Vec::new();
+        array.write_npy(&mut npy)?;
+        let compressed = miniz_oxide::deflate::compress_to_vec(&npy, 6);
+        let crc = crc32(&npy);
+
+        let mut common = Vec::new();
+        common.extend_from_slice(&VERSION.to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes());
+        common.extend_from_slice(&DEFLATE.to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes());
+        common.extend_from_slice(&DOS_DATE.to_le_bytes());
+        common.extend_from_slice(&crc.to_le_bytes());
+        common.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+        common.extend_from_slice(&(npy.len() as u32).to_le_bytes());
+        common.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes());
+
+        let mut local = Vec::new();
+        local.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        local.extend_from_slice(&common);
+        local.extend_from_slice(name.as_bytes());
+        writer.write_all(&local)?;
+        writer.write_all(&compressed)?;
+
+        central_directory.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        central_directory.extend_from_slice(&VERSION.to_le_bytes());
+        central_directory.extend_from_slice(&common);
+        // Comment length, disk, internal and external attributes.
+        central_directory.extend_from_slice(&[0; 10]);
+        central_directory.extend_from_slice(&(offset as u32).to_le_bytes());
+        central_directory.extend_from_slice(name.as_bytes());
+        offset += local.len() + compressed.len();
+    }
+    writer.write_all(&central_directory)?;
+    writer.write_all(&0x0605_4b50u32.to_le_bytes())?;
+    writer.write_all(&[0; 4])?;
+    writer.write_all(&(arrays.len() as u16).to_le_bytes())?;
+    writer.write_all(&(arrays.len() as u16).to_le_bytes())?;
+    writer.write_all(&(central_directory.len() as u32).to_le_bytes())?;
+    writer.write_all(&(offset as u32).to_le_bytes())?;
+    writer.write_all(&0u16.to_le_bytes())
+}
+
+pub fn save_npz(path: impl AsRef<Path>, arrays: &[(&str, &Array)]) -> std::io::Result<()> {
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+    write_npz(&mut writer, arrays)?;
+    writer.flush()
+}
+
+/// Evaluates `derivative` on every cell, with a central difference sampler of the velocity clamped on the borders.
+fn velocity_derivative(velocity: &[f32], dimensions: (usize, usize), derivative: impl Fn(&dyn Fn(isize, isize, usize) -> f32, isize, isize) -> f32) -> Vec<f32> {
+    let at = |x: isize, y: isize, component: usize| {
+        let x = x.max(0).min(dimensions.0 as isize - 1) as usize;
+        let y = y.max(0).min(dimensions.1 as isize - 1) as usize;
+        velocity[(y * dimensions.0 + x) * 2 + component]
+    };
+    let mut values = Vec::with_capacity(dimensions.0 * dimensions.1);
+    for y in 0 .. dimensions.1 as isize {
+        for x in 0 .. dimensions.0 as isize {
+            values.push(derivative(&at, x, y));
+        }
+    }
+    values
+}
+
+/// Central difference divergence of an interleaved velocity field, in cell units.
+pub fn divergence(velocity: &[f32], dimensions: (usize, usize)) -> Vec<f32> {
+    velocity_derivative(velocity, dimensions, |at, x, y| 0.5 * (at(x + 1, y, 0) - at(x - 1, y, 0) + at(x, y + 1, 1) - at(x, y - 1, 1)))
+}
+
+/// Central difference vorticity `dv/dx - du/dy` of an interleaved velocity field, in cell units.
+pub fn vorticity(velocity: &[f32], dimensions: (usize, usize)) -> Vec<f32> {
+    velocity_derivative(velocity, dimensions, |at, x, y| 0.5 * (at(x + 1, y, 1) - at(x - 1, y, 1) - at(x, y + 1, 0) + at(x, y - 1, 0)))
+}
+
+/// Collects frames of named fields and saves them stacked as `[time, y, x(, component)]` arrays, with a `time` array.
+#[derive(Default)]
+pub struct TimeSeries {
+    times: Vec<f32>,
+    fields: Vec<(String, Vec<usize>, Vec<f32>)>
+}
+
+impl TimeSeries {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the `fields` at `time`. Every frame must have the same fields, with the same shapes.
+    pub fn record(&mut self, time: f32, fields: &[(&str, &Array)]) {
+        if self.times.is_empty() {
+            self.fields = fields.iter().map(|(name, array)| (name.to_string(), array.shape.clone(), Vec::new())).collect();
+        }
+        assert_eq!(fields.len(), self.fields.len(), "Every frame must have the same fields.");
+        for ((name, array), (series_name, shape, data)) in fields.iter().zip(&mut self.fields) {
+            assert!(name == series_name && array.shape == *shape, "The {} field changed between the frames.", name);
+            data.extend_from_slice(&array.data);
+        }
+        self.times.push(time);
+    }
+
+    pub fn arrays(&self) -> Vec<(String, Array)> {
+        let mut arrays = vec![("time".to_string(), Array::new(vec![self.times.len()], self.times.clone()))];
+        for (name, shape, data) in &self.fields {
+            let mut shape = shape.clone();
+            shape.insert(0, self.times.len());
+            arrays.push((name.clone(), Array::new(shape, data.clone())));
+        }
+        arrays
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let arrays = self.arrays();
+        let arrays: Vec<(&str, &Array)> = arrays.iter().map(|(name, array)| (name.as_str(), array)).collect();
+        save_npz(path, &arrays)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn npy_round_trip() {
+        let array = Array::from_field((0 .. 12).map(|value| value as f32).collect(), (3, 2), 2);
+        assert_eq!(array.shape, vec![2, 3, 2]);
+        let mut bytes = Vec::new();
+        array.write_npy(&mut bytes).unwrap();
+        assert_eq!((bytes.len() - 12 * 4) % 64, 0);
+        assert!(String::from_utf8_lossy(&bytes[10 .. 128]).starts_with("{'descr': '<f4', 'fortran_order': False, 'shape': (2, 3, 2), }"));
+        assert_eq!(Array::read_npy(&mut &bytes[..]).unwrap(), array);
+    }
+
+    #[test]
+    fn npy_float64_import() {
+        let mut bytes = Vec::new();
+        let mut header = "{'descr': '<f8', 'fortran_order': False, 'shape': (2,), }".to_string();
+        header.push_str(&" ".repeat(128 - 10 - header.len() - 1));
+        header.push('\n');
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&[1, 0]);
+        bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(header.as_bytes());
+        bytes.extend_from_slice(&1.5f64.to_le_bytes());
+        bytes.extend_from_slice(&(-2.0f64).to_le_bytes());
+        assert_eq!(Array::read_npy(&mut &bytes[..]).unwrap(), Array::new(vec![2], vec![1.5, -2.0]));
+    }
+
+    #[test]
+    fn npy_corrupted_shape() {
+        let npy = |shape: &str| {
+            let mut bytes = Vec::new();
+            let header = format!("{{'descr': '<f4', 'fortran_order': False, 'shape': {}, }}\n", shape);
+            bytes.extend_from_slice(MAGIC);
+            bytes.extend_from_slice(&[1, 0]);
+            bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(header.as_bytes());
+            bytes.extend_from_slice(&1.0f32.to_le_bytes());
+            bytes
+        };
+        assert_eq!(Array::read_npy(&mut &npy("(1,)")[..]).unwrap(), Array::new(vec![1], vec![1.0]));
+        for shape in &["(4294967296, 4294967296, 4294967296)", "(1000000,)", ")", "é)", "[1]"] {
+            assert_eq!(Array::read_npy(&mut &npy(shape)[..]).unwrap_err().kind(), ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn components_and_divergence() {
+        // u = x, v = 0 on a 3x1 grid.
+        let velocity = vec![0.0, 0.0, /**/ 1.0, 0.0, /**/ 2.0, 0.0];
+        assert_eq!(Array::component(&velocity, (3, 1), 2, 0).data, vec![0.0, 1.0, 2.0]);
+        assert_eq!(divergence(&velocity, (3, 1)), vec![0.5, 1.0, 0.5]);
+        // u = -y, v = x rotates with a vorticity of 2.
+        let rotation: Vec<f32> = (0 .. 9).flat_map(|index| vec![-((index / 3) as f32), (index % 3) as f32]).collect();
+        assert_eq!(vorticity(&rotation, (3, 3))[4], 2.0);
+    }
+
+    #[test]
+    fn time_series() {
+        let mut series = TimeSeries::new();
+        series.record(0.0, &[("density", &Array::from_field(vec![1.0, 2.0], (2, 1), 1))]);
+        series.record(0.5, &[("density", &Array::from_field(vec![3.0, 4.0], (2, 1), 1))]);
+        let arrays = series.arrays();
+        assert_eq!(arrays[0].1, Array::new(vec![2], vec![0.0, 0.5]));
+        assert_eq!(arrays[1].1, Array::new(vec![2, 1, 2], vec![1.0, 2.0, 3.0, 4.0]));
+        let mut bytes = Vec::new();
+        let arrays: Vec<(&str, &Array)> = arrays.iter().map(|(name, array)| (name.as_str(), array)).collect();
+        write_npz(&mut bytes, &arrays).unwrap();
+        assert_eq!(&bytes[.. 4], b"PK\x03\x04");
+    }
+}
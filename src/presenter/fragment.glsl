@@ -1,15 +1,136 @@
 #version 450
 
-layout(rg32f, location = 0) uniform image2D velocity;
-layout(r32f, location = 1) uniform image2D density;
+layout(rg32f, location = 0) uniform image2D source;
+layout(r32f, location = 1) uniform image2D levelSet;
 layout(location = 2) uniform vec2 resolution;
 layout(location = 3) uniform vec2 fieldResolution;
+layout(r32f, location = 4) uniform image2D obstacle;
+layout(location = 5) uniform int displayMode;
+layout(location = 6) uniform int colorMap;
+layout(location = 7) uniform vec2 valueRange;
+layout(location = 8) uniform bool showColorBar;
+// The size of the field in clip space, under one on the letterboxed axis.
+layout(location = 9) uniform vec2 scale;
+layout(location = 10) uniform bool nearest;
+layout(rgba32f, location = 11) uniform image2D lic;
+// LIC pixels per cell.
+layout(location = 12) uniform float licResolution;
 
 out vec4 color;
 
+#define SCALAR_MODE 0
+#define DIRECTION_MODE 1
+#define LIQUID_SURFACE_MODE 2
+#define LIC_MODE 3
+
+#define GRAYSCALE_MAP 0
+#define VIRIDIS_MAP 1
+#define MAGMA_MAP 2
+#define DIVERGING_MAP 3
+
+// Polynomial fits of the matplotlib colour maps.
+vec3 viridis(float t) {
+    const vec3 c0 = vec3(0.2777273272234177, 0.005407344544966578, 0.3340998053353061);
+    const vec3 c1 = vec3(0.1050930431085774, 1.404613529898575, 1.384590162594685);
+    const vec3 c2 = vec3(-0.3308618287255563, 0.214847559468213, 0.09509516302823659);
+    const vec3 c3 = vec3(-4.634230498983486, -5.799100973351585, -19.33244095627987);
+    const vec3 c4 = vec3(6.228269936347081, 14.17993336680509, 56.69055260068105);
+    const vec3 c5 = vec3(4.776384997670288, -13.74514537774601, -65.35303263337234);
+    const vec3 c6 = vec3(-5.435455855934631, 4.645852612178535, 26.3124352495832);
+    return c0 + t * (c1 + t * (c2 + t * (c3 + t * (c4 + t * (c5 + t * c6)))));
+}
+
+vec3 magma(float t) {
+    const vec3 c0 = vec3(-0.002136485053939582, -0.000749655052795221, -0.005386127855323933);
+    const vec3 c1 = vec3(0.2516605407371642, 0.6775232436837668, 2.494026599312351);
+    const vec3 c2 = vec3(8.353717279216625, -3.577719514958484, 0.3144679030132573);
+    const vec3 c3 = vec3(-27.66873308576866, 14.26473078096533, -13.64921318813922);
+    const vec3 c4 = vec3(52.17613981234068, -27.94360607168351, 12.94416944238394);
+    const vec3 c5 = vec3(-50.76852536473588, 29.04658282127291, 4.23415299384598);
+    const vec3 c6 = vec3(18.65570506591883, -11.48977351997711, -5.601961508734096);
+    return c0 + t * (c1 + t * (c2 + t * (c3 + t * (c4 + t * (c5 + t * c6)))));
+}
+
+// Blue to white to red, for signed fields centered on zero.
+vec3 diverging(float t) {
+    vec3 blue = vec3(0.23, 0.30, 0.75);
+    vec3 white = vec3(0.87, 0.87, 0.87);
+    vec3 red = vec3(0.71, 0.02, 0.15);
+    return t < 0.5 ? mix(blue, white, t * 2.0) : mix(white, red, t * 2.0 - 1.0);
+}
+
+vec3 map(float t) {
+    t = clamp(t, 0.0, 1.0);
+    if (colorMap == VIRIDIS_MAP) return viridis(t);
+    if (colorMap == MAGMA_MAP) return magma(t);
+    if (colorMap == DIVERGING_MAP) return diverging(t);
+    return vec3(t);
+}
+
+// Image uniforms can't be passed to functions, so the sampling is written as macros over the globals set by setupSampling.
+ivec2 base;
+vec2 weight;
+
+void setupSampling(vec2 position) {
+    if (nearest) {
+        base = ivec2(floor(position + 0.5));
+        weight = vec2(0.0);
+    } else {
+        base = ivec2(floor(position));
+        weight = position - floor(position);
+    }
+}
+
+#define LOAD(image, coord) imageLoad(image, clamp(coord, ivec2(0), imageSize(image) - 1))
+#define SAMPLE(image) mix(mix(LOAD(image, base), LOAD(image, base + ivec2(1, 0)), weight.x), \
+                          mix(LOAD(image, base + ivec2(0, 1)), LOAD(image, base + ivec2(1, 1)), weight.x), weight.y)
+
+vec3 hsv2rgb(vec3 c) {
+    vec4 k = vec4(1.0, 2.0 / 3.0, 1.0 / 3.0, 3.0);
+    vec3 p = abs(fract(c.xxx + k.xyz) * 6.0 - k.www);
+    return c.z * mix(k.xxx, clamp(p - k.xxx, 0.0, 1.0), c.y);
+}
+
+float normalized(float value) {
+    return (value - valueRange.x) / max(valueRange.y - valueRange.x, 1e-20);
+}
+
 void main() {
-    ivec2 coord = ivec2(gl_FragCoord.xy);
-    float density = imageLoad(density, coord).x;
-    vec2 velocity = imageLoad(velocity, coord).xy;
-    color = vec4(vec3(density), 1.0);
- }
\ No newline at end of file
+    // The colour bar is a vertical strip on the right side, from the minimum at the bottom to the maximum at the top.
+    vec2 uv = gl_FragCoord.xy / resolution;
+    if (showColorBar && displayMode != LIQUID_SURFACE_MODE && displayMode != LIC_MODE && uv.x > 0.94 && uv.x < 0.97 && uv.y > 0.1 && uv.y < 0.9) {
+        float t = (uv.y - 0.1) / 0.8;
+        color = vec4(displayMode == DIRECTION_MODE ? vec3(t) : map(t), 1.0);
+        return;
+    }
+
+    // Cell centers are on integer coordinates, so the field covers [-0.5, fieldResolution - 0.5].
+    vec2 ndc = uv * 2.0 - 1.0;
+    vec2 position = (ndc / scale + 1.0) * 0.5 * fieldResolution - 0.5;
+    if (any(lessThan(position, vec2(-0.5))) || any(greaterThan(position, fieldResolution - 0.5))) {
+        color = vec4(0.0, 0.0, 0.0, 1.0);
+        return;
+    }
+    setupSampling(position);
+    vec2 value = SAMPLE(source).xy;
+    if (displayMode == DIRECTION_MODE) {
+        // The source is the velocity, interpolated before taking the angle so it doesn't wrap between texels.
+        // The hue is the direction and the value is the magnitude.
+        float hue = atan(value.y, value.x) / (2.0 * 3.14159265) + 0.5;
+        color = vec4(hsv2rgb(vec3(hue, 1.0, clamp(normalized(length(value)), 0.0, 1.0))), 1.0);
+    } else if (displayMode == LIQUID_SURFACE_MODE) {
+        float phi = SAMPLE(levelSet).x;
+        vec3 air = vec3(0.9, 0.95, 1.0);
+        vec3 liquid = mix(vec3(0.1, 0.4, 0.8), vec3(0.0, 0.1, 0.4), clamp(-phi / 32.0, 0.0, 1.0));
+        color = vec4(phi > 0.0 ? air : liquid, 1.0);
+        // The surface is drawn as a line where the distance is under a cell.
+        if (abs(phi) < 1.0) color = vec4(0.0, 0.05, 0.2, 1.0);
+    } else if (displayMode == LIC_MODE) {
+        setupSampling((position + 0.5) * licResolution - 0.5);
+        color = vec4(SAMPLE(lic).rgb, 1.0);
+        setupSampling(position);
+    } else {
+        color = vec4(map(normalized(value.x)), 1.0);
+    }
+    if (SAMPLE(obstacle).x > 0.5) color = vec4(0.2, 0.3, 0.6, 1.0);
+}
@@ -0,0 +1,78 @@
+#version 450
+
+layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+layout(rg32f, location = 0) uniform image2D velocity;
+layout(r32f, location = 1) uniform image2D density;
+layout(r32f, location = 2) uniform image2D noise;
+layout(rgba32f, location = 3) uniform image2D image;
+// Kernel half length, in image pixels.
+layout(location = 4) uniform float kernelLength;
+layout(location = 5) uniform int tint;
+// The speed at the top of the magnitude tint.
+layout(location = 6) uniform float tintSpeed;
+
+#define NO_TINT 0
+#define MAGNITUDE_TINT 1
+#define DENSITY_TINT 2
+
+const float STEP = 0.5;
+
+vec2 velocityAt(ivec2 coord) {
+    return imageLoad(velocity, clamp(coord, ivec2(0), imageSize(velocity) - 1)).xy;
+}
+
+// The field velocity at an image position.
+vec2 sampleVelocity(vec2 position) {
+    vec2 field = (position + 0.5) / vec2(imageSize(image)) * vec2(imageSize(velocity)) - 0.5;
+    ivec2 base = ivec2(floor(field));
+    vec2 t = field - floor(field);
+    return mix(mix(velocityAt(base), velocityAt(base + ivec2(1, 0)), t.x),
+               mix(velocityAt(base + ivec2(0, 1)), velocityAt(base + ivec2(1, 1)), t.x), t.y);
+}
+
+bool inside(vec2 position) {
+    return all(greaterThanEqual(position, vec2(-0.5))) && all(lessThan(position, vec2(imageSize(image)) - 0.5));
+}
+
+// Sums the noise along the streamline in one direction, with a triangle kernel.
+void convolve(vec2 position, float direction, inout float sum, inout float weight) {
+    int steps = int(kernelLength / STEP);
+    for (int i = 1; i <= steps; i++) {
+        vec2 v = sampleVelocity(position);
+        float speed = length(v);
+        if (speed < 1e-6) break;
+        // Midpoint step along the normalized velocity, so the kernel length doesn't depend on the speed.
+        vec2 midpoint = position + direction * 0.5 * STEP * v / speed;
+        v = sampleVelocity(midpoint);
+        speed = length(v);
+        if (speed < 1e-6) break;
+        position += direction * STEP * v / speed;
+        if (!inside(position)) break;
+        float w = 1.0 - float(i) / float(steps + 1);
+        sum += w * imageLoad(noise, ivec2(round(position))).x;
+        weight += w;
+    }
+}
+
+void main() {
+    ivec2 coord = ivec2(gl_GlobalInvocationID.xy);
+    vec2 position = vec2(coord);
+    float sum = imageLoad(noise, coord).x;
+    float weight = 1.0;
+    convolve(position, 1.0, sum, weight);
+    convolve(position, -1.0, sum, weight);
+    // Averaging flattens the noise, so the contrast is stretched back.
+    float value = clamp(0.5 + (sum / weight - 0.5) * 3.0, 0.0, 1.0);
+
+    vec3 color = vec3(1.0);
+    vec2 field = (position + 0.5) / vec2(imageSize(image)) * vec2(imageSize(velocity)) - 0.5;
+    if (tint == MAGNITUDE_TINT) {
+        float t = clamp(length(sampleVelocity(position)) / max(tintSpeed, 1e-20), 0.0, 1.0);
+        color = mix(vec3(0.2, 0.4, 1.0), vec3(1.0, 0.3, 0.1), t);
+    } else if (tint == DENSITY_TINT) {
+        float t = clamp(imageLoad(density, ivec2(round(field))).x, 0.0, 1.0);
+        color = mix(vec3(1.0), vec3(1.0, 0.6, 0.2), t);
+    }
+    imageStore(image, coord, vec4(color * value, 1.0));
+}
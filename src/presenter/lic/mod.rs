@@ -0,0 +1,122 @@
+use crate::context::Context;
+use crate::fluid::Fluid;
+
+/// What colours the LIC image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LicTint {
+    None,
+    /// Blue to red up to the speed.
+    Magnitude(f32),
+    Density
+}
+
+impl LicTint {
+    fn id(&self) -> i32 {
+        match self {
+            LicTint::None         => 0,
+            LicTint::Magnitude(_) => 1,
+            LicTint::Density      => 2
+        }
+    }
+}
+
+/// Line integral convolution: blurs white noise along the streamlines of the velocity.
+pub struct LicRenderer {
+    program: gpu::ComputeProgram,
+    noise_field: Option<gpu::Texture2D>,
+    image: Option<gpu::Texture2D>,
+    /// Kernel half length, in image pixels.
+    pub length: f32,
+    pub tint: LicTint
+}
+
+impl LicRenderer {
+    pub fn new(context: &Context) -> Self {
+        let shader = gpu::ComputeShader::new(&context.context, include_str!("lic_2d.glsl")).expect("Couldn't create ComputeShader.");
+        let program = gpu::ComputeProgram::new(&context.context, &shader).expect("Couldn't create ComputeProgram.");
+        let noise_field = None;
+        let image = None;
+        let length = 15.0;
+        let tint = LicTint::None;
+        Self { program, noise_field, image, length, tint }
+    }
+
+    /// Renders the LIC of `fluid` into an RGBA image of `dimensions`, which can be finer than the field.
+    pub fn render(&mut self, context: &Context, fluid: &Fluid, dimensions: (usize, usize)) -> &gpu::Texture2D {
+        const VELOCITY_FIELD_LOCATION : usize = 0;
+        const DENSITY_FIELD_LOCATION  : usize = 1;
+        const NOISE_FIELD_LOCATION    : usize = 2;
+        const IMAGE_LOCATION          : usize = 3;
+        const KERNEL_LENGTH_LOCATION  : usize = 4;
+        const TINT_LOCATION           : usize = 5;
+        const TINT_SPEED_LOCATION     : usize = 6;
+        if self.image.as_ref().map(|image| image.dimensions()) != Some(dimensions) {
+            let noise_format = gpu::TextureFormat::new(gpu::ColorFormat::R, gpu::Type::F32);
+            let noise: Vec<f32> = (0 .. dimensions.0 * dimensions.1).map(noise).collect();
+            self.noise_field = Some(gpu::Texture2D::from_data(&context.context, dimensions, &noise_format, &noise, &noise_format));
+            let image_format = gpu::TextureFormat::new(gpu::ColorFormat::components(4), gpu::Type::F32);
+            self.image = Some(gpu::Texture2D::allocate(&context.context, dimensions, &image_format));
+        }
+        let noise_field = self.noise_field.as_ref().expect("The noise is allocated.");
+        let image = self.image.as_ref().expect("The image is allocated.");
+        let tint_speed = match self.tint {
+            LicTint::Magnitude(speed) => speed,
+            _ => 0.0
+        };
+        self.program.bind_image_2d(&fluid.velocity_field, VELOCITY_FIELD_LOCATION);
+        self.program.bind_image_2d(&fluid.density_field, DENSITY_FIELD_LOCATION);
+        self.program.bind_image_2d(noise_field, NOISE_FIELD_LOCATION);
+        self.program.bind_image_2d(image, IMAGE_LOCATION);
+        self.program.bind_f32(self.length, KERNEL_LENGTH_LOCATION);
+        self.program.bind_i32(self.tint.id(), TINT_LOCATION);
+        self.program.bind_f32(tint_speed, TINT_SPEED_LOCATION);
+        self.program.compute((dimensions.0, dimensions.1, 1));
+        //FIXME: How to expose it on the GPU API?
+        // Ref: https://www.khronos.org/registry/OpenGL-Refpages/gl4/html/glMemoryBarrier.xhtml
+        unsafe {
+            gl::MemoryBarrier(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+        }
+        image
+    }
+
+    /// Renders offscreen and reads the image back as RGBA8 pixels, top row first.
+    pub fn export(&mut self, context: &Context, fluid: &Fluid, dimensions: (usize, usize)) -> Vec<u8> {
+        let data: Vec<f32> = self.render(context, fluid, dimensions).data();
+        to_rgba8(&data, dimensions)
+    }
+}
+
+/// Converts RGBA32F data, bottom row first like the textures, to RGBA8 pixels, top row first like the image files.
+pub fn to_rgba8(data: &[f32], dimensions: (usize, usize)) -> Vec<u8> {
+    let row = dimensions.0 * 4;
+    data.chunks(row).rev().flatten().map(|value| (value.clamp(0.0, 1.0) * 255.0).round() as u8).collect()
+}
+
+// White noise from an integer hash, so the pattern is the same between runs.
+fn noise(index: usize) -> f32 {
+    let mut value = index as u32;
+    value ^= value >> 16;
+    value = value.wrapping_mul(0x7feb_352d);
+    value ^= value >> 15;
+    value = value.wrapping_mul(0x846c_a68b);
+    value ^= value >> 16;
+    value as f32 / std::u32::MAX as f32
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rgba8_flips_rows() {
+        let data = vec![
+            0.0, 0.0, 0.0, 1.0, /**/ 1.0, 1.0, 1.0, 1.0,
+            0.5, 2.0, -1.0, 1.0, /**/ 0.0, 0.0, 0.0, 0.0
+        ];
+        let pixels = to_rgba8(&data, (2, 2));
+        assert_eq!(pixels, vec![
+            128, 255, 0, 255, /**/ 0, 0, 0, 0,
+            0, 0, 0, 255, /**/ 255, 255, 255, 255
+        ]);
+    }
+}
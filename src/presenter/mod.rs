@@ -1,10 +1,201 @@
 use crate::Context;
 use crate::fluid::Fluid;
+use crate::tracer::Tracers;
+use crate::streamline::Polyline;
+use crate::simulator::{Reducer, Reduction};
+
+mod lic;
+
+pub use lic::{LicRenderer, LicTint, to_rgba8};
+
+/// What the `Presenter` draws.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DisplayMode {
+    Density,
+    Temperature,
+    VelocityMagnitude,
+    /// The direction as the hue and the magnitude as the value.
+    VelocityDirection,
+    Pressure,
+    Divergence,
+    Vorticity,
+    LiquidSurface,
+    /// Line integral convolution of the velocity.
+    Lic
+}
+
+impl DisplayMode {
+    pub fn next(&self) -> Self {
+        match self {
+            DisplayMode::Density           => DisplayMode::Temperature,
+            DisplayMode::Temperature       => DisplayMode::VelocityMagnitude,
+            DisplayMode::VelocityMagnitude => DisplayMode::VelocityDirection,
+            DisplayMode::VelocityDirection => DisplayMode::Pressure,
+            DisplayMode::Pressure          => DisplayMode::Divergence,
+            DisplayMode::Divergence        => DisplayMode::Vorticity,
+            DisplayMode::Vorticity         => DisplayMode::LiquidSurface,
+            DisplayMode::LiquidSurface     => DisplayMode::Lic,
+            DisplayMode::Lic               => DisplayMode::Density
+        }
+    }
+
+    /// Whether there's something to show: the pressure only exists after a projection, the liquid surface in liquid mode.
+    pub fn is_available(&self, has_pressure: bool, has_level_set: bool) -> bool {
+        match self {
+            DisplayMode::Pressure      => has_pressure,
+            DisplayMode::LiquidSurface => has_level_set,
+            _                          => true
+        }
+    }
+
+    /// The `next` mode that `is_available`.
+    pub fn next_available(&self, has_pressure: bool, has_level_set: bool) -> Self {
+        let mut display_mode = self.next();
+        while !display_mode.is_available(has_pressure, has_level_set) {
+            display_mode = display_mode.next();
+        }
+        display_mode
+    }
+
+    /// The field evaluated by `source_2d.glsl`.
+    fn source_id(&self) -> i32 {
+        match self {
+            DisplayMode::Density | DisplayMode::LiquidSurface => 0,
+            DisplayMode::Lic                                  => 0,
+            DisplayMode::VelocityMagnitude                    => 1,
+            DisplayMode::Pressure                             => 2,
+            DisplayMode::Divergence                           => 3,
+            DisplayMode::Vorticity                            => 4,
+            DisplayMode::VelocityDirection                    => 5,
+            DisplayMode::Temperature                          => 6
+        }
+    }
+
+    /// How `fragment.glsl` colours the source.
+    fn id(&self) -> i32 {
+        match self {
+            DisplayMode::VelocityDirection => 1,
+            DisplayMode::LiquidSurface     => 2,
+            DisplayMode::Lic               => 3,
+            _                              => 0
+        }
+    }
+
+    /// The fields with both signs, centered on zero.
+    pub fn is_signed(&self) -> bool {
+        matches!(self, DisplayMode::Pressure | DisplayMode::Divergence | DisplayMode::Vorticity)
+    }
+
+    pub fn default_color_map(&self) -> ColorMap {
+        match self {
+            DisplayMode::Density           => ColorMap::Grayscale,
+            DisplayMode::VelocityMagnitude => ColorMap::Viridis,
+            _ if self.is_signed()          => ColorMap::Diverging,
+            _                              => ColorMap::Magma
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorMap {
+    Grayscale,
+    Viridis,
+    Magma,
+    /// Blue to white to red, for the signed fields.
+    Diverging
+}
+
+impl ColorMap {
+    fn id(&self) -> i32 {
+        match self {
+            ColorMap::Grayscale => 0,
+            ColorMap::Viridis   => 1,
+            ColorMap::Magma     => 2,
+            ColorMap::Diverging => 3
+        }
+    }
+}
+
+/// The values mapped to the ends of the `ColorMap`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ValueRange {
+    /// The range of the current frame, reduced on the GPU, symmetric around zero for the signed fields.
+    Auto,
+    Fixed(f32, f32)
+}
+
+/// The value range from the `minimum` and the `maximum` of the field.
+fn auto_range(minimum: f32, maximum: f32, symmetric: bool) -> (f32, f32) {
+    if !(minimum <= maximum) {
+        return (0.0, 1.0);
+    }
+    if symmetric {
+        let extent = minimum.abs().max(maximum.abs());
+        (-extent, extent)
+    } else {
+        (minimum, maximum)
+    }
+}
+
+/// How the field fills the window.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fit {
+    Stretch,
+    /// Letterboxes the field to keep its aspect ratio.
+    PreserveAspect
+}
+
+impl Fit {
+    /// The size of the field in clip space, from -scale to scale.
+    pub fn scale(&self, viewport_dimensions: (usize, usize), field_dimensions: (usize, usize)) -> (f32, f32) {
+        match self {
+            Fit::Stretch => (1.0, 1.0),
+            Fit::PreserveAspect => {
+                let viewport_aspect = viewport_dimensions.0 as f32 / viewport_dimensions.1 as f32;
+                let field_aspect = field_dimensions.0 as f32 / field_dimensions.1 as f32;
+                if viewport_aspect > field_aspect {
+                    (field_aspect / viewport_aspect, 1.0)
+                } else {
+                    (1.0, viewport_aspect / field_aspect)
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sampling {
+    Bilinear,
+    Nearest
+}
+
+/// How the `Tracers` are drawn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TracerStyle {
+    Points,
+    Trails
+}
 
 pub struct Presenter {
     pub raster_program: gpu::RasterProgram,
-    pub framebuffer: gpu::Framebuffer,
-    pub vertex_array_object: gpu::VertexArrayObject
+    pub vertex_array_object: gpu::VertexArrayObject,
+    pub tracer_program: gpu::RasterProgram,
+    pub polyline_program: gpu::RasterProgram,
+    source_program: gpu::ComputeProgram,
+    source_field: Option<gpu::Texture2D>,
+    /// The displayed value alone, reduced for `ValueRange::Auto`.
+    value_field: Option<gpu::Texture2D>,
+    reducer: Option<Reducer>,
+    pub display_mode: DisplayMode,
+    /// Overrides the `DisplayMode::default_color_map`.
+    pub color_map: Option<ColorMap>,
+    pub value_range: ValueRange,
+    pub show_color_bar: bool,
+    pub fit: Fit,
+    pub sampling: Sampling,
+    pub lic_renderer: LicRenderer,
+    /// LIC pixels per cell.
+    pub lic_resolution: usize
 }
 
 impl Presenter {
@@ -12,20 +203,252 @@ impl Presenter {
         let fragment_shader = gpu::FragmentShader::new(&context.context, include_str!("fragment.glsl")).expect("Couldn't create FragmentShader.");
         let vertex_shader = gpu::VertexShader::new(&context.context, include_str!("vertex.glsl")).expect("Couldn't create VertexShader.");
         let raster_program = gpu::RasterProgram::new(&context.context, &fragment_shader, &vertex_shader).expect("Couldn't create RasterProgram.");
-        let framebuffer = gpu::Framebuffer::default(&context.context);
         let vertex_array_object = gpu::VertexArrayObject::new(&context.context);
-        Self { raster_program, framebuffer, vertex_array_object }
+        let fragment_shader = gpu::FragmentShader::new(&context.context, include_str!("tracer_fragment.glsl")).expect("Couldn't create FragmentShader.");
+        let vertex_shader = gpu::VertexShader::new(&context.context, include_str!("tracer_vertex.glsl")).expect("Couldn't create VertexShader.");
+        let tracer_program = gpu::RasterProgram::new(&context.context, &fragment_shader, &vertex_shader).expect("Couldn't create RasterProgram.");
+        let fragment_shader = gpu::FragmentShader::new(&context.context, include_str!("polyline_fragment.glsl")).expect("Couldn't create FragmentShader.");
+        let vertex_shader = gpu::VertexShader::new(&context.context, include_str!("polyline_vertex.glsl")).expect("Couldn't create VertexShader.");
+        let polyline_program = gpu::RasterProgram::new(&context.context, &fragment_shader, &vertex_shader).expect("Couldn't create RasterProgram.");
+        let source_shader = gpu::ComputeShader::new(&context.context, include_str!("source_2d.glsl")).expect("Couldn't create ComputeShader.");
+        let source_program = gpu::ComputeProgram::new(&context.context, &source_shader).expect("Couldn't create ComputeProgram.");
+        let source_field = None;
+        let value_field = None;
+        let reducer = None;
+        let display_mode = DisplayMode::Density;
+        let color_map = None;
+        let value_range = ValueRange::Fixed(0.0, 1.0);
+        let show_color_bar = false;
+        let fit = Fit::PreserveAspect;
+        let sampling = Sampling::Bilinear;
+        let lic_renderer = LicRenderer::new(context);
+        let lic_resolution = 2;
+        Self { raster_program, vertex_array_object, tracer_program, polyline_program, source_program, source_field, value_field, reducer, display_mode, color_map, value_range, show_color_bar, fit, sampling, lic_renderer, lic_resolution }
+    }
+
+    pub fn scale(&self, context: &Context, field_dimensions: (usize, usize)) -> (f32, f32) {
+        self.fit.scale(context.dimensions, field_dimensions)
     }
 
-    pub fn present(&mut self, context: &Context, fluid: &Fluid) {
-        const VELOCITY_FIELD_LOCATION      : usize = 0;
-        const DENSITY_FIELD_LOCATION       : usize = 1;
+    /// Converts a window position (origin on the top left) to field coordinates (origin on the bottom left, cell centers on integers).
+    pub fn window_to_field(&self, context: &Context, position: (f32, f32), field_dimensions: (usize, usize)) -> (f32, f32) {
+        let scale = self.scale(context, field_dimensions);
+        let ndc = (position.0 / context.dimensions.0 as f32 * 2.0 - 1.0, 1.0 - position.1 / context.dimensions.1 as f32 * 2.0);
+        let x = (ndc.0 / scale.0 + 1.0) * 0.5 * field_dimensions.0 as f32 - 0.5;
+        let y = (ndc.1 / scale.1 + 1.0) * 0.5 * field_dimensions.1 as f32 - 0.5;
+        (x, y)
+    }
+
+    /// Draws the field selected by the `display_mode`. When it isn't `DisplayMode::is_available`, it falls back to the density.
+    pub fn present(&mut self, context: &Context, fluid: &Fluid, pressure_field: Option<&gpu::Texture2D>) {
+        const SOURCE_FIELD_LOCATION        : usize = 0;
+        const LEVEL_SET_FIELD_LOCATION     : usize = 1;
         const VIEWPORT_DIMENSIONS_LOCATION : usize = 2;
         const FIELD_DIMENSIONS_LOCATION    : usize = 3;
-        self.raster_program.bind_image_2d(&fluid.velocity_field, VELOCITY_FIELD_LOCATION);
-        self.raster_program.bind_image_2d(&fluid.density_field, DENSITY_FIELD_LOCATION);
+        const OBSTACLE_FIELD_LOCATION      : usize = 4;
+        const DISPLAY_MODE_LOCATION        : usize = 5;
+        const COLOR_MAP_LOCATION           : usize = 6;
+        const VALUE_RANGE_LOCATION         : usize = 7;
+        const SHOW_COLOR_BAR_LOCATION      : usize = 8;
+        const SCALE_LOCATION               : usize = 9;
+        const NEAREST_LOCATION             : usize = 10;
+        const LIC_IMAGE_LOCATION           : usize = 11;
+        const LIC_RESOLUTION_LOCATION      : usize = 12;
+        let display_mode = match (&self.display_mode, &fluid.level_set_field, pressure_field) {
+            (DisplayMode::LiquidSurface, Some(level_set_field), _) => {
+                self.raster_program.bind_image_2d(level_set_field, LEVEL_SET_FIELD_LOCATION);
+                DisplayMode::LiquidSurface
+            },
+            (DisplayMode::LiquidSurface, None, _) | (DisplayMode::Pressure, _, None) => DisplayMode::Density,
+            (display_mode, _, _) => *display_mode
+        };
+        if display_mode == DisplayMode::Lic {
+            let dimensions = (fluid.dimensions.0 * self.lic_resolution, fluid.dimensions.1 * self.lic_resolution);
+            let image = self.lic_renderer.render(context, fluid, dimensions);
+            self.raster_program.bind_image_2d(image, LIC_IMAGE_LOCATION);
+            self.raster_program.bind_f32(self.lic_resolution as f32, LIC_RESOLUTION_LOCATION);
+        }
+        self.evaluate_source(context, fluid, pressure_field, display_mode);
+        let source_field = self.source_field.as_ref().expect("The source field is evaluated.");
+        let value_range = match self.value_range {
+            ValueRange::Auto => self.auto_value_range(display_mode),
+            ValueRange::Fixed(minimum, maximum) => (minimum, maximum)
+        };
+        let color_map = self.color_map.unwrap_or_else(|| display_mode.default_color_map());
+        self.raster_program.bind_image_2d(source_field, SOURCE_FIELD_LOCATION);
         self.raster_program.bind_vec2((context.dimensions.0 as f32, context.dimensions.1 as f32), VIEWPORT_DIMENSIONS_LOCATION);
         self.raster_program.bind_vec2((fluid.dimensions.0 as f32, fluid.dimensions.1 as f32), FIELD_DIMENSIONS_LOCATION);
-        self.raster_program.raster(&self.framebuffer, &self.vertex_array_object, gpu::RasterGeometry::Points, 1);
+        self.raster_program.bind_image_2d(&fluid.obstacle_field, OBSTACLE_FIELD_LOCATION);
+        self.raster_program.bind_i32(display_mode.id(), DISPLAY_MODE_LOCATION);
+        self.raster_program.bind_i32(color_map.id(), COLOR_MAP_LOCATION);
+        self.raster_program.bind_vec2(value_range, VALUE_RANGE_LOCATION);
+        self.raster_program.bind_bool(self.show_color_bar, SHOW_COLOR_BAR_LOCATION);
+        self.raster_program.bind_vec2(self.scale(context, fluid.dimensions), SCALE_LOCATION);
+        self.raster_program.bind_bool(self.sampling == Sampling::Nearest, NEAREST_LOCATION);
+        self.raster_program.raster(&context.framebuffer, &self.vertex_array_object, gpu::RasterGeometry::Triangles, 6);
+    }
+
+    /// The `ValueRange::Auto` of the last evaluated source.
+    fn auto_value_range(&self, display_mode: DisplayMode) -> (f32, f32) {
+        let value_field = self.value_field.as_ref().expect("The value field is evaluated.");
+        let reducer = self.reducer.as_ref().expect("The reducer is allocated with the value field.");
+        let maximum = reducer.reduce_scalar(value_field, Reduction::Max);
+        match display_mode {
+            // The value is the velocity magnitude, it starts from zero so the hue stays readable on slow flows.
+            DisplayMode::VelocityDirection => auto_range(0.0, maximum, false),
+            _ => auto_range(reducer.reduce_scalar(value_field, Reduction::Min), maximum, display_mode.is_signed())
+        }
+    }
+
+    /// Evaluates the displayed quantity into the `source_field`, and its value alone into the `value_field`.
+    fn evaluate_source(&mut self, context: &Context, fluid: &Fluid, pressure_field: Option<&gpu::Texture2D>, display_mode: DisplayMode) {
+        const VELOCITY_FIELD_LOCATION    : usize = 0;
+        const DENSITY_FIELD_LOCATION     : usize = 1;
+        const PRESSURE_FIELD_LOCATION    : usize = 2;
+        const SOURCE_FIELD_LOCATION      : usize = 3;
+        const SOURCE_ID_LOCATION         : usize = 4;
+        const TEMPERATURE_FIELD_LOCATION : usize = 5;
+        const VALUE_FIELD_LOCATION       : usize = 6;
+        if self.source_field.as_ref().map(|source_field| source_field.dimensions()) != Some(fluid.dimensions) {
+            let format = gpu::TextureFormat::new(gpu::ColorFormat::RG, gpu::Type::F32);
+            self.source_field = Some(gpu::Texture2D::allocate(&context.context, fluid.dimensions, &format));
+            let format = gpu::TextureFormat::new(gpu::ColorFormat::R, gpu::Type::F32);
+            self.value_field = Some(gpu::Texture2D::allocate(&context.context, fluid.dimensions, &format));
+            self.reducer = Some(Reducer::new(context, fluid.dimensions));
+        }
+        let source_field = self.source_field.as_ref().expect("The source field is allocated.");
+        let value_field = self.value_field.as_ref().expect("The value field is allocated.");
+        self.source_program.bind_image_2d(&fluid.velocity_field, VELOCITY_FIELD_LOCATION);
+        self.source_program.bind_image_2d(&fluid.density_field, DENSITY_FIELD_LOCATION);
+        self.source_program.bind_image_2d(&fluid.temperature_field, TEMPERATURE_FIELD_LOCATION);
+        if let Some(pressure_field) = pressure_field {
+            self.source_program.bind_image_2d(pressure_field, PRESSURE_FIELD_LOCATION);
+        }
+        self.source_program.bind_image_2d(source_field, SOURCE_FIELD_LOCATION);
+        self.source_program.bind_image_2d(value_field, VALUE_FIELD_LOCATION);
+        self.source_program.bind_i32(display_mode.source_id(), SOURCE_ID_LOCATION);
+        self.source_program.compute((fluid.dimensions.0, fluid.dimensions.1, 1));
+        //FIXME: How to expose it on the GPU API?
+        // Ref: https://www.khronos.org/registry/OpenGL-Refpages/gl4/html/glMemoryBarrier.xhtml
+        unsafe {
+            gl::MemoryBarrier(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+        }
+    }
+
+    /// Draws the `tracers` over the field.
+    pub fn present_tracers(&mut self, context: &Context, fluid: &Fluid, tracers: &Tracers, style: TracerStyle) {
+        const TRACER_FIELD_LOCATION     : usize = 0;
+        const TRAIL_FIELD_LOCATION      : usize = 1;
+        const FIELD_DIMENSIONS_LOCATION : usize = 2;
+        const HEAD_LOCATION             : usize = 3;
+        const DRAW_TRAILS_LOCATION      : usize = 4;
+        const SCALE_LOCATION            : usize = 5;
+        self.tracer_program.bind_image_2d(&tracers.tracer_field, TRACER_FIELD_LOCATION);
+        self.tracer_program.bind_image_2d(&tracers.trail_field, TRAIL_FIELD_LOCATION);
+        self.tracer_program.bind_vec2((fluid.dimensions.0 as f32, fluid.dimensions.1 as f32), FIELD_DIMENSIONS_LOCATION);
+        self.tracer_program.bind_i32(tracers.head as i32, HEAD_LOCATION);
+        self.tracer_program.bind_vec2(self.scale(context, fluid.dimensions), SCALE_LOCATION);
+        match style {
+            TracerStyle::Points => {
+                self.tracer_program.bind_bool(false, DRAW_TRAILS_LOCATION);
+                self.tracer_program.raster(&context.framebuffer, &self.vertex_array_object, gpu::RasterGeometry::Points, tracers.count);
+            },
+            TracerStyle::Trails => {
+                let vertices = tracers.count * (tracers.trail_length - 1) * 2;
+                self.tracer_program.bind_bool(true, DRAW_TRAILS_LOCATION);
+                self.tracer_program.raster(&context.framebuffer, &self.vertex_array_object, gpu::RasterGeometry::Lines, vertices);
+            }
+        }
     }
-}
\ No newline at end of file
+
+    /// Draws streamlines or pathlines over the field.
+    pub fn present_polylines(&mut self, context: &Context, fluid: &Fluid, polylines: &[Polyline]) {
+        const FIELD_DIMENSIONS_LOCATION : usize = 0;
+        const SCALE_LOCATION            : usize = 1;
+        let mut data = Vec::new();
+        for polyline in polylines {
+            for segment in polyline.points.windows(2) {
+                data.extend_from_slice(&[segment[0].0, segment[0].1, segment[1].0, segment[1].1]);
+            }
+        }
+        if data.is_empty() {
+            return;
+        }
+        let buffer = gpu::Buffer::from_data(&context.context, &data);
+        let mut vertex_array_object = gpu::VertexArrayObject::new(&context.context);
+        vertex_array_object.set_vertex_buffer(&buffer, 0, 2);
+        vertex_array_object.set_vertices(data.len() / 2);
+        self.polyline_program.bind_vec2((fluid.dimensions.0 as f32, fluid.dimensions.1 as f32), FIELD_DIMENSIONS_LOCATION);
+        self.polyline_program.bind_vec2(self.scale(context, fluid.dimensions), SCALE_LOCATION);
+        self.polyline_program.raster(&context.framebuffer, &vertex_array_object, gpu::RasterGeometry::Lines, vertex_array_object.get_vertices());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::field::Field;
+
+    #[test]
+    fn auto_ranges() {
+        assert_eq!(auto_range(-1.0, 3.0, false), (-1.0, 3.0));
+        assert_eq!(auto_range(-1.0, 3.0, true), (-3.0, 3.0));
+        assert_eq!(auto_range(std::f32::NAN, 3.0, false), (0.0, 1.0));
+    }
+
+    #[test]
+    fn sources() {
+        let dimensions = (3, 1);
+        let context = Context::new(dimensions);
+        let mut presenter = Presenter::new(&context);
+        let mut fluid = Fluid::new(&context, dimensions, 0.0, 0.0);
+        fluid.density_field = Field::from_data(&context, dimensions, 1, &[0.0, 0.25, 0.5]).field;
+        fluid.temperature_field = Field::from_data(&context, dimensions, 1, &[-1.0, 0.5, 2.0]).field;
+        fluid.velocity_field = Field::from_data(&context, dimensions, 2, &[3.0, 4.0, /**/ 0.0, 0.0, /**/ 0.0, -1.0]).field;
+        let values = |presenter: &Presenter| presenter.value_field.as_ref().expect("The value field is evaluated.").data() as Vec<f32>;
+
+        presenter.evaluate_source(&context, &fluid, None, DisplayMode::Density);
+        assert_eq!((values(&presenter), presenter.auto_value_range(DisplayMode::Density)), (vec![0.0, 0.25, 0.5], (0.0, 0.5)));
+        presenter.evaluate_source(&context, &fluid, None, DisplayMode::Temperature);
+        assert_eq!((values(&presenter), presenter.auto_value_range(DisplayMode::Temperature)), (vec![-1.0, 0.5, 2.0], (-1.0, 2.0)));
+
+        // The direction keeps the velocity in the source, its range is the magnitude.
+        presenter.evaluate_source(&context, &fluid, None, DisplayMode::VelocityDirection);
+        let source: Vec<f32> = presenter.source_field.as_ref().expect("The source field is evaluated.").data();
+        assert_eq!(source, vec![3.0, 4.0, 0.0, 0.0, 0.0, -1.0]);
+        let (magnitudes, range) = (values(&presenter), presenter.auto_value_range(DisplayMode::VelocityDirection));
+        assert!((magnitudes[0] - 5.0).abs() < 1e-5 && magnitudes[1 ..] == [0.0, 1.0], "{:?}", magnitudes);
+        assert!(range.0 == 0.0 && (range.1 - 5.0).abs() < 1e-5, "{:?}", range);
+
+        // The signed fields are centered on zero.
+        let pressure_field = Field::from_data(&context, dimensions, 1, &[-0.5, 0.0, 0.25]).field;
+        presenter.evaluate_source(&context, &fluid, Some(&pressure_field), DisplayMode::Pressure);
+        assert_eq!(presenter.auto_value_range(DisplayMode::Pressure), (-0.5, 0.5));
+    }
+
+    #[test]
+    fn available_display_modes() {
+        let cycle = |has_pressure: bool, has_level_set: bool| {
+            let mut display_modes = vec![DisplayMode::Density];
+            loop {
+                let display_mode = display_modes[display_modes.len() - 1].next_available(has_pressure, has_level_set);
+                if display_mode == DisplayMode::Density {
+                    return display_modes;
+                }
+                display_modes.push(display_mode);
+            }
+        };
+        use DisplayMode::*;
+        assert_eq!(cycle(true, true), vec![Density, Temperature, VelocityMagnitude, VelocityDirection, Pressure, Divergence, Vorticity, LiquidSurface, Lic]);
+        assert_eq!(cycle(true, false), vec![Density, Temperature, VelocityMagnitude, VelocityDirection, Pressure, Divergence, Vorticity, Lic]);
+        assert_eq!(cycle(false, true), vec![Density, Temperature, VelocityMagnitude, VelocityDirection, Divergence, Vorticity, LiquidSurface, Lic]);
+        assert_eq!(cycle(false, false), vec![Density, Temperature, VelocityMagnitude, VelocityDirection, Divergence, Vorticity, Lic]);
+    }
+
+    #[test]
+    fn fits() {
+        assert_eq!(Fit::Stretch.scale((1024, 768), (128, 128)), (1.0, 1.0));
+        assert_eq!(Fit::PreserveAspect.scale((1024, 768), (128, 128)), (0.75, 1.0));
+        assert_eq!(Fit::PreserveAspect.scale((512, 1024), (128, 128)), (1.0, 0.5));
+    }
+}
@@ -0,0 +1,7 @@
+#version 460
+
+out vec4 color;
+
+void main() {
+    color = vec4(0.9, 0.2, 0.2, 1.0);
+}
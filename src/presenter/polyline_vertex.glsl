@@ -0,0 +1,12 @@
+#version 460
+
+layout(location = 0) in vec2 position;
+layout(location = 0) uniform vec2 fieldResolution;
+// The size of the field in clip space, from the Presenter fit.
+layout(location = 1) uniform vec2 scale;
+
+void main() {
+    // Cell centers are on integer coordinates, so the field covers [-0.5, resolution - 0.5].
+    vec2 ndc = (position + 0.5) / fieldResolution * 2.0 - 1.0;
+    gl_Position = vec4(ndc * scale, 0.0, 1.0);
+}
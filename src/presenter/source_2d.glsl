@@ -0,0 +1,50 @@
+#version 450
+
+layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+layout(rg32f, location = 0) uniform image2D velocity;
+layout(r32f, location = 1) uniform image2D density;
+layout(r32f, location = 2) uniform image2D pressure;
+// The value to colour map in x, or the velocity for the direction.
+layout(rg32f, location = 3) uniform image2D source;
+layout(location = 4) uniform int sourceId;
+layout(r32f, location = 5) uniform image2D temperature;
+// The value alone, for the reductions of the automatic value range.
+layout(r32f, location = 6) uniform image2D value;
+
+#define DENSITY_SOURCE 0
+#define VELOCITY_MAGNITUDE_SOURCE 1
+#define PRESSURE_SOURCE 2
+#define DIVERGENCE_SOURCE 3
+#define VORTICITY_SOURCE 4
+#define VELOCITY_SOURCE 5
+#define TEMPERATURE_SOURCE 6
+
+vec2 velocityAt(ivec2 coord) {
+    return imageLoad(velocity, clamp(coord, ivec2(0), imageSize(velocity) - 1)).xy;
+}
+
+void main() {
+    ivec2 coord = ivec2(gl_GlobalInvocationID.xy);
+    ivec2 dx = ivec2(1, 0);
+    ivec2 dy = ivec2(0, 1);
+    vec2 sourceValue = vec2(0.0);
+    if (sourceId == VELOCITY_MAGNITUDE_SOURCE) {
+        sourceValue.x = length(velocityAt(coord));
+    } else if (sourceId == VELOCITY_SOURCE) {
+        sourceValue = velocityAt(coord);
+    } else if (sourceId == PRESSURE_SOURCE) {
+        sourceValue.x = imageLoad(pressure, coord).x;
+    } else if (sourceId == DIVERGENCE_SOURCE) {
+        sourceValue.x = 0.5 * (velocityAt(coord + dx).x - velocityAt(coord - dx).x + velocityAt(coord + dy).y - velocityAt(coord - dy).y);
+    } else if (sourceId == VORTICITY_SOURCE) {
+        sourceValue.x = 0.5 * (velocityAt(coord + dx).y - velocityAt(coord - dx).y - velocityAt(coord + dy).x + velocityAt(coord - dy).x);
+    } else if (sourceId == TEMPERATURE_SOURCE) {
+        sourceValue.x = imageLoad(temperature, coord).x;
+    } else {
+        sourceValue.x = imageLoad(density, coord).x;
+    }
+    imageStore(source, coord, vec4(sourceValue, 0.0, 0.0));
+    // The direction is ranged by the magnitude.
+    imageStore(value, coord, vec4(sourceId == VELOCITY_SOURCE ? length(sourceValue) : sourceValue.x));
+}
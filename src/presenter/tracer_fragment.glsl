@@ -0,0 +1,9 @@
+#version 460
+
+in float fade;
+
+out vec4 color;
+
+void main() {
+    color = vec4(mix(vec3(0.1, 0.3, 0.6), vec3(1.0, 0.8, 0.2), fade), 1.0);
+}
@@ -0,0 +1,40 @@
+#version 460
+
+layout(rgba32f, location = 0) uniform image2D tracerField;
+layout(rg32f, location = 1) uniform image2D trailField;
+layout(location = 2) uniform vec2 fieldResolution;
+layout(location = 3) uniform int head;
+// Zero draws one point per tracer, otherwise it draws the trails as line segments.
+layout(location = 4) uniform bool drawTrails;
+// The size of the field in clip space, from the Presenter fit.
+layout(location = 5) uniform vec2 scale;
+
+out float fade;
+
+void main() {
+    int width = imageSize(tracerField).x;
+    int rows = imageSize(tracerField).y;
+    int trailLength = imageSize(trailField).y / rows;
+    int index;
+    vec2 position;
+    if (drawTrails) {
+        int segments = trailLength - 1;
+        index = gl_VertexID / (segments * 2);
+        int segment = (gl_VertexID / 2) % segments;
+        int age = segment + gl_VertexID % 2;
+        int row = (head - age + trailLength) % trailLength;
+        position = imageLoad(trailField, ivec2(index % width, index / width + row * rows)).xy;
+        fade = 1.0 - float(age) / float(trailLength);
+    } else {
+        index = gl_VertexID;
+        position = imageLoad(tracerField, ivec2(index % width, index / width)).xy;
+        fade = 1.0;
+        gl_PointSize = 2.0;
+    }
+
+    bool alive = imageLoad(tracerField, ivec2(index % width, index / width)).w > 0.5;
+    // Cell centers are on integer coordinates, so the field covers [-0.5, resolution - 0.5].
+    vec2 ndc = (position + 0.5) / fieldResolution * 2.0 - 1.0;
+    // Dead tracers are moved out of the clip volume.
+    gl_Position = alive ? vec4(ndc * scale, 0.0, 1.0) : vec4(2.0, 2.0, 2.0, 1.0);
+}
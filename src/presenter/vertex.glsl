@@ -1,4 +1,11 @@
+#version 450
+
+// Two triangles covering the viewport. The fragment shader maps the pixels to the field.
+const vec2 corners[6] = vec2[](
+    vec2(-1.0, -1.0), vec2( 1.0, -1.0), vec2( 1.0,  1.0),
+    vec2(-1.0, -1.0), vec2( 1.0,  1.0), vec2(-1.0,  1.0)
+);
+
 void main() {
-    gl_Position = vec4(0.0, 0.0, 0.0, 1.0);
-    gl_PointSize = 512.0;
-}
\ No newline at end of file
+    gl_Position = vec4(corners[gl_VertexID], 0.0, 1.0);
+}
@@ -0,0 +1,673 @@
+//! Declarative scene files, so experiments can be versioned and shared without touching `main.rs`.
+//! See `scenes/` for examples. Positions and sizes are in cells, `domain` only scales the exported data.
+
+use crate::capture::FrameCapture;
+use crate::context::Context;
+use crate::field::Field;
+use crate::fluid::Fluid;
+use crate::initializer::{Initializer, Preset, Image, Channel, Uniform};
+use crate::interactor::Interactor;
+use crate::presenter::Presenter;
+use crate::simulator::{Simulator, Force, TransferScheme, ParticleBackend, Emitter, EmitterKind, Region, sponge_mask};
+use crate::streamline::VelocitySampler;
+use crate::vtk::{ImageData, VtkSeries};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use toml::Value;
+use toml::value::Table;
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(PathBuf, std::io::Error),
+    Parse(toml::de::Error),
+    Invalid(String),
+    Context(String)
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::Io(path, error) => write!(formatter, "{}: {}", path.display(), error),
+            SceneError::Parse(error)    => write!(formatter, "{}", error),
+            SceneError::Invalid(error)  => write!(formatter, "{}", error),
+            SceneError::Context(error)  => write!(formatter, "{}", error)
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Boundary {
+    /// Closed walls on the sides.
+    Walls,
+    /// A sponge layer of `width` cells that absorbs the outgoing flow. See `simulator::sponge_mask`.
+    Open { width: f32, strength: f32 }
+}
+
+/// The region of an obstacle or an emitter.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Shape {
+    Circle { center: (f32, f32), radius: f32 },
+    Rectangle { min: (f32, f32), max: (f32, f32) },
+    /// The cells where `channel` of the image is over `threshold`.
+    Image { path: PathBuf, channel: Channel, threshold: f32 }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ForceDescription {
+    Constant { acceleration: (f32, f32) },
+    /// `amplitude * sin(2 pi frequency t)`.
+    Oscillating { amplitude: (f32, f32), frequency: f32 }
+}
+
+impl ForceDescription {
+    pub fn force(&self) -> Force {
+        match *self {
+            ForceDescription::Constant { acceleration } => Force::Constant(acceleration),
+            ForceDescription::Oscillating { amplitude, frequency } => Force::TimeVarying(Box::new(move |time| {
+                let phase = (2.0 * std::f32::consts::PI * frequency * time).sin();
+                (amplitude.0 * phase, amplitude.1 * phase)
+            }))
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EmitterDescription {
+    pub shape: Shape,
+    pub kind: EmitterKind,
+    /// The strength is `1 + amplitude * sin(2 pi frequency t)` when it's set, as (frequency, amplitude).
+    pub pulse: Option<(f32, f32)>,
+    pub jitter: f32
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum InitialCondition {
+    /// The checkerboard of `Initializer::initialize`.
+    Default,
+    Preset(Preset),
+    /// GLSL expressions, see `Initializer::density_from_expression`.
+    Expressions { density: Option<String>, velocity: Option<String>, uniforms: Vec<(String, Uniform)> },
+    /// The density from `channel` of an image, and optionally the velocity from the `Image::velocity_channels` of another.
+    Image { density: PathBuf, channel: Channel, velocity: Option<PathBuf>, velocity_scale: f32 }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Particles {
+    pub scheme: TransferScheme,
+    pub backend: ParticleBackend,
+    pub particles_per_axis: usize
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SolverSettings {
+    /// A fixed time step. The wall clock time is used when it isn't set.
+    pub time_step: Option<f32>,
+    pub iterations: usize,
+    /// Runs the particle solver instead of the grid one when set.
+    pub particles: Option<Particles>
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct OutputSchedule {
+    pub directory: PathBuf,
+    /// Captures every `frame_interval` presented frames.
+    pub frame_interval: Option<usize>,
+    /// In simulation seconds, like the other intervals.
+    pub snapshot_interval: Option<f32>,
+    pub vtk_interval: Option<f32>,
+    /// Stops the simulation at this time.
+    pub end_time: Option<f32>
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scene {
+    pub dimensions: (usize, usize),
+    /// The physical size of the grid, only used by the exports.
+    pub domain: (f32, f32),
+    pub diffusion: f32,
+    pub viscosity: f32,
+    pub density_dissipation: f32,
+    pub velocity_dissipation: f32,
+    pub temperature_dissipation: f32,
+    pub boundary: Boundary,
+    pub obstacles: Vec<Shape>,
+    pub emitters: Vec<EmitterDescription>,
+    pub forces: Vec<ForceDescription>,
+    pub initial: InitialCondition,
+    pub solver: SolverSettings,
+    pub output: OutputSchedule
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self {
+            dimensions: (256, 256),
+            domain: (1.0, 1.0),
+            diffusion: 1.0,
+            viscosity: 0.0000001,
+            density_dissipation: 0.0,
+            velocity_dissipation: 0.0,
+            temperature_dissipation: 0.0,
+            boundary: Boundary::Walls,
+            obstacles: Vec::new(),
+            emitters: Vec::new(),
+            forces: Vec::new(),
+            initial: InitialCondition::Default,
+            solver: SolverSettings { time_step: None, iterations: 30, particles: None },
+            output: OutputSchedule { directory: PathBuf::from("captures"), frame_interval: None, snapshot_interval: None, vtk_interval: None, end_time: None }
+        }
+    }
+}
+
+/// Integers are promoted to floats.
+fn as_f32(value: &Value) -> Option<f32> {
+    match *value {
+        Value::Integer(value) => Some(value as f32),
+        Value::Float(value)   => Some(value as f32),
+        _                     => None
+    }
+}
+
+fn as_usize(value: &Value) -> Option<usize> {
+    match *value {
+        Value::Integer(value) if value >= 0 => Some(value as usize),
+        _                                   => None
+    }
+}
+
+/// A two numbers array, like `[1.0, 2]`.
+fn as_vec2(value: &Value) -> Option<(f32, f32)> {
+    match value.as_array()?.as_slice() {
+        [x, y] => Some((as_f32(x)?, as_f32(y)?)),
+        _      => None
+    }
+}
+
+/// A table of the scene file, checking the value types and reporting the keys it doesn't know.
+struct Section<'a> {
+    name: String,
+    table: Option<&'a Table>
+}
+
+impl<'a> Section<'a> {
+    fn new(name: impl Into<String>, table: Option<&'a Table>) -> Self {
+        Self { name: name.into(), table }
+    }
+
+    fn child(root: &'a Table, name: &str) -> Result<Self, SceneError> {
+        match root.get(name) {
+            None                     => Ok(Self::new(name, None)),
+            Some(Value::Table(table)) => Ok(Self::new(name, Some(table))),
+            Some(value)              => Err(SceneError::Invalid(format!("{}: expected a table, found {}", name, value.type_str())))
+        }
+    }
+
+    fn check_keys(&self, keys: &[&str]) -> Result<(), SceneError> {
+        for key in self.table.iter().flat_map(|table| table.keys()) {
+            if !keys.contains(&key.as_str()) {
+                return Err(SceneError::Invalid(format!("{}: unknown key {}, expected one of {}", self.name, key, keys.join(", "))));
+            }
+        }
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Option<&'a Value> {
+        self.table.and_then(|table| table.get(key))
+    }
+
+    fn convert<T>(&self, key: &str, expected: &str, convert: impl Fn(&'a Value) -> Option<T>) -> Result<Option<T>, SceneError> {
+        match self.get(key) {
+            None        => Ok(None),
+            Some(value) => convert(value).map(Some).ok_or_else(|| SceneError::Invalid(format!("{}.{}: expected {}, found {}", self.name, key, expected, value.type_str())))
+        }
+    }
+
+    fn required<T>(&self, key: &str, value: Option<T>) -> Result<T, SceneError> {
+        value.ok_or_else(|| SceneError::Invalid(format!("{}: missing {}", self.name, key)))
+    }
+
+    fn f32(&self, key: &str) -> Result<Option<f32>, SceneError> {
+        self.convert(key, "a number", as_f32)
+    }
+
+    fn usize(&self, key: &str) -> Result<Option<usize>, SceneError> {
+        self.convert(key, "a positive integer", as_usize)
+    }
+
+    fn vec2(&self, key: &str) -> Result<Option<(f32, f32)>, SceneError> {
+        self.convert(key, "a pair of numbers", as_vec2)
+    }
+
+    fn str(&self, key: &str) -> Result<Option<&'a str>, SceneError> {
+        self.convert(key, "a string", Value::as_str)
+    }
+
+    fn tables(&self, key: &str) -> Result<Vec<Section<'a>>, SceneError> {
+        let tables = self.convert(key, "an array of tables", |value| value.as_array()?.iter().map(Value::as_table).collect::<Option<Vec<_>>>())?;
+        Ok(tables.unwrap_or_default().into_iter().enumerate().map(|(index, table)| Section::new(format!("{}[{}]", key, index), Some(table))).collect())
+    }
+}
+
+fn channel(name: &str) -> Result<Channel, SceneError> {
+    match name {
+        "red"       => Ok(Channel::Red),
+        "green"     => Ok(Channel::Green),
+        "blue"      => Ok(Channel::Blue),
+        "alpha"     => Ok(Channel::Alpha),
+        "luminance" => Ok(Channel::Luminance),
+        _           => Err(SceneError::Invalid(format!("Unknown channel {}, expected red, green, blue, alpha or luminance", name)))
+    }
+}
+
+/// Parses the shape keys of `section`, which may also have the `extra_keys` of what it's the shape of.
+fn parse_shape(section: &Section, extra_keys: &[&str]) -> Result<Shape, SceneError> {
+    let keys = |shape_keys: &[&'static str]| -> Vec<&str> { shape_keys.iter().chain(extra_keys).copied().collect() };
+    match section.required("shape", section.str("shape")?)? {
+        "circle" => {
+            section.check_keys(&keys(&["shape", "center", "radius"]))?;
+            Ok(Shape::Circle { center: section.required("center", section.vec2("center")?)?, radius: section.required("radius", section.f32("radius")?)? })
+        },
+        "rectangle" => {
+            section.check_keys(&keys(&["shape", "min", "max"]))?;
+            Ok(Shape::Rectangle { min: section.required("min", section.vec2("min")?)?, max: section.required("max", section.vec2("max")?)? })
+        },
+        "image" => {
+            section.check_keys(&keys(&["shape", "path", "channel", "threshold"]))?;
+            let path = PathBuf::from(section.required("path", section.str("path")?)?);
+            let channel = channel(section.str("channel")?.unwrap_or("luminance"))?;
+            Ok(Shape::Image { path, channel, threshold: section.f32("threshold")?.unwrap_or(0.5) })
+        },
+        shape => Err(SceneError::Invalid(format!("{}: unknown shape {}, expected circle, rectangle or image", section.name, shape)))
+    }
+}
+
+fn parse_emitter(section: &Section) -> Result<EmitterDescription, SceneError> {
+    let shape = parse_shape(section, &["kind", "dye_rate", "temperature_rate", "velocity", "velocity_rate", "rate", "jitter", "pulse_frequency", "pulse_amplitude"])?;
+    let kind = match section.required("kind", section.str("kind")?)? {
+        "source" => EmitterKind::Source {
+            dye_rate: section.f32("dye_rate")?.unwrap_or(0.0),
+            temperature_rate: section.f32("temperature_rate")?.unwrap_or(0.0),
+            velocity: section.vec2("velocity")?.unwrap_or((0.0, 0.0)),
+            velocity_rate: section.f32("velocity_rate")?.unwrap_or(0.0)
+        },
+        "sink"    => EmitterKind::Sink { rate: section.required("rate", section.f32("rate")?)? },
+        "outflow" => EmitterKind::Outflow { velocity: section.vec2("velocity")?.unwrap_or((0.0, 0.0)) },
+        kind      => return Err(SceneError::Invalid(format!("{}: unknown kind {}, expected source, sink or outflow", section.name, kind)))
+    };
+    let pulse = match section.f32("pulse_frequency")? {
+        Some(frequency) => Some((frequency, section.f32("pulse_amplitude")?.unwrap_or(1.0))),
+        None            => None
+    };
+    Ok(EmitterDescription { shape, kind, pulse, jitter: section.f32("jitter")?.unwrap_or(0.0) })
+}
+
+fn parse_force(section: &Section) -> Result<ForceDescription, SceneError> {
+    match section.required("type", section.str("type")?)? {
+        "constant" => {
+            section.check_keys(&["type", "acceleration"])?;
+            Ok(ForceDescription::Constant { acceleration: section.required("acceleration", section.vec2("acceleration")?)? })
+        },
+        "oscillating" => {
+            section.check_keys(&["type", "amplitude", "frequency"])?;
+            Ok(ForceDescription::Oscillating { amplitude: section.required("amplitude", section.vec2("amplitude")?)?, frequency: section.required("frequency", section.f32("frequency")?)? })
+        },
+        kind => Err(SceneError::Invalid(format!("{}: unknown type {}, expected constant or oscillating", section.name, kind)))
+    }
+}
+
+fn parse_initial(section: &Section) -> Result<InitialCondition, SceneError> {
+    if let Some(name) = section.str("preset")? {
+        section.check_keys(&["preset", "parameters"])?;
+        let parameters = Section::child(section.table.expect("The preset key is in the table."), "parameters")?;
+        let mut values = HashMap::new();
+        for key in parameters.table.iter().flat_map(|table| table.keys()) {
+            values.insert(key.clone(), parameters.f32(key)?.expect("The key is in the table."));
+        }
+        return Preset::parse(name, &values).map(InitialCondition::Preset).map_err(|error| SceneError::Invalid(format!("{}: {}", section.name, error)));
+    }
+    if let Some(density) = section.str("image")? {
+        section.check_keys(&["image", "channel", "velocity_image", "velocity_scale"])?;
+        return Ok(InitialCondition::Image {
+            density: PathBuf::from(density),
+            channel: channel(section.str("channel")?.unwrap_or("luminance"))?,
+            velocity: section.str("velocity_image")?.map(PathBuf::from),
+            velocity_scale: section.f32("velocity_scale")?.unwrap_or(100.0)
+        });
+    }
+    if section.get("density").is_some() || section.get("velocity").is_some() {
+        section.check_keys(&["density", "velocity", "uniforms"])?;
+        let mut uniforms = Vec::new();
+        let uniform_section = Section::child(section.table.expect("The expressions are in the table."), "uniforms")?;
+        for (name, value) in uniform_section.table.iter().flat_map(|table| table.iter()) {
+            let uniform = match (as_f32(value), as_vec2(value)) {
+                (Some(value), _) => Uniform::Float(value),
+                (_, Some((x, y))) => Uniform::Vec2(x, y),
+                _ => return Err(SceneError::Invalid(format!("uniforms.{}: expected a number or a pair of numbers, found {}", name, value.type_str())))
+            };
+            uniforms.push((name.clone(), uniform));
+        }
+        return Ok(InitialCondition::Expressions { density: section.str("density")?.map(String::from), velocity: section.str("velocity")?.map(String::from), uniforms });
+    }
+    section.check_keys(&[])?;
+    Ok(InitialCondition::Default)
+}
+
+fn parse_solver(section: &Section) -> Result<SolverSettings, SceneError> {
+    section.check_keys(&["time_step", "iterations", "particles", "flip_ratio", "backend", "particles_per_axis"])?;
+    let scheme = match section.str("particles")?.unwrap_or("none") {
+        "none"     => None,
+        "apic"     => Some(TransferScheme::Apic),
+        "pic_flip" => Some(TransferScheme::PicFlip { flip_ratio: section.f32("flip_ratio")?.unwrap_or(0.95) }),
+        scheme     => return Err(SceneError::Invalid(format!("{}: unknown particles {}, expected none, apic or pic_flip", section.name, scheme)))
+    };
+    let backend = match section.str("backend")?.unwrap_or("gpu") {
+        "gpu"   => ParticleBackend::Gpu,
+        "cpu"   => ParticleBackend::Cpu,
+        backend => return Err(SceneError::Invalid(format!("{}: unknown backend {}, expected gpu or cpu", section.name, backend)))
+    };
+    let particles_per_axis = section.usize("particles_per_axis")?.unwrap_or(2);
+    let particles = scheme.map(|scheme| Particles { scheme, backend, particles_per_axis });
+    Ok(SolverSettings { time_step: section.f32("time_step")?, iterations: section.usize("iterations")?.unwrap_or(30), particles })
+}
+
+fn load_image(path: &Path) -> Result<Image, SceneError> {
+    Image::load(path).map_err(|error| SceneError::Io(path.to_path_buf(), error))
+}
+
+/// 1 on the cells `inside` the shape, 0 elsewhere.
+fn mask(dimensions: (usize, usize), inside: impl Fn(f32, f32) -> bool) -> Vec<f32> {
+    Field::data_from_fn(dimensions, 1, |x, y| [if inside(x as f32, y as f32) { 1.0 } else { 0.0 }])
+}
+
+/// Whether a write every `interval` is due at `time`, moving `next` past it.
+fn is_due(next: &mut f32, interval: Option<f32>, time: f32) -> bool {
+    match interval {
+        Some(interval) if time >= *next => {
+            while *next <= time {
+                *next += interval.max(std::f32::EPSILON);
+            }
+            true
+        },
+        _ => false
+    }
+}
+
+impl Scene {
+    pub fn parse(source: &str) -> Result<Self, SceneError> {
+        let root: Table = toml::from_str(source).map_err(SceneError::Parse)?;
+        Section::new("scene", Some(&root)).check_keys(&["grid", "fluid", "boundary", "obstacles", "emitters", "forces", "initial", "solver", "output"])?;
+        let defaults = Self::default();
+
+        let grid = Section::child(&root, "grid")?;
+        grid.check_keys(&["size", "domain"])?;
+        let dimensions = match grid.get("size") {
+            Some(size) => match size.as_array().map(Vec::as_slice) {
+                Some([x, y]) => match (as_usize(x), as_usize(y)) {
+                    (Some(x), Some(y)) if x > 2 && y > 2 => (x, y),
+                    _ => return Err(SceneError::Invalid("grid.size: expected two integers bigger than 2".to_string()))
+                },
+                _ => return Err(SceneError::Invalid("grid.size: expected two integers bigger than 2".to_string()))
+            },
+            None => defaults.dimensions
+        };
+        let domain = grid.vec2("domain")?.unwrap_or(defaults.domain);
+
+        let fluid = Section::child(&root, "fluid")?;
+        fluid.check_keys(&["diffusion", "viscosity", "density_dissipation", "velocity_dissipation", "temperature_dissipation"])?;
+
+        let boundary = Section::child(&root, "boundary")?;
+        let boundary = match boundary.str("type")?.unwrap_or("walls") {
+            "walls" => {
+                boundary.check_keys(&["type"])?;
+                Boundary::Walls
+            },
+            "open" => {
+                boundary.check_keys(&["type", "width", "strength"])?;
+                Boundary::Open { width: boundary.f32("width")?.unwrap_or(16.0), strength: boundary.f32("strength")?.unwrap_or(4.0) }
+            },
+            kind => return Err(SceneError::Invalid(format!("boundary: unknown type {}, expected walls or open", kind)))
+        };
+
+        let root_section = Section::new("scene", Some(&root));
+        let obstacles = root_section.tables("obstacles")?.iter().map(|section| parse_shape(section, &[])).collect::<Result<_, _>>()?;
+        let emitters = root_section.tables("emitters")?.iter().map(parse_emitter).collect::<Result<_, _>>()?;
+        let forces = root_section.tables("forces")?.iter().map(parse_force).collect::<Result<_, _>>()?;
+        let initial = parse_initial(&Section::child(&root, "initial")?)?;
+        let solver = parse_solver(&Section::child(&root, "solver")?)?;
+
+        let output = Section::child(&root, "output")?;
+        output.check_keys(&["directory", "frame_interval", "snapshot_interval", "vtk_interval", "end_time"])?;
+        let output = OutputSchedule {
+            directory: output.str("directory")?.map(PathBuf::from).unwrap_or(defaults.output.directory),
+            frame_interval: output.usize("frame_interval")?,
+            snapshot_interval: output.f32("snapshot_interval")?,
+            vtk_interval: output.f32("vtk_interval")?,
+            end_time: output.f32("end_time")?
+        };
+
+        Ok(Self {
+            dimensions,
+            domain,
+            diffusion: fluid.f32("diffusion")?.unwrap_or(defaults.diffusion),
+            viscosity: fluid.f32("viscosity")?.unwrap_or(defaults.viscosity),
+            density_dissipation: fluid.f32("density_dissipation")?.unwrap_or(defaults.density_dissipation),
+            velocity_dissipation: fluid.f32("velocity_dissipation")?.unwrap_or(defaults.velocity_dissipation),
+            temperature_dissipation: fluid.f32("temperature_dissipation")?.unwrap_or(defaults.temperature_dissipation),
+            boundary,
+            obstacles,
+            emitters,
+            forces,
+            initial,
+            solver,
+            output
+        })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SceneError> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path).map_err(|error| SceneError::Io(path.to_path_buf(), error))?;
+        Self::parse(&source)
+    }
+
+    /// The size of a cell in the `domain` units.
+    pub fn spacing(&self) -> (f32, f32) {
+        (self.domain.0 / self.dimensions.0 as f32, self.domain.1 / self.dimensions.1 as f32)
+    }
+
+    /// Creates the context, the fluid, the simulator and their helpers, and sets the initial conditions.
+    pub fn build(&self, headless: bool) -> Result<Simulation, SceneError> {
+        let context = if headless { Context::headless(self.dimensions).map_err(SceneError::Context)? } else { Context::new(self.dimensions) };
+        let mut initializer = Initializer::new(&context);
+        let presenter = Presenter::new(&context);
+        let interactor = Interactor::new(&context);
+        let mut simulator = Simulator::new(&context, self.dimensions);
+        simulator.iterations = self.solver.iterations;
+        for force in &self.forces {
+            simulator.add_force(force.force());
+        }
+        for emitter in &self.emitters {
+            simulator.add_emitter(self.emitter(&context, emitter)?);
+        }
+
+        let mut fluid = Fluid::new(&context, self.dimensions, self.diffusion, self.viscosity);
+        fluid.density_dissipation = self.density_dissipation;
+        fluid.velocity_dissipation = self.velocity_dissipation;
+        fluid.temperature_dissipation = self.temperature_dissipation;
+        if let Boundary::Open { width, strength } = self.boundary {
+            fluid.damping_mask = Some(sponge_mask(&context, self.dimensions, width, strength));
+        }
+        self.initialize(&context, &mut initializer, &mut simulator, &mut fluid)?;
+
+        let output = Output::new(&self.output, self.spacing());
+        Ok(Simulation { context, fluid, simulator, initializer, interactor, presenter, output })
+    }
+
+    /// 1 on the cells in `shape`, 0 elsewhere.
+    pub fn shape_mask(&self, shape: &Shape) -> Result<Vec<f32>, SceneError> {
+        Ok(match *shape {
+            Shape::Circle { center, radius } => mask(self.dimensions, |x, y| (x - center.0).powi(2) + (y - center.1).powi(2) <= radius * radius),
+            Shape::Rectangle { min, max } => mask(self.dimensions, |x, y| x >= min.0 && x <= max.0 && y >= min.1 && y <= max.1),
+            Shape::Image { ref path, channel, threshold } => load_image(path)?.resample(self.dimensions, channel).iter().map(|value| if *value > threshold { 1.0 } else { 0.0 }).collect()
+        })
+    }
+
+    pub fn emitter(&self, context: &Context, description: &EmitterDescription) -> Result<Emitter, SceneError> {
+        let region = match description.shape {
+            Shape::Circle { center, radius } => Region::Circle { center, radius },
+            Shape::Rectangle { min, max } => Region::Rectangle { min, max },
+            Shape::Image { .. } => {
+                let mask = self.shape_mask(&description.shape)?;
+                Region::mask_from_fn(context, self.dimensions, |x, y| mask[y * self.dimensions.0 + x])
+            }
+        };
+        let mut emitter = Emitter::new(region, description.kind);
+        if let Some((frequency, amplitude)) = description.pulse {
+            emitter.strength = Some(Box::new(move |time| 1.0 + amplitude * (2.0 * std::f32::consts::PI * frequency * time).sin()));
+        }
+        emitter.jitter = description.jitter;
+        Ok(emitter)
+    }
+
+    /// Sets the initial conditions, the obstacles and the particles. It's also how the scene is reset.
+    pub fn initialize(&self, context: &Context, initializer: &mut Initializer, simulator: &mut Simulator, fluid: &mut Fluid) -> Result<(), SceneError> {
+        match &self.initial {
+            InitialCondition::Default => initializer.initialize(fluid),
+            InitialCondition::Preset(preset) => initializer.apply_preset(context, fluid, preset),
+            InitialCondition::Expressions { density, velocity, uniforms } => {
+                // Whatever the expressions don't set starts at rest and without dye.
+                initializer.apply_preset(context, fluid, &Preset::Uniform { velocity_x: 0.0, velocity_y: 0.0 });
+                initializer.clear_scalar_field(&fluid.density_field);
+                initializer.clear_scalar_field(&fluid.previous_density_field);
+                let uniforms: Vec<(&str, Uniform)> = uniforms.iter().map(|(name, uniform)| (name.as_str(), *uniform)).collect();
+                if let Some(density) = density {
+                    initializer.density_from_expression(context, fluid, density, &uniforms).map_err(|error| SceneError::Invalid(format!("initial.density: {}", error)))?;
+                }
+                if let Some(velocity) = velocity {
+                    initializer.velocity_from_expression(context, fluid, velocity, &uniforms).map_err(|error| SceneError::Invalid(format!("initial.velocity: {}", error)))?;
+                }
+            },
+            InitialCondition::Image { density, channel, velocity, velocity_scale } => {
+                initializer.apply_preset(context, fluid, &Preset::Uniform { velocity_x: 0.0, velocity_y: 0.0 });
+                initializer.density_from_image(context, fluid, &load_image(density)?, *channel);
+                if let Some(velocity) = velocity {
+                    initializer.velocity_from_image(context, fluid, &load_image(velocity)?, *velocity_scale);
+                }
+            }
+        }
+
+        let mut obstacles = vec![0.0; self.dimensions.0 * self.dimensions.1];
+        for shape in &self.obstacles {
+            for (obstacle, value) in obstacles.iter_mut().zip(self.shape_mask(shape)?) {
+                *obstacle = f32::max(*obstacle, value);
+            }
+        }
+        if !self.obstacles.is_empty() {
+            fluid.obstacle_field = Field::from_data(&context, self.dimensions, 1, &obstacles).field;
+        }
+
+        if let Some(particles) = &self.solver.particles {
+            simulator.particle_solver.scheme = particles.scheme;
+            let velocity = VelocitySampler::from_field(&fluid.velocity_field);
+            let inside = |x: f32, y: f32| obstacles[y as usize * self.dimensions.0 + x as usize] == 0.0;
+            simulator.particle_solver.seed(context, particles.backend, self.dimensions, particles.particles_per_axis, inside, |x, y| velocity.sample((x, y)));
+        }
+        Ok(())
+    }
+}
+
+/// Everything `Scene::build` creates.
+pub struct Simulation {
+    pub context: Context,
+    pub fluid: Fluid,
+    pub simulator: Simulator,
+    pub initializer: Initializer,
+    pub interactor: Interactor,
+    pub presenter: Presenter,
+    pub output: Output
+}
+
+/// Writes the scheduled frames, snapshots and VTK files.
+pub struct Output {
+    pub schedule: OutputSchedule,
+    pub capture: FrameCapture,
+    vtk_series: VtkSeries,
+    spacing: (f32, f32),
+    next_snapshot: f32,
+    next_vtk: f32,
+    snapshots: usize
+}
+
+impl Output {
+    pub fn new(schedule: &OutputSchedule, spacing: (f32, f32)) -> Self {
+        let mut capture = FrameCapture::new(schedule.directory.clone(), "frame");
+        capture.interval = schedule.frame_interval;
+        let vtk_series = VtkSeries::new(schedule.directory.clone(), "fluid");
+        Self { schedule: schedule.clone(), capture, vtk_series, spacing, next_snapshot: 0.0, next_vtk: 0.0, snapshots: 0 }
+    }
+
+    /// Writes the snapshots and the VTK files that are due at the simulator time.
+    pub fn write(&mut self, fluid: &Fluid, simulator: &Simulator) -> std::io::Result<()> {
+        let time = simulator.time;
+        if is_due(&mut self.next_snapshot, self.schedule.snapshot_interval, time) {
+            std::fs::create_dir_all(&self.schedule.directory)?;
+            crate::snapshot::save(fluid, self.schedule.directory.join(format!("snapshot_{:06}.snapshot", self.snapshots)))?;
+            self.snapshots += 1;
+        }
+        if is_due(&mut self.next_vtk, self.schedule.vtk_interval, time) {
+            let spacing = (self.spacing.0 as f64, self.spacing.1 as f64);
+            // Before the first step there's no pressure to export.
+            let image = ImageData::from_fluid(fluid, simulator.pressure_field(fluid), spacing, (0.0, 0.0));
+            self.vtk_series.write_frame(time, &image)?;
+        }
+        Ok(())
+    }
+
+    pub fn is_finished(&self, time: f32) -> bool {
+        matches!(self.schedule.end_time, Some(end_time) if time >= end_time)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn example() {
+        let scene = Scene::parse(include_str!("../../scenes/vortex_street.toml")).unwrap();
+        assert_eq!(scene.dimensions, (512, 128));
+        assert_eq!(scene.spacing(), (4.0 / 512.0, 1.0 / 128.0));
+        assert_eq!(scene.boundary, Boundary::Open { width: 16.0, strength: 4.0 });
+        assert_eq!(scene.obstacles, vec![Shape::Circle { center: (96.0, 64.0), radius: 12.0 }]);
+        assert_eq!(scene.emitters[0], EmitterDescription {
+            shape: Shape::Rectangle { min: (1.0, 40.0), max: (4.0, 88.0) },
+            kind: EmitterKind::Source { dye_rate: 2.0, temperature_rate: 0.0, velocity: (60.0, 0.0), velocity_rate: 10.0 },
+            pulse: Some((0.5, 0.25)),
+            jitter: 0.0
+        });
+        assert_eq!(scene.emitters[1].kind, EmitterKind::Outflow { velocity: (60.0, 0.0) });
+        assert_eq!(scene.initial, InitialCondition::Preset(Preset::Uniform { velocity_x: 60.0, velocity_y: 0.0 }));
+        assert_eq!(scene.solver.time_step, Some(1.0 / 60.0));
+        assert_eq!(scene.output.vtk_interval, Some(0.5));
+    }
+
+    #[test]
+    fn defaults_and_errors() {
+        assert_eq!(Scene::parse("").unwrap(), Scene::default());
+        let scene = Scene::parse("[initial]\ndensity = \"exp(-dot(p - c, p - c) / r)\"\n[initial.uniforms]\nc = [128, 128]\nr = 64").unwrap();
+        assert_eq!(scene.initial, InitialCondition::Expressions {
+            density: Some("exp(-dot(p - c, p - c) / r)".to_string()),
+            velocity: None,
+            uniforms: vec![("c".to_string(), Uniform::Vec2(128.0, 128.0)), ("r".to_string(), Uniform::Float(64.0))]
+        });
+        let error = |source: &str| Scene::parse(source).unwrap_err().to_string();
+        assert_eq!(error("[grid]\nsize = [256, \"a\"]"), "grid.size: expected two integers bigger than 2");
+        assert_eq!(error("[fluid]\nviscosity = \"high\""), "fluid.viscosity: expected a number, found string");
+        assert!(error("[fluid]\nviscocity = 1.0").starts_with("fluid: unknown key viscocity"));
+        assert!(error("[[obstacles]]\nshape = \"circle\"\nradius = 4").starts_with("obstacles[0]: missing center"));
+        assert!(error("[[emitters]]\nshape = \"circle\"\ncenter = [0, 0]\nradius = 4\nkind = \"sink\"").starts_with("emitters[0]: missing rate"));
+        assert!(error("[initial]\npreset = \"lamb_oseen\"\n[initial.parameters]\nspeed = 1").starts_with("initial: Unknown parameter speed"));
+    }
+}
@@ -0,0 +1,333 @@
+//! CPU version of the grid step of `Simulator::simulate`, used when the GPU backend isn't available.
+//! Every function mirrors a shader, with the same boundaries, so both backends converge to the same flows.
+//! The fields are interleaved and row by row, like `gpu::Texture2D::data`. There's no liquid mode, and the viscosity
+//! is a fixed per cell field, like a Newtonian `Fluid::viscosity_field`.
+
+use crate::field::Field;
+use crate::simulator::{Force, Emitter, EmitterKind, Region};
+use crate::simulator::particle_solver::jitter;
+
+/// The fields of a `Fluid`, on the CPU.
+pub struct CpuFluid {
+    pub dimensions: (usize, usize),
+    pub velocity: Vec<f32>,
+    pub previous_velocity: Vec<f32>,
+    pub density: Vec<f32>,
+    pub previous_density: Vec<f32>,
+    pub temperature: Vec<f32>,
+    pub previous_temperature: Vec<f32>,
+    /// Obstacles where it's over 0.5.
+    pub obstacles: Vec<f32>,
+    /// The per cell viscosity, scaled by the inner volume like `Fluid::viscosity_field`. There's no diffusion without it.
+    pub viscosity: Option<Vec<f32>>,
+    pub damping_mask: Option<Vec<f32>>,
+    pub density_dissipation: f32,
+    pub velocity_dissipation: f32,
+    pub temperature_dissipation: f32
+}
+
+impl CpuFluid {
+    /// A fluid at rest, without dye, temperature, obstacles and viscosity.
+    pub fn new(dimensions: (usize, usize)) -> Self {
+        let cells = dimensions.0 * dimensions.1;
+        Self {
+            dimensions,
+            velocity: vec![0.0; cells * 2],
+            previous_velocity: vec![0.0; cells * 2],
+            density: vec![0.0; cells],
+            previous_density: vec![0.0; cells],
+            temperature: vec![0.0; cells],
+            previous_temperature: vec![0.0; cells],
+            obstacles: vec![0.0; cells],
+            viscosity: None,
+            damping_mask: None,
+            density_dissipation: 0.0,
+            velocity_dissipation: 0.0,
+            temperature_dissipation: 0.0
+        }
+    }
+
+    pub fn set_viscosity_field(&mut self, viscosity: impl Fn(usize, usize) -> f32) {
+        self.viscosity = Some(Field::data_from_fn(self.dimensions, 1, |x, y| [viscosity(x, y)]));
+    }
+
+    pub fn inner_volume(&self) -> f32 {
+        ((self.dimensions.0 - 2) * (self.dimensions.1 - 2)) as f32
+    }
+}
+
+fn is_inside(coordinate: (isize, isize), dimensions: (usize, usize)) -> bool {
+    coordinate.0 >= 0 && coordinate.1 >= 0 && coordinate.0 < dimensions.0 as isize && coordinate.1 < dimensions.1 as isize
+}
+
+// Like imageLoad, which is zero outside of the image.
+fn load(field: &[f32], dimensions: (usize, usize), components: usize, coordinate: (isize, isize), component: usize) -> f32 {
+    if is_inside(coordinate, dimensions) {
+        field[(coordinate.1 as usize * dimensions.0 + coordinate.0 as usize) * components + component]
+    } else {
+        0.0
+    }
+}
+
+fn repeat_load(field: &[f32], dimensions: (usize, usize), components: usize, coordinate: (isize, isize), component: usize) -> f32 {
+    let x = coordinate.0.rem_euclid(dimensions.0 as isize);
+    let y = coordinate.1.rem_euclid(dimensions.1 as isize);
+    load(field, dimensions, components, (x, y), component)
+}
+
+// GLSL's mix.
+fn mix(a: f32, b: f32, t: f32) -> f32 {
+    a * (1.0 - t) + b * t
+}
+
+/// Bilinearly samples `component` of `field` at `position`, wrapping around the edges like the advection shaders.
+fn bilinear_load(field: &[f32], dimensions: (usize, usize), components: usize, position: (f32, f32), component: usize) -> f32 {
+    let interpolation = (position.0 - position.0.floor(), position.1 - position.1.floor());
+    let left_bottom = (position.0.floor() as isize, position.1.floor() as isize);
+    let right_top = (position.0.ceil() as isize, position.1.ceil() as isize);
+    let load = |coordinate| repeat_load(field, dimensions, components, coordinate, component);
+    let bottom = mix(load(left_bottom), load((right_top.0, left_bottom.1)), interpolation.0);
+    let top = mix(load((left_bottom.0, right_top.1)), load(right_top), interpolation.0);
+    mix(bottom, top, interpolation.1)
+}
+
+/// Semi-Lagrangian advection of the `components` of `previous_field` by `velocity`, into `field`.
+pub fn advect(field: &mut [f32], previous_field: &[f32], velocity: &[f32], dimensions: (usize, usize), components: usize, delta_time: f32) {
+    for y in 0 .. dimensions.1 {
+        for x in 0 .. dimensions.0 {
+            let index = y * dimensions.0 + x;
+            let position = (x as f32 - velocity[index * 2] * delta_time, y as f32 - velocity[index * 2 + 1] * delta_time);
+            for component in 0 .. components {
+                field[index * components + component] = bilinear_load(previous_field, dimensions, components, position, component);
+            }
+        }
+    }
+}
+
+/// Jacobi iterations of the implicit diffusion of `previous_velocity` with the per cell `viscosity`, from `velocity`.
+pub fn diffuse_variable(viscosity: &[f32], velocity: &mut Vec<f32>, previous_velocity: &[f32], dimensions: (usize, usize), delta_time: f32, iterations: usize) {
+    const NEIGHBORS_OFFSETS: [(isize, isize); 4] = [(-1, 0), (1, 0), (0, 1), (0, -1)];
+    let scale = delta_time * ((dimensions.0 - 2) * (dimensions.1 - 2)) as f32;
+    let mut temporary = vec![0.0; velocity.len()];
+    for _ in 0 .. iterations {
+        for y in 0 .. dimensions.1 {
+            for x in 0 .. dimensions.0 {
+                let index = y * dimensions.0 + x;
+                let mut sum = (previous_velocity[index * 2], previous_velocity[index * 2 + 1]);
+                let mut diagonal = 1.0;
+                for offset in &NEIGHBORS_OFFSETS {
+                    let neighbor = (x as isize + offset.0, y as isize + offset.1);
+                    if !is_inside(neighbor, dimensions) {
+                        continue;
+                    }
+                    let neighbor_index = neighbor.1 as usize * dimensions.0 + neighbor.0 as usize;
+                    let face_viscosity = 2.0 * viscosity[index] * viscosity[neighbor_index] / (viscosity[index] + viscosity[neighbor_index]).max(1e-12);
+                    let a = face_viscosity * scale;
+                    sum = (sum.0 + a * velocity[neighbor_index * 2], sum.1 + a * velocity[neighbor_index * 2 + 1]);
+                    diagonal += a;
+                }
+                temporary[index * 2] = sum.0 / diagonal;
+                temporary[index * 2 + 1] = sum.1 / diagonal;
+            }
+        }
+        std::mem::swap(&mut temporary, velocity);
+    }
+}
+
+/// Removes the divergence of `velocity` like `Projector::project` and returns the pressure.
+/// The pressure is zero outside of the field and the edge cells keep their velocity.
+pub fn project(velocity: &mut [f32], dimensions: (usize, usize), iterations: usize) -> Vec<f32> {
+    let mut divergence = vec![0.0; dimensions.0 * dimensions.1];
+    for y in 0 .. dimensions.1 {
+        for x in 0 .. dimensions.0 {
+            let (x, y) = (x as isize, y as isize);
+            let load = |coordinate, component| load(velocity, dimensions, 2, coordinate, component);
+            let sum = load((x + 1, y), 0) - load((x - 1, y), 0) + load((x, y + 1), 1) - load((x, y - 1), 1);
+            divergence[y as usize * dimensions.0 + x as usize] = -0.5 * sum;
+        }
+    }
+
+    let mut p = vec![0.0; divergence.len()];
+    let mut temporary = vec![0.0; divergence.len()];
+    for _ in 0 .. iterations {
+        for y in 0 .. dimensions.1 {
+            for x in 0 .. dimensions.0 {
+                let (x, y) = (x as isize, y as isize);
+                let load = |coordinate| load(&p, dimensions, 1, coordinate, 0);
+                let index = y as usize * dimensions.0 + x as usize;
+                temporary[index] = (load((x - 1, y)) + load((x + 1, y)) + load((x, y - 1)) + load((x, y + 1)) + divergence[index]) * 0.25;
+            }
+        }
+        std::mem::swap(&mut temporary, &mut p);
+    }
+
+    for y in 1 .. dimensions.1 - 1 {
+        for x in 1 .. dimensions.0 - 1 {
+            let index = y * dimensions.0 + x;
+            velocity[index * 2] -= 0.5 * (p[index + 1] - p[index - 1]);
+            velocity[index * 2 + 1] -= 0.5 * (p[index + dimensions.0] - p[index - dimensions.0]);
+        }
+    }
+    p
+}
+
+/// Applies `v += f * delta_time` for all the `forces` evaluated at `time`.
+pub fn apply_forces(velocity: &mut [f32], forces: &[Force], time: f32, delta_time: f32) {
+    for force in forces {
+        // The fields are read back from the GPU.
+        let (field, scale) = match force {
+            Force::Constant(force) => (vec![force.0, force.1], 1.0),
+            Force::TimeVarying(force) => {
+                let force = force(time);
+                (vec![force.0, force.1], 1.0)
+            },
+            Force::Field(field) => (field.data(), 1.0),
+            Force::TimeVaryingField(field, scale) => (field.data(), scale(time))
+        };
+        for (index, value) in velocity.iter_mut().enumerate() {
+            *value += field[index % field.len()] * scale * delta_time;
+        }
+    }
+}
+
+/// Multiplies `field` by `exp(-(rate + mask) * delta_time)`.
+pub fn dissipate(field: &mut [f32], components: usize, rate: f32, damping_mask: Option<&Vec<f32>>, delta_time: f32) {
+    for (index, value) in field.iter_mut().enumerate() {
+        let mask = damping_mask.map_or(0.0, |mask| mask[index / components]);
+        *value *= (-(rate + mask) * delta_time).exp();
+    }
+}
+
+/// Zeroes the velocity and the density inside the obstacles.
+pub fn limit_obstacles(fluid: &mut CpuFluid) {
+    for (index, obstacle) in fluid.obstacles.iter().enumerate() {
+        if *obstacle > 0.5 {
+            fluid.velocity[index * 2] = 0.0;
+            fluid.velocity[index * 2 + 1] = 0.0;
+            fluid.density[index] = 0.0;
+        }
+    }
+}
+
+/// The grid mode of `Simulator` on the CPU.
+pub struct CpuSimulator {
+    pub forces: Vec<Force>,
+    pub emitters: Vec<Emitter>,
+    /// Jacobi iterations of the diffusion and the projection solves.
+    pub iterations: usize,
+    pub time: f32,
+    pressure: Option<Vec<f32>>,
+    step: usize
+}
+
+impl Default for CpuSimulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CpuSimulator {
+    pub fn new() -> Self {
+        let forces = Vec::new();
+        let emitters = Vec::new();
+        let iterations = 30;
+        let time = 0.0;
+        let pressure = None;
+        let step = 0;
+        Self { forces, emitters, iterations, time, pressure, step }
+    }
+
+    /// The pressure of the last projection, `None` before the first step.
+    pub fn pressure(&self) -> Option<&Vec<f32>> {
+        self.pressure.as_ref()
+    }
+
+    /// Applies the `emitters` to the previous fields of `fluid`, like `EmitterApplier::apply`.
+    fn apply_emitters(&self, fluid: &mut CpuFluid, delta_time: f32) {
+        let dimensions = fluid.dimensions;
+        let emitters = &self.emitters;
+        for (index, emitter) in emitters.iter().enumerate() {
+            let offset = jitter(self.step * emitters.len() + index);
+            let offset = (offset.0 * emitter.jitter, offset.1 * emitter.jitter);
+            // How much of the cell is in the region, with a one cell wide antialiased edge.
+            let weight: Box<dyn Fn(f32, f32) -> f32> = match &emitter.region {
+                Region::Circle { center, radius } => {
+                    let (center, radius) = ((center.0 + offset.0, center.1 + offset.1), *radius);
+                    Box::new(move |x, y| (radius - (x - center.0).hypot(y - center.1) + 0.5).clamp(0.0, 1.0))
+                },
+                Region::Rectangle { min, max } => {
+                    let center = ((min.0 + max.0) * 0.5 + offset.0, (min.1 + max.1) * 0.5 + offset.1);
+                    let extent = ((max.0 - min.0) * 0.5 + 0.5, (max.1 - min.1) * 0.5 + 0.5);
+                    Box::new(move |x, y| {
+                        let inside = (extent.0 - (x - center.0).abs() + 0.5, extent.1 - (y - center.1).abs() + 0.5);
+                        inside.0.min(inside.1).clamp(0.0, 1.0)
+                    })
+                },
+                Region::Mask(mask) => {
+                    let mask: Vec<f32> = mask.data();
+                    Box::new(move |x, y| {
+                        let coordinate = ((x - offset.0).round() as isize, (y - offset.1).round() as isize);
+                        load(&mask, dimensions, 1, coordinate, 0).clamp(0.0, 1.0)
+                    })
+                }
+            };
+            let strength = emitter.strength.as_ref().map_or(1.0, |strength| strength(self.time));
+            for y in 0 .. dimensions.1 {
+                for x in 0 .. dimensions.0 {
+                    let amount = weight(x as f32, y as f32);
+                    if amount == 0.0 {
+                        continue;
+                    }
+                    let index = y * dimensions.0 + x;
+                    let (density, temperature) = (&mut fluid.previous_density[index], &mut fluid.previous_temperature[index]);
+                    let (velocity_x, velocity_y) = fluid.previous_velocity.split_at_mut(index * 2 + 1);
+                    let velocity = (&mut velocity_x[index * 2], &mut velocity_y[0]);
+                    match emitter.kind {
+                        EmitterKind::Source { dye_rate, temperature_rate, velocity: target, velocity_rate } => {
+                            *density += dye_rate * strength * delta_time * amount;
+                            *temperature += temperature_rate * strength * delta_time * amount;
+                            let t = (1.0 - (-velocity_rate * strength * delta_time).exp()) * amount;
+                            *velocity.0 = mix(*velocity.0, target.0, t);
+                            *velocity.1 = mix(*velocity.1, target.1, t);
+                        },
+                        EmitterKind::Sink { rate } => {
+                            let decay = (-rate * strength * delta_time * amount).exp();
+                            *density *= decay;
+                            *temperature *= decay;
+                        },
+                        EmitterKind::Outflow { velocity: target } => {
+                            *density *= 1.0 - amount;
+                            *temperature *= 1.0 - amount;
+                            *velocity.0 = mix(*velocity.0, target.0 * strength, amount);
+                            *velocity.1 = mix(*velocity.1, target.1 * strength, amount);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// The same steps, in the same order, as `Simulator::simulate` in grid mode.
+    pub fn simulate(&mut self, fluid: &mut CpuFluid, delta_time: f32) {
+        let dimensions = fluid.dimensions;
+        std::mem::swap(&mut fluid.density, &mut fluid.previous_density);
+        std::mem::swap(&mut fluid.temperature, &mut fluid.previous_temperature);
+        std::mem::swap(&mut fluid.velocity, &mut fluid.previous_velocity);
+        apply_forces(&mut fluid.previous_velocity, &self.forces, self.time, delta_time);
+        self.apply_emitters(fluid, delta_time);
+        if let Some(viscosity) = &fluid.viscosity {
+            diffuse_variable(viscosity, &mut fluid.velocity, &fluid.previous_velocity, dimensions, delta_time, self.iterations);
+            std::mem::swap(&mut fluid.velocity, &mut fluid.previous_velocity);
+        }
+        advect(&mut fluid.velocity, &fluid.previous_velocity, &fluid.previous_velocity, dimensions, 2, delta_time);
+        self.pressure = Some(project(&mut fluid.velocity, dimensions, self.iterations));
+        advect(&mut fluid.density, &fluid.previous_density, &fluid.velocity, dimensions, 1, delta_time);
+        advect(&mut fluid.temperature, &fluid.previous_temperature, &fluid.velocity, dimensions, 1, delta_time);
+        dissipate(&mut fluid.velocity, 2, fluid.velocity_dissipation, fluid.damping_mask.as_ref(), delta_time);
+        dissipate(&mut fluid.density, 1, fluid.density_dissipation, fluid.damping_mask.as_ref(), delta_time);
+        dissipate(&mut fluid.temperature, 1, fluid.temperature_dissipation, fluid.damping_mask.as_ref(), delta_time);
+        limit_obstacles(fluid);
+        self.step += 1;
+        self.time += delta_time;
+    }
+}
@@ -3,13 +3,26 @@ use crate::fluid::Fluid;
 use crate::simulator::linear_solver::LinearSolver;
 
 pub struct Diffuser {
-    linear_solver: LinearSolver
+    linear_solver: LinearSolver,
+    variable_diffusion_program: gpu::ComputeProgram,
+    temporary_vector: gpu::Texture2D
 }
 
 impl Diffuser {
+    const OUTPUT_FIELD_LOCATION    : usize = 0;
+    const X_FIELD_LOCATION         : usize = 1;
+    const B_FIELD_LOCATION         : usize = 2;
+    const VISCOSITY_FIELD_LOCATION : usize = 3;
+    const SCALE_LOCATION           : usize = 4;
+
     pub fn new(context: &Context, dimensions: (usize,usize)) -> Self {
         let linear_solver = LinearSolver::new(context, dimensions);
-        Self { linear_solver }
+
+        let variable_diffusion_shader = gpu::ComputeShader::new(&context.context, include_str!("variable_diffusion_2d.glsl")).expect("Couldn't create ComputeShader.");
+        let variable_diffusion_program = gpu::ComputeProgram::new(&context.context, &variable_diffusion_shader).expect("Couldn't create ComputeProgram.");
+        let format = gpu::TextureFormat::new(gpu::ColorFormat::RG, gpu::Type::F32);
+        let temporary_vector = gpu::Texture2D::allocate(&context.context, dimensions, &format);
+        Self { linear_solver, variable_diffusion_program, temporary_vector }
     }
 
     pub fn diffuse(&mut self, diffusion: f32, is_velocity_field: bool, current_field: &mut gpu::Texture2D, previous_field: &gpu::Texture2D, delta_time: f32, iterations: usize) {
@@ -20,4 +33,127 @@ impl Diffuser {
         let c = 1.0 + 6.0 * a;
         self.linear_solver.solve(is_velocity_field, current_field, previous_field, a, c, iterations);
     }
-}
\ No newline at end of file
+
+    /// Diffuses the vector `previous_field` into `current_field` with the per cell `viscosity_field`, with Jacobi iterations
+    /// of a backward Euler step. The face coefficients are the harmonic mean of the viscosities times `delta_time` and the
+    /// inner volume, and the sides of the field have a zero gradient.
+    /// Unlike `diffuse`, which keeps the 3D `1 + 6a` diagonal and zero values outside of the field, the diagonal is `1 + sum(a)`.
+    pub fn diffuse_variable(&mut self, viscosity_field: &gpu::Texture2D, current_field: &mut gpu::Texture2D, previous_field: &gpu::Texture2D, delta_time: f32, iterations: usize) {
+        let dimensions = current_field.dimensions();
+        let volume = ((dimensions.0 - 2) * (dimensions.1 - 2)) as f32;
+        let scale = delta_time * volume;
+        let dimensions = (dimensions.0, dimensions.1, 1);
+
+        let program = &self.variable_diffusion_program;
+        program.bind_image_2d(previous_field, Self::B_FIELD_LOCATION);
+        program.bind_image_2d(viscosity_field, Self::VISCOSITY_FIELD_LOCATION);
+        program.bind_f32(scale, Self::SCALE_LOCATION);
+        for _ in 0 .. iterations {
+            program.bind_image_2d(&self.temporary_vector, Self::OUTPUT_FIELD_LOCATION);
+            program.bind_image_2d(current_field, Self::X_FIELD_LOCATION);
+            program.compute(dimensions);
+            //FIXME: How to expose it on the GPU API?
+            // Ref: https://www.khronos.org/registry/OpenGL-Refpages/gl4/html/glMemoryBarrier.xhtml
+            unsafe {
+                gl::MemoryBarrier(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+            }
+            std::mem::swap(&mut self.temporary_vector, current_field);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::context::Context;
+    use crate::simulator::diffuser::Diffuser;
+
+    fn initialize_field(context: &Context, dimensions: (usize, usize), data: &[f32], color_format: gpu::ColorFormat) -> gpu::Texture2D {
+        let format = gpu::TextureFormat::new(color_format, gpu::Type::F32);
+        let field = gpu::Texture2D::from_data(&context.context, dimensions, &format, &data, &format);
+        assert_eq!(field.data() as Vec<f32>, data);
+        field
+    }
+
+    #[test]
+    fn inviscid_cells_keep_their_velocity() {
+        let dimensions = (3, 3);
+        let context = Context::new(dimensions);
+        let mut diffuser = Diffuser::new(&context, dimensions);
+
+        let viscosity_data = vec![
+            0.0, 0.0, 0.0,
+            0.0, 0.0, 0.0,
+            0.0, 0.0, 0.0
+        ];
+        let previous_data = vec![
+            0.0, 0.0, /**/ 0.0, 0.0, /**/ 0.0, 0.0,
+            0.0, 0.0, /**/ 1.0, 2.0, /**/ 0.0, 0.0,
+            0.0, 0.0, /**/ 0.0, 0.0, /**/ 0.0, 0.0
+        ];
+        let viscosity_field = initialize_field(&context, dimensions, &viscosity_data, gpu::ColorFormat::R);
+        let previous_field = initialize_field(&context, dimensions, &previous_data, gpu::ColorFormat::RG);
+        let mut field = initialize_field(&context, dimensions, &vec![1.0; 18], gpu::ColorFormat::RG);
+
+        diffuser.diffuse_variable(&viscosity_field, &mut field, &previous_field, 1.0, 2);
+        assert_eq!(field.data() as Vec<f32>, previous_data);
+    }
+
+    #[test]
+    fn harmonic_face_viscosity() {
+        // The inner volume is 1, so the face coefficients are the face viscosities.
+        let dimensions = (3, 3);
+        let context = Context::new(dimensions);
+        let mut diffuser = Diffuser::new(&context, dimensions);
+
+        let viscosity_data = vec![
+            0.0, 1.0, 1.0,
+            0.0, 1.0, 1.0,
+            0.0, 1.0, 1.0
+        ];
+        let previous_data = vec![1.0; 18];
+        let viscosity_field = initialize_field(&context, dimensions, &viscosity_data, gpu::ColorFormat::R);
+        let previous_field = initialize_field(&context, dimensions, &previous_data, gpu::ColorFormat::RG);
+
+        // From zero, one iteration is b / (1 + sum(a)). The faces next to the inviscid column don't count.
+        let mut field = initialize_field(&context, dimensions, &vec![0.0; 18], gpu::ColorFormat::RG);
+        diffuser.diffuse_variable(&viscosity_field, &mut field, &previous_field, 1.0, 1);
+        let expected_data = vec![
+            1.0, 1.0, /**/ 1.0 / 3.0, 1.0 / 3.0, /**/ 1.0 / 3.0, 1.0 / 3.0,
+            1.0, 1.0, /**/ 1.0 / 4.0, 1.0 / 4.0, /**/ 1.0 / 4.0, 1.0 / 4.0,
+            1.0, 1.0, /**/ 1.0 / 3.0, 1.0 / 3.0, /**/ 1.0 / 3.0, 1.0 / 3.0
+        ];
+        let data: Vec<f32> = field.data();
+        for (value, expected) in data.iter().zip(&expected_data) {
+            assert!((value - expected).abs() < 1e-6, "{:?} instead of {:?}", data, expected_data);
+        }
+
+        // A uniform field is the solution.
+        let mut field = initialize_field(&context, dimensions, &previous_data, gpu::ColorFormat::RG);
+        diffuser.diffuse_variable(&viscosity_field, &mut field, &previous_field, 1.0, 4);
+        assert_eq!(field.data() as Vec<f32>, previous_data);
+    }
+
+    #[test]
+    fn viscous_cells_spread_their_velocity() {
+        let dimensions = (5, 5);
+        let context = Context::new(dimensions);
+        let mut diffuser = Diffuser::new(&context, dimensions);
+
+        // An inner volume of 9.
+        let viscosity_field = initialize_field(&context, dimensions, &vec![1.0 / 9.0; 25], gpu::ColorFormat::R);
+        let mut previous_data = vec![0.0; 50];
+        previous_data[2 * 10 + 2 * 2] = 1.0;
+        let previous_field = initialize_field(&context, dimensions, &previous_data, gpu::ColorFormat::RG);
+        let mut field = initialize_field(&context, dimensions, &previous_data, gpu::ColorFormat::RG);
+        diffuser.diffuse_variable(&viscosity_field, &mut field, &previous_field, 1.0, 40);
+
+        // The backward Euler step keeps the momentum, spreads it symmetrically and doesn't mix the components.
+        let data: Vec<f32> = field.data();
+        let x = |column: usize, row: usize| data[(row * dimensions.0 + column) * 2];
+        let momentum: f32 = data.iter().step_by(2).sum();
+        assert!((momentum - 1.0).abs() < 1e-3);
+        assert!(x(2, 2) < 1.0 && x(2, 2) > x(1, 2) && x(1, 2) > x(0, 2) && x(0, 2) > 0.0);
+        assert!((x(1, 2) - x(3, 2)).abs() < 1e-6 && (x(2, 1) - x(2, 3)).abs() < 1e-6 && (x(1, 2) - x(2, 1)).abs() < 1e-6);
+        assert!(data.iter().skip(1).step_by(2).all(|value| *value == 0.0));
+    }
+}
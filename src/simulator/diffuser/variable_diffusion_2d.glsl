@@ -0,0 +1,39 @@
+#version 450
+
+// One Jacobi iteration of (1 + sum(a)) x - sum(a * xNeighbor) = b, where a is the viscosity on the face times scale.
+layout(rg32f, location = 0) uniform image2D outputField;
+layout(rg32f, location = 1) uniform image2D xField;
+layout(rg32f, location = 2) uniform image2D bField;
+layout(r32f, location = 3) uniform image2D viscosityField;
+layout(location = 4) uniform float scale;
+
+layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+#define NUMBER_OF_NEIGHBORS 4
+const ivec2 neighborsOffsets[NUMBER_OF_NEIGHBORS] = {
+    ivec2(-1,  0),
+    ivec2( 1,  0),
+    ivec2( 0,  1),
+    ivec2( 0, -1)
+};
+
+void main() {
+    ivec2 coordinate = ivec2(gl_GlobalInvocationID.xy);
+    ivec2 size = imageSize(xField);
+    float viscosity = imageLoad(viscosityField, coordinate).x;
+
+    vec4 sum = imageLoad(bField, coordinate);
+    float diagonal = 1.0;
+    for (int i = 0; i < NUMBER_OF_NEIGHBORS; i++) {
+        ivec2 neighborCoordinate = coordinate + neighborsOffsets[i];
+        if (any(lessThan(neighborCoordinate, ivec2(0))) || any(greaterThanEqual(neighborCoordinate, size))) continue;
+        // The face viscosity is the harmonic mean, so a thin fluid doesn't leak momentum into a thick one.
+        float neighborViscosity = imageLoad(viscosityField, neighborCoordinate).x;
+        float faceViscosity = 2.0 * viscosity * neighborViscosity / max(viscosity + neighborViscosity, 1e-12);
+        float a = faceViscosity * scale;
+        sum += a * imageLoad(xField, neighborCoordinate);
+        diagonal += a;
+    }
+
+    imageStore(outputField, coordinate, sum / diagonal);
+}
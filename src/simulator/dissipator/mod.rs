@@ -0,0 +1,148 @@
+use crate::context::Context;
+use crate::field::Field;
+
+/// Creates a damping mask that ramps quadratically from zero, `width` cells away from the sides, to `strength` on the sides.
+/// It absorbs the outgoing flow like a sponge layer around open boundaries.
+pub fn sponge_mask(context: &Context, dimensions: (usize, usize), width: f32, strength: f32) -> gpu::Texture2D {
+    Field::from_fn(context, dimensions, 1, |x, y| {
+        let distance_x = x.min(dimensions.0 - 1 - x) as f32;
+        let distance_y = y.min(dimensions.1 - 1 - y) as f32;
+        let distance = distance_x.min(distance_y);
+        let depth = (1.0 - distance / width.max(std::f32::EPSILON)).max(0.0);
+        [strength * depth * depth]
+    }).field
+}
+
+/// Exponentially decays the fields by `exp(-(rate + mask) * delta_time)`.
+pub struct Dissipator {
+    scalar_dissipation_program: gpu::ComputeProgram,
+    vector_dissipation_program: gpu::ComputeProgram
+}
+
+impl Dissipator {
+    pub fn new(context: &Context) -> Self {
+        let scalar_dissipation_shader  = gpu::ComputeShader::new(&context.context, include_str!("scalar_dissipation_2d.glsl")).expect("Couldn't create compute_shader.");
+        let scalar_dissipation_program = gpu::ComputeProgram::new(&context.context, &scalar_dissipation_shader).expect("Couldn't create compute_program.");
+
+        let vector_dissipation_shader  = gpu::ComputeShader::new(&context.context, include_str!("vec2_dissipation_2d.glsl")).expect("Couldn't create compute_shader.");
+        let vector_dissipation_program = gpu::ComputeProgram::new(&context.context, &vector_dissipation_shader).expect("Couldn't create compute_program.");
+
+        Self { scalar_dissipation_program, vector_dissipation_program }
+    }
+
+    fn dissipate_program(&self, program: &gpu::ComputeProgram, field: &mut gpu::Texture2D, rate: f32, damping_mask: Option<&gpu::Texture2D>, delta_time: f32) {
+        const FIELD_LOCATION            : usize = 0;
+        const DAMPING_MASK_LOCATION     : usize = 1;
+        const RATE_LOCATION             : usize = 2;
+        const USE_DAMPING_MASK_LOCATION : usize = 3;
+        const DELTA_TIME_LOCATION       : usize = 4;
+        if rate == 0.0 && damping_mask.is_none() {
+            return;
+        }
+        let dimensions = field.dimensions();
+        let dimensions = (dimensions.0, dimensions.1, 1);
+        program.bind_image_2d(field, FIELD_LOCATION);
+        if let Some(damping_mask) = damping_mask {
+            program.bind_image_2d(damping_mask, DAMPING_MASK_LOCATION);
+        }
+        program.bind_f32(rate, RATE_LOCATION);
+        program.bind_bool(damping_mask.is_some(), USE_DAMPING_MASK_LOCATION);
+        program.bind_f32(delta_time, DELTA_TIME_LOCATION);
+        program.compute(dimensions);
+        //FIXME: How to expose it on the GPU API?
+        // Ref: https://www.khronos.org/registry/OpenGL-Refpages/gl4/html/glMemoryBarrier.xhtml
+        unsafe {
+            gl::MemoryBarrier(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+        }
+    }
+
+    pub fn dissipate_scalar(&self, field: &mut gpu::Texture2D, rate: f32, damping_mask: Option<&gpu::Texture2D>, delta_time: f32) {
+        self.dissipate_program(&self.scalar_dissipation_program, field, rate, damping_mask, delta_time)
+    }
+
+    pub fn dissipate_vector(&self, field: &mut gpu::Texture2D, rate: f32, damping_mask: Option<&gpu::Texture2D>, delta_time: f32) {
+        self.dissipate_program(&self.vector_dissipation_program, field, rate, damping_mask, delta_time)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::context::Context;
+    use crate::field::Field;
+    use crate::simulator::dissipator::{Dissipator, sponge_mask};
+
+    fn initialize_field(context: &Context, dimensions: (usize, usize), data: &[f32], components: usize) -> gpu::Texture2D {
+        Field::from_data(context, dimensions, components, data).field
+    }
+
+    fn assert_close(data: &[f32], expected_data: &[f32]) {
+        assert_eq!(data.len(), expected_data.len());
+        for (value, expected) in data.iter().zip(expected_data) {
+            assert!((value - expected).abs() < 1e-5, "{:?} instead of {:?}", data, expected_data);
+        }
+    }
+
+    fn assert_field(field: &gpu::Texture2D, expected_data: &[f32]) {
+        let data: Vec<f32> = field.data();
+        assert_close(&data, expected_data);
+    }
+
+    #[test]
+    fn exponential_rate() {
+        let dimensions = (2, 1);
+        let context = Context::new(dimensions);
+        let dissipator = Dissipator::new(&context);
+        let mut field = initialize_field(&context, dimensions, &[2.0, -1.0], 1);
+        dissipator.dissipate_scalar(&mut field, 0.5, None, 2.0);
+        assert_field(&field, &[2.0 * (-1.0f32).exp(), -(-1.0f32).exp()]);
+
+        let mut field = initialize_field(&context, dimensions, &[2.0, -1.0, 4.0, 0.5], 2);
+        dissipator.dissipate_vector(&mut field, 0.5, None, 2.0);
+        let decay = (-1.0f32).exp();
+        assert_field(&field, &[2.0 * decay, -decay, 4.0 * decay, 0.5 * decay]);
+    }
+
+    #[test]
+    fn time_step_independent() {
+        let dimensions = (2, 1);
+        let context = Context::new(dimensions);
+        let dissipator = Dissipator::new(&context);
+        let mut results = Vec::new();
+        for &steps in &[1, 8] {
+            let mut field = initialize_field(&context, dimensions, &[1.0, 3.0], 1);
+            for _ in 0 .. steps {
+                dissipator.dissipate_scalar(&mut field, 0.75, None, 1.0 / steps as f32);
+            }
+            results.push(field.data() as Vec<f32>);
+        }
+        assert_close(&results[0], &[(-0.75f32).exp(), 3.0 * (-0.75f32).exp()]);
+        assert_close(&results[1], &results[0]);
+    }
+
+    #[test]
+    fn damping_mask_adds_to_the_rate() {
+        let dimensions = (3, 1);
+        let context = Context::new(dimensions);
+        let dissipator = Dissipator::new(&context);
+        let damping_mask = initialize_field(&context, dimensions, &[0.0, 1.0, 2.0], 1);
+        let mut field = initialize_field(&context, dimensions, &[1.0; 3], 1);
+        dissipator.dissipate_scalar(&mut field, 0.5, Some(&damping_mask), 1.0);
+        assert_field(&field, &[(-0.5f32).exp(), (-1.5f32).exp(), (-2.5f32).exp()]);
+
+        // Without a rate, only the masked cells decay.
+        let mut field = initialize_field(&context, dimensions, &[1.0; 6], 2);
+        dissipator.dissipate_vector(&mut field, 0.0, Some(&damping_mask), 1.0);
+        let (one, two) = ((-1.0f32).exp(), (-2.0f32).exp());
+        assert_field(&field, &[1.0, 1.0, one, one, two, two]);
+    }
+
+    #[test]
+    fn sponge() {
+        let dimensions = (9, 9);
+        let context = Context::new(dimensions);
+        let mask: Vec<f32> = sponge_mask(&context, dimensions, 2.0, 4.0).data();
+        // Quadratic from the sides to `width` cells inside, along the closest side.
+        assert_close(&mask[4 * 9 .. 5 * 9], &[4.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 4.0]);
+        assert_eq!((mask[9 + 1], mask[9 + 4]), (1.0, 1.0));
+    }
+}
@@ -0,0 +1,17 @@
+#version 450
+
+layout(r32f, location = 0) uniform image2D field;
+layout(r32f, location = 1) uniform image2D dampingMask;
+layout(location = 2) uniform float rate;
+layout(location = 3) uniform bool useDampingMask;
+layout(location = 4) uniform float deltaTime;
+
+layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+void main() {
+    ivec2 coordinate = ivec2(gl_GlobalInvocationID.xy);
+    float totalRate = rate;
+    if (useDampingMask) totalRate += imageLoad(dampingMask, coordinate).x;
+    vec4 value = imageLoad(field, coordinate) * exp(-totalRate * deltaTime);
+    imageStore(field, coordinate, value);
+}
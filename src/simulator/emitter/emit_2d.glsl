@@ -0,0 +1,64 @@
+#version 450
+
+layout(r32f,  location = 0) uniform image2D densityField;
+layout(rg32f, location = 1) uniform image2D velocityField;
+layout(r32f,  location = 2) uniform image2D maskField;
+layout(location = 3)  uniform int shape;
+layout(location = 4)  uniform vec2 center;
+layout(location = 5)  uniform vec2 extent;
+layout(location = 6)  uniform int kind;
+layout(location = 7)  uniform float dyeRate;
+layout(location = 8)  uniform vec2 targetVelocity;
+layout(location = 9)  uniform float velocityRate;
+layout(location = 10) uniform float deltaTime;
+layout(r32f,  location = 11) uniform image2D temperatureField;
+layout(location = 12) uniform float temperatureRate;
+
+layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+#define CIRCLE    0
+#define RECTANGLE 1
+#define MASK      2
+
+#define SOURCE  0
+#define SINK    1
+#define OUTFLOW 2
+
+// How much of the cell is in the region, with a one cell wide antialiased edge.
+float weight(vec2 position) {
+    if (shape == CIRCLE) {
+        return clamp(extent.x - distance(position, center) + 0.5, 0.0, 1.0);
+    } else if (shape == RECTANGLE) {
+        vec2 inside = extent - abs(position - center) + 0.5;
+        return clamp(min(inside.x, inside.y), 0.0, 1.0);
+    }
+    // The mask is shifted by the center, its loads are zero outside of the field.
+    return clamp(imageLoad(maskField, ivec2(round(position - center))).x, 0.0, 1.0);
+}
+
+void main() {
+    ivec2 coordinate = ivec2(gl_GlobalInvocationID.xy);
+    float amount = weight(vec2(coordinate));
+    if (amount == 0.0) {
+        return;
+    }
+    float density = imageLoad(densityField, coordinate).x;
+    float temperature = imageLoad(temperatureField, coordinate).x;
+    vec2 velocity = imageLoad(velocityField, coordinate).xy;
+    if (kind == SOURCE) {
+        density += dyeRate * deltaTime * amount;
+        temperature += temperatureRate * deltaTime * amount;
+        // Relaxes towards the target velocity, exponentially so it doesn't depend on the time step.
+        velocity = mix(velocity, targetVelocity, (1.0 - exp(-velocityRate * deltaTime)) * amount);
+    } else if (kind == SINK) {
+        density *= exp(-dyeRate * deltaTime * amount);
+        temperature *= exp(-dyeRate * deltaTime * amount);
+    } else {
+        density *= 1.0 - amount;
+        temperature *= 1.0 - amount;
+        velocity = mix(velocity, targetVelocity, amount);
+    }
+    imageStore(densityField, coordinate, vec4(density));
+    imageStore(temperatureField, coordinate, vec4(temperature));
+    imageStore(velocityField, coordinate, vec4(velocity, 0.0, 0.0));
+}
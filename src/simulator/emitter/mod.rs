@@ -0,0 +1,240 @@
+use crate::context::Context;
+use crate::field::Field;
+use crate::simulator::particle_solver::jitter;
+
+/// Where an emitter acts, in cells.
+pub enum Region {
+    Circle { center: (f32, f32), radius: f32 },
+    /// The cells from `min` to `max`, inclusive.
+    Rectangle { min: (f32, f32), max: (f32, f32) },
+    /// A R field with the per cell weight, from 0 outside to 1 inside.
+    Mask(gpu::Texture2D)
+}
+
+impl Region {
+    /// Creates a `Region::Mask` by evaluating `weight` on the CPU for every cell coordinate.
+    pub fn mask_from_fn(context: &Context, dimensions: (usize, usize), weight: impl Fn(usize, usize) -> f32) -> Self {
+        Region::Mask(Field::from_fn(context, dimensions, 1, |x, y| [weight(x, y)]).field)
+    }
+}
+
+/// What an emitter does in its region. The rates are per second, so the amounts don't depend on the time step.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EmitterKind {
+    /// Adds `dye_rate` dye and `temperature_rate` temperature per second and pulls the velocity towards `velocity` at `velocity_rate`.
+    Source { dye_rate: f32, temperature_rate: f32, velocity: (f32, f32), velocity_rate: f32 },
+    /// Removes the dye and the temperature with an exponential decay of `rate` per second.
+    Sink { rate: f32 },
+    /// Removes all the dye and the temperature and sets the velocity, e.g. to let the flow leave the domain.
+    Outflow { velocity: (f32, f32) }
+}
+
+/// A persistent source or sink, applied on every step of `Simulator::simulate`.
+pub struct Emitter {
+    pub region: Region,
+    pub kind: EmitterKind,
+    /// Scales the rates of `kind`, or the outflow velocity, by a function of the simulation time, e.g. to pulse.
+    pub strength: Option<Box<dyn Fn(f32) -> f32>>,
+    /// Moves the region up to `jitter` cells in a random direction on every step.
+    pub jitter: f32
+}
+
+impl Emitter {
+    pub fn new(region: Region, kind: EmitterKind) -> Self {
+        let strength = None;
+        let jitter = 0.0;
+        Self { region, kind, strength, jitter }
+    }
+}
+
+pub struct EmitterApplier {
+    program: gpu::ComputeProgram,
+    step: usize
+}
+
+impl EmitterApplier {
+    pub fn new(context: &Context) -> Self {
+        let shader = gpu::ComputeShader::new(&context.context, include_str!("emit_2d.glsl")).expect("Couldn't create emit_shader.");
+        let program = gpu::ComputeProgram::new(&context.context, &shader).expect("Couldn't create emit_program.");
+        let step = 0;
+        Self { program, step }
+    }
+
+    /// Applies all the `emitters` evaluated at `time` to the density, the temperature and the velocity fields.
+    pub fn apply(&mut self, density_field: &mut gpu::Texture2D, temperature_field: &mut gpu::Texture2D, velocity_field: &mut gpu::Texture2D, emitters: &[Emitter], time: f32, delta_time: f32) {
+        const DENSITY_FIELD_LOCATION     : usize = 0;
+        const VELOCITY_FIELD_LOCATION    : usize = 1;
+        const MASK_FIELD_LOCATION        : usize = 2;
+        const SHAPE_LOCATION             : usize = 3;
+        const CENTER_LOCATION            : usize = 4;
+        const EXTENT_LOCATION            : usize = 5;
+        const KIND_LOCATION              : usize = 6;
+        const DYE_RATE_LOCATION          : usize = 7;
+        const VELOCITY_LOCATION          : usize = 8;
+        const VELOCITY_RATE_LOCATION     : usize = 9;
+        const DELTA_TIME_LOCATION        : usize = 10;
+        const TEMPERATURE_FIELD_LOCATION : usize = 11;
+        const TEMPERATURE_RATE_LOCATION  : usize = 12;
+        let dimensions = density_field.dimensions();
+        let dimensions = (dimensions.0, dimensions.1, 1);
+        for (index, emitter) in emitters.iter().enumerate() {
+            let offset = jitter(self.step * emitters.len() + index);
+            let offset = (offset.0 * emitter.jitter, offset.1 * emitter.jitter);
+            let (shape, center, extent) = match &emitter.region {
+                Region::Circle { center, radius } => (0, *center, (*radius, *radius)),
+                Region::Rectangle { min, max } => (1, ((min.0 + max.0) * 0.5, (min.1 + max.1) * 0.5), ((max.0 - min.0) * 0.5 + 0.5, (max.1 - min.1) * 0.5 + 0.5)),
+                Region::Mask(mask) => {
+                    self.program.bind_image_2d(mask, MASK_FIELD_LOCATION);
+                    (2, (0.0, 0.0), (0.0, 0.0))
+                }
+            };
+            let strength = emitter.strength.as_ref().map_or(1.0, |strength| strength(time));
+            let (kind, dye_rate, temperature_rate, velocity, velocity_rate) = match emitter.kind {
+                EmitterKind::Source { dye_rate, temperature_rate, velocity, velocity_rate } => (0, dye_rate * strength, temperature_rate * strength, velocity, velocity_rate * strength),
+                EmitterKind::Sink { rate } => (1, rate * strength, 0.0, (0.0, 0.0), 0.0),
+                EmitterKind::Outflow { velocity } => (2, 0.0, 0.0, (velocity.0 * strength, velocity.1 * strength), 0.0)
+            };
+            self.program.bind_image_2d(density_field, DENSITY_FIELD_LOCATION);
+            self.program.bind_image_2d(velocity_field, VELOCITY_FIELD_LOCATION);
+            self.program.bind_image_2d(temperature_field, TEMPERATURE_FIELD_LOCATION);
+            self.program.bind_i32(shape, SHAPE_LOCATION);
+            self.program.bind_vec2((center.0 + offset.0, center.1 + offset.1), CENTER_LOCATION);
+            self.program.bind_vec2(extent, EXTENT_LOCATION);
+            self.program.bind_i32(kind, KIND_LOCATION);
+            self.program.bind_f32(dye_rate, DYE_RATE_LOCATION);
+            self.program.bind_f32(temperature_rate, TEMPERATURE_RATE_LOCATION);
+            self.program.bind_vec2(velocity, VELOCITY_LOCATION);
+            self.program.bind_f32(velocity_rate, VELOCITY_RATE_LOCATION);
+            self.program.bind_f32(delta_time, DELTA_TIME_LOCATION);
+            self.program.compute(dimensions);
+            //FIXME: How to expose it on the GPU API?
+            // Ref: https://www.khronos.org/registry/OpenGL-Refpages/gl4/html/glMemoryBarrier.xhtml
+            unsafe {
+                gl::MemoryBarrier(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+            }
+        }
+        self.step += 1;
+    }
+
+}
+
+#[cfg(test)]
+mod test {
+    use crate::context::Context;
+    use crate::field::Field;
+    use crate::simulator::emitter::{EmitterApplier, Emitter, EmitterKind, Region};
+    use crate::simulator::particle_solver::jitter;
+
+    struct Fields {
+        density: gpu::Texture2D,
+        temperature: gpu::Texture2D,
+        velocity: gpu::Texture2D
+    }
+
+    impl Fields {
+        fn new(context: &Context, dimensions: (usize, usize), scalar: f32, velocity: (f32, f32)) -> Self {
+            let density = Field::from_fn(context, dimensions, 1, |_, _| [scalar]).field;
+            let temperature = Field::from_fn(context, dimensions, 1, |_, _| [scalar]).field;
+            let velocity = Field::from_fn(context, dimensions, 2, |_, _| [velocity.0, velocity.1]).field;
+            Self { density, temperature, velocity }
+        }
+
+        fn apply(&mut self, emitter_applier: &mut EmitterApplier, emitters: &[Emitter], time: f32, delta_time: f32) {
+            emitter_applier.apply(&mut self.density, &mut self.temperature, &mut self.velocity, emitters, time, delta_time);
+        }
+    }
+
+    fn dye(dye_rate: f32) -> EmitterKind {
+        EmitterKind::Source { dye_rate, temperature_rate: 0.0, velocity: (0.0, 0.0), velocity_rate: 0.0 }
+    }
+
+    #[test]
+    fn amounts_are_time_step_independent() {
+        let dimensions = (4, 1);
+        let context = Context::new(dimensions);
+        let mut emitter_applier = EmitterApplier::new(&context);
+        let emitters = vec![
+            Emitter::new(Region::Rectangle { min: (0.0, 0.0), max: (1.0, 0.0) }, EmitterKind::Source { dye_rate: 2.0, temperature_rate: 3.0, velocity: (4.0, 0.0), velocity_rate: 1.0 }),
+            Emitter::new(Region::Rectangle { min: (2.0, 0.0), max: (2.0, 0.0) }, EmitterKind::Sink { rate: 1.0 }),
+            Emitter::new(Region::Rectangle { min: (3.0, 0.0), max: (3.0, 0.0) }, EmitterKind::Outflow { velocity: (1.0, 0.0) })
+        ];
+        let mut results = Vec::new();
+        for &steps in &[1, 4] {
+            let mut fields = Fields::new(&context, dimensions, 1.0, (0.0, 0.0));
+            for step in 0 .. steps {
+                fields.apply(&mut emitter_applier, &emitters, step as f32 / steps as f32, 1.0 / steps as f32);
+            }
+            results.push((fields.density.data() as Vec<f32>, fields.temperature.data() as Vec<f32>, fields.velocity.data() as Vec<f32>));
+        }
+        for (density, temperature, velocity) in &results {
+            let expected_velocity = 4.0 * (1.0 - (-1.0f32).exp());
+            assert!((density[0] - 3.0).abs() < 1e-5 && (temperature[0] - 4.0).abs() < 1e-5 && (velocity[0] - expected_velocity).abs() < 1e-5);
+            assert!((density[2] - (-1.0f32).exp()).abs() < 1e-5 && (temperature[2] - (-1.0f32).exp()).abs() < 1e-5);
+            assert_eq!((density[3], temperature[3], velocity[6]), (0.0, 0.0, 1.0));
+        }
+    }
+
+    #[test]
+    fn circle() {
+        let dimensions = (5, 5);
+        let context = Context::new(dimensions);
+        let mut emitter_applier = EmitterApplier::new(&context);
+        let mut fields = Fields::new(&context, dimensions, 0.0, (0.0, 0.0));
+        fields.apply(&mut emitter_applier, &[Emitter::new(Region::Circle { center: (2.0, 2.0), radius: 1.5 }, dye(1.0))], 0.0, 1.0);
+
+        // The weight is the radius minus the distance, plus half a cell for the antialiasing.
+        let diagonal = 2.0 - 2.0f32.sqrt();
+        let expected_data = vec![
+            0.0, 0.0,      0.0, 0.0,      0.0,
+            0.0, diagonal, 1.0, diagonal, 0.0,
+            0.0, 1.0,      1.0, 1.0,      0.0,
+            0.0, diagonal, 1.0, diagonal, 0.0,
+            0.0, 0.0,      0.0, 0.0,      0.0
+        ];
+        let data: Vec<f32> = fields.density.data();
+        for (value, expected) in data.iter().zip(&expected_data) {
+            assert!((value - expected).abs() < 1e-5, "{:?}", data);
+        }
+    }
+
+    #[test]
+    fn mask() {
+        let dimensions = (5, 1);
+        let context = Context::new(dimensions);
+        let mut emitter_applier = EmitterApplier::new(&context);
+        let mut fields = Fields::new(&context, dimensions, 0.0, (0.0, 0.0));
+        let emitters = vec![Emitter::new(Region::mask_from_fn(&context, dimensions, |x, _| x as f32 * 0.25), dye(2.0))];
+        fields.apply(&mut emitter_applier, &emitters, 0.0, 1.0);
+        assert_eq!(fields.density.data() as Vec<f32>, vec![0.0, 0.5, 1.0, 1.5, 2.0]);
+    }
+
+    #[test]
+    fn jitter_moves_the_region() {
+        let dimensions = (9, 9);
+        let context = Context::new(dimensions);
+        let mut emitter_applier = EmitterApplier::new(&context);
+        let mut fields = Fields::new(&context, dimensions, 0.0, (0.0, 0.0));
+        let mut emitter = Emitter::new(Region::Circle { center: (4.0, 4.0), radius: 0.5 }, dye(1.0));
+        emitter.jitter = 2.0;
+        let emitters = vec![emitter];
+
+        // Every step moves the circle by the next offset.
+        let mut expected_data = vec![0.0; dimensions.0 * dimensions.1];
+        for step in 0 .. 3 {
+            fields.apply(&mut emitter_applier, &emitters, step as f32, 1.0);
+            let offset = jitter(step);
+            let center = (4.0 + offset.0 * 2.0, 4.0 + offset.1 * 2.0);
+            for y in 0 .. dimensions.1 {
+                for x in 0 .. dimensions.0 {
+                    let distance = ((x as f32 - center.0).powi(2) + (y as f32 - center.1).powi(2)).sqrt();
+                    expected_data[y * dimensions.0 + x] += (1.0 - distance).max(0.0).min(1.0);
+                }
+            }
+        }
+        assert!(expected_data.iter().filter(|value| **value > 0.0).count() > 4);
+        let data: Vec<f32> = fields.density.data();
+        for (value, expected) in data.iter().zip(&expected_data) {
+            assert!((value - expected).abs() < 1e-4, "{:?} instead of {:?}", data, expected_data);
+        }
+    }
+}
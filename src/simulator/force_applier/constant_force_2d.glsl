@@ -0,0 +1,14 @@
+#version 450
+
+layout(rg32f, location = 0) uniform image2D velocityField;
+layout(location = 1) uniform vec2 acceleration;
+layout(location = 2) uniform float deltaTime;
+
+layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+void main() {
+    ivec2 coordinate = ivec2(gl_GlobalInvocationID.xy);
+    vec2 velocity = imageLoad(velocityField, coordinate).xy;
+    velocity += acceleration * deltaTime;
+    imageStore(velocityField, coordinate, vec4(velocity, 0.0, 0.0));
+}
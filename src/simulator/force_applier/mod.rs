@@ -0,0 +1,140 @@
+use crate::context::Context;
+use crate::field::Field;
+
+/// An external acceleration applied to the velocity field.
+pub enum Force {
+    /// The same acceleration on every cell, e.g. gravity.
+    Constant((f32, f32)),
+    /// An acceleration on every cell that changes with the simulation time, e.g. gusts of wind.
+    TimeVarying(Box<dyn Fn(f32) -> (f32, f32)>),
+    /// A per cell acceleration stored in a RG field.
+    Field(gpu::Texture2D),
+    /// A per cell acceleration scaled by a function of the simulation time.
+    TimeVaryingField(gpu::Texture2D, Box<dyn Fn(f32) -> f32>)
+}
+
+impl Force {
+    /// Creates a `Force::Field` from interleaved (x, y) accelerations.
+    pub fn field_from_data(context: &Context, dimensions: (usize, usize), data: &[f32]) -> Self {
+        assert_eq!(data.len(), dimensions.0 * dimensions.1 * 2, "The force field data must have two components per cell.");
+        Force::Field(Field::from_data(context, dimensions, 2, data).field)
+    }
+
+    /// Creates a `Force::Field` by evaluating `force` on the CPU for every cell coordinate.
+    pub fn field_from_fn(context: &Context, dimensions: (usize, usize), force: impl Fn(usize, usize) -> (f32, f32)) -> Self {
+        Force::Field(Field::from_fn(context, dimensions, 2, |x, y| {
+            let (force_x, force_y) = force(x, y);
+            [force_x, force_y]
+        }).field)
+    }
+}
+
+pub struct ForceApplier {
+    constant_program: gpu::ComputeProgram,
+    field_program: gpu::ComputeProgram
+}
+
+impl ForceApplier {
+    pub fn new(context: &Context) -> Self {
+        let constant_shader = gpu::ComputeShader::new(&context.context, include_str!("constant_force_2d.glsl")).expect("Couldn't create constant_shader.");
+        let constant_program = gpu::ComputeProgram::new(&context.context, &constant_shader).expect("Couldn't create constant_program.");
+        let field_shader = gpu::ComputeShader::new(&context.context, include_str!("field_force_2d.glsl")).expect("Couldn't create field_shader.");
+        let field_program = gpu::ComputeProgram::new(&context.context, &field_shader).expect("Couldn't create field_program.");
+        Self { constant_program, field_program }
+    }
+
+    /// Applies `v += f * delta_time` for all the `forces` evaluated at `time`.
+    pub fn apply(&self, velocity_field: &mut gpu::Texture2D, forces: &[Force], time: f32, delta_time: f32) {
+        // The uniform forces are summed on the CPU so they cost a single dispatch.
+        let mut acceleration = (0.0, 0.0);
+        let mut has_acceleration = false;
+        for force in forces {
+            match force {
+                Force::Constant(force) => {
+                    acceleration = (acceleration.0 + force.0, acceleration.1 + force.1);
+                    has_acceleration = true;
+                },
+                Force::TimeVarying(force) => {
+                    let force = force(time);
+                    acceleration = (acceleration.0 + force.0, acceleration.1 + force.1);
+                    has_acceleration = true;
+                },
+                Force::Field(field) => self.apply_field(velocity_field, field, 1.0, delta_time),
+                Force::TimeVaryingField(field, scale) => self.apply_field(velocity_field, field, scale(time), delta_time)
+            }
+        }
+        if has_acceleration {
+            self.apply_constant(velocity_field, acceleration, delta_time);
+        }
+    }
+
+    fn apply_constant(&self, velocity_field: &mut gpu::Texture2D, acceleration: (f32, f32), delta_time: f32) {
+        const VELOCITY_FIELD_LOCATION : usize = 0;
+        const ACCELERATION_LOCATION   : usize = 1;
+        const DELTA_TIME_LOCATION     : usize = 2;
+        let dimensions = velocity_field.dimensions();
+        let dimensions = (dimensions.0, dimensions.1, 1);
+        self.constant_program.bind_image_2d(velocity_field, VELOCITY_FIELD_LOCATION);
+        self.constant_program.bind_vec2(acceleration, ACCELERATION_LOCATION);
+        self.constant_program.bind_f32(delta_time, DELTA_TIME_LOCATION);
+        self.constant_program.compute(dimensions);
+        //FIXME: How to expose it on the GPU API?
+        // Ref: https://www.khronos.org/registry/OpenGL-Refpages/gl4/html/glMemoryBarrier.xhtml
+        unsafe {
+            gl::MemoryBarrier(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+        }
+    }
+
+    fn apply_field(&self, velocity_field: &mut gpu::Texture2D, force_field: &gpu::Texture2D, scale: f32, delta_time: f32) {
+        const VELOCITY_FIELD_LOCATION : usize = 0;
+        const FORCE_FIELD_LOCATION    : usize = 1;
+        const SCALE_LOCATION          : usize = 2;
+        const DELTA_TIME_LOCATION     : usize = 3;
+        let dimensions = velocity_field.dimensions();
+        let dimensions = (dimensions.0, dimensions.1, 1);
+        self.field_program.bind_image_2d(velocity_field, VELOCITY_FIELD_LOCATION);
+        self.field_program.bind_image_2d(force_field, FORCE_FIELD_LOCATION);
+        self.field_program.bind_f32(scale, SCALE_LOCATION);
+        self.field_program.bind_f32(delta_time, DELTA_TIME_LOCATION);
+        self.field_program.compute(dimensions);
+        //FIXME: How to expose it on the GPU API?
+        // Ref: https://www.khronos.org/registry/OpenGL-Refpages/gl4/html/glMemoryBarrier.xhtml
+        unsafe {
+            gl::MemoryBarrier(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::context::Context;
+    use crate::field::Field;
+    use crate::simulator::force_applier::{ForceApplier, Force};
+
+    fn initialize(dimensions: (usize, usize)) -> (Context, ForceApplier) {
+        let context = Context::new(dimensions);
+        let force_applier = ForceApplier::new(&context);
+        (context, force_applier)
+    }
+
+    fn initialize_vector_field(context: &Context, dimensions: (usize, usize), data: &[f32]) -> gpu::Texture2D {
+        let field = Field::from_data(context, dimensions, 2, data).field;
+        assert_eq!(field.data() as Vec<f32>, data);
+        field
+    }
+
+    #[test]
+    fn constant_and_field_forces() {
+        let dimensions = (2, 1);
+        let (context, force_applier) = initialize(dimensions);
+
+        let mut velocity_field = initialize_vector_field(&context, dimensions, &[0.0, 0.0, /**/ 1.0, 1.0]);
+        let forces = vec![
+            Force::Constant((0.0, -10.0)),
+            Force::TimeVarying(Box::new(|time| (time, 0.0))),
+            Force::field_from_fn(&context, dimensions, |x, _| (x as f32 * 4.0, 0.0))
+        ];
+        force_applier.apply(&mut velocity_field, &forces, 2.0, 0.5);
+        assert_eq!(velocity_field.data() as Vec<f32>, vec![1.0, -5.0, /**/ 4.0, -4.0]);
+    }
+}
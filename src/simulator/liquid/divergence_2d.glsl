@@ -0,0 +1,27 @@
+#version 450
+
+layout(rg32f, location = 0) uniform image2D velocityField;
+layout(r32f, location = 1) uniform image2D levelSetField;
+layout(r32f, location = 2) uniform image2D divergenceField;
+layout(r32f, location = 3) uniform image2D pressureField;
+
+layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+// The domain is surrounded by solid walls, so there is no velocity outside of it.
+vec2 velocityAt(ivec2 coordinate) {
+    ivec2 size = imageSize(velocityField);
+    if (any(lessThan(coordinate, ivec2(0))) || any(greaterThanEqual(coordinate, size))) return vec2(0.0);
+    return imageLoad(velocityField, coordinate).xy;
+}
+
+void main() {
+    ivec2 coordinate = ivec2(gl_GlobalInvocationID.xy);
+    imageStore(pressureField, coordinate, vec4(0.0));
+
+    float divergence = 0.0;
+    if (imageLoad(levelSetField, coordinate).x <= 0.0) {
+        divergence = 0.5 * (velocityAt(coordinate + ivec2(1, 0)).x - velocityAt(coordinate - ivec2(1, 0)).x
+                          + velocityAt(coordinate + ivec2(0, 1)).y - velocityAt(coordinate - ivec2(0, 1)).y);
+    }
+    imageStore(divergenceField, coordinate, vec4(divergence));
+}
@@ -0,0 +1,43 @@
+#version 450
+
+// Extends the valid velocities by one cell, averaging the valid neighbors of the invalid cells.
+layout(rg32f, location = 0) uniform image2D outputVelocityField;
+layout(rg32f, location = 1) uniform image2D velocityField;
+layout(r32f, location = 2) uniform image2D outputValidField;
+layout(r32f, location = 3) uniform image2D validField;
+
+layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+#define NUMBER_OF_NEIGHBORS 4
+const ivec2 neighborsOffsets[NUMBER_OF_NEIGHBORS] = {
+    ivec2(-1,  0),
+    ivec2( 1,  0),
+    ivec2( 0,  1),
+    ivec2( 0, -1)
+};
+
+void main() {
+    ivec2 coordinate = ivec2(gl_GlobalInvocationID.xy);
+    ivec2 size = imageSize(velocityField);
+    vec4 velocity = imageLoad(velocityField, coordinate);
+    float valid = imageLoad(validField, coordinate).x;
+
+    if (valid < 0.5) {
+        vec4 sum = vec4(0.0);
+        float count = 0.0;
+        for (int i = 0; i < NUMBER_OF_NEIGHBORS; i++) {
+            ivec2 neighborCoordinate = coordinate + neighborsOffsets[i];
+            if (any(lessThan(neighborCoordinate, ivec2(0))) || any(greaterThanEqual(neighborCoordinate, size))) continue;
+            if (imageLoad(validField, neighborCoordinate).x < 0.5) continue;
+            sum += imageLoad(velocityField, neighborCoordinate);
+            count += 1.0;
+        }
+        if (count > 0.0) {
+            velocity = sum / count;
+            valid = 1.0;
+        }
+    }
+
+    imageStore(outputVelocityField, coordinate, velocity);
+    imageStore(outputValidField, coordinate, vec4(valid));
+}
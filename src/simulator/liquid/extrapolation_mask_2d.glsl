@@ -0,0 +1,13 @@
+#version 450
+
+layout(r32f, location = 0) uniform image2D validField;
+layout(r32f, location = 1) uniform image2D levelSetField;
+layout(r32f, location = 2) uniform image2D obstacleField;
+
+layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+void main() {
+    ivec2 coordinate = ivec2(gl_GlobalInvocationID.xy);
+    bool isLiquid = imageLoad(levelSetField, coordinate).x <= 0.0 && imageLoad(obstacleField, coordinate).x <= 0.5;
+    imageStore(validField, coordinate, vec4(isLiquid ? 1.0 : 0.0));
+}
@@ -0,0 +1,37 @@
+#version 450
+
+layout(rg32f, location = 0) uniform image2D velocityField;
+layout(r32f, location = 1) uniform image2D pressureField;
+layout(r32f, location = 2) uniform image2D levelSetField;
+layout(r32f, location = 3) uniform image2D obstacleField;
+
+layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+bool isSolid(ivec2 coordinate) {
+    ivec2 size = imageSize(pressureField);
+    if (any(lessThan(coordinate, ivec2(0))) || any(greaterThanEqual(coordinate, size))) return true;
+    return imageLoad(obstacleField, coordinate).x > 0.5;
+}
+
+float pressureAt(ivec2 coordinate, float centerPressure) {
+    if (isSolid(coordinate)) return centerPressure;
+    if (imageLoad(levelSetField, coordinate).x > 0.0) return 0.0;
+    return imageLoad(pressureField, coordinate).x;
+}
+
+void main() {
+    ivec2 coordinate = ivec2(gl_GlobalInvocationID.xy);
+    if (imageLoad(levelSetField, coordinate).x > 0.0) return; // The air velocity is extrapolated afterwards.
+    if (isSolid(coordinate)) {
+        imageStore(velocityField, coordinate, vec4(0.0));
+        return;
+    }
+
+    float pressure = imageLoad(pressureField, coordinate).x;
+    vec2 gradient = 0.5 * vec2(
+        pressureAt(coordinate + ivec2(1, 0), pressure) - pressureAt(coordinate - ivec2(1, 0), pressure),
+        pressureAt(coordinate + ivec2(0, 1), pressure) - pressureAt(coordinate - ivec2(0, 1), pressure)
+    );
+    vec2 velocity = imageLoad(velocityField, coordinate).xy - gradient;
+    imageStore(velocityField, coordinate, vec4(velocity, 0.0, 0.0));
+}
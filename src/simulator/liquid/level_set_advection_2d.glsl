@@ -0,0 +1,33 @@
+#version 450
+
+layout(r32f, location = 0) writeonly uniform image2D field;
+layout(r32f, location = 1) uniform image2D previousField;
+layout(rg32f, location = 2) uniform image2D velocityField;
+layout(location = 3) uniform float deltaTime;
+
+layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+// Unlike the scalar advection, the level set is clamped on the borders, so the liquid doesn't wrap around the domain.
+vec4 clampLoad(ivec2 coordinate) {
+    coordinate = clamp(coordinate, ivec2(0), imageSize(previousField) - ivec2(1));
+    return imageLoad(previousField, coordinate);
+}
+
+vec4 bilinearLoad(vec2 coordinate) {
+    vec2 interpolation = fract(coordinate);
+    ivec2 leftBottom   = ivec2(floor(coordinate));
+    ivec2 rightTop     = leftBottom + ivec2(1, 1);
+    ivec2 rightBottom  = ivec2(rightTop.x, leftBottom.y);
+    ivec2 leftTop      = ivec2(leftBottom.x, rightTop.y);
+
+    vec4 bottomValue = mix(clampLoad(leftBottom), clampLoad(rightBottom), interpolation.x);
+    vec4 topValue    = mix(clampLoad(leftTop), clampLoad(rightTop), interpolation.x);
+    return mix(bottomValue, topValue, interpolation.y);
+}
+
+void main() {
+    ivec2 coordinate = ivec2(gl_GlobalInvocationID.xy);
+    vec2 velocity = imageLoad(velocityField, coordinate).xy;
+    vec2 previousCoordinate = vec2(coordinate) - velocity * deltaTime;
+    imageStore(field, coordinate, bilinearLoad(previousCoordinate));
+}
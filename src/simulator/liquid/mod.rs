@@ -0,0 +1,316 @@
+use crate::context::Context;
+use crate::fluid::Fluid;
+
+/// Free surface liquid step. The liquid is where `fluid.level_set_field` is negative.
+pub struct LiquidSolver {
+    level_set_advection_program: gpu::ComputeProgram,
+    divergence_program: gpu::ComputeProgram,
+    pressure_program: gpu::ComputeProgram,
+    gradient_program: gpu::ComputeProgram,
+    extrapolation_mask_program: gpu::ComputeProgram,
+    extrapolation_program: gpu::ComputeProgram,
+    redistance_program: gpu::ComputeProgram,
+    divergence_field: gpu::Texture2D,
+    pressure_field: gpu::Texture2D,
+    temporary_pressure: gpu::Texture2D,
+    valid_field: gpu::Texture2D,
+    temporary_valid: gpu::Texture2D,
+    temporary_velocity: gpu::Texture2D,
+    temporary_level_set: gpu::Texture2D,
+    initial_level_set: gpu::Texture2D,
+    projected: bool,
+    steps: usize,
+    /// Redistance the level set every `redistance_interval` steps. Zero disables it.
+    pub redistance_interval: usize,
+    pub redistance_iterations: usize,
+    pub pressure_iterations: usize,
+    /// How many cells the liquid velocity is extended into the air.
+    pub extrapolation_iterations: usize
+}
+
+fn program(context: &Context, source: &str) -> gpu::ComputeProgram {
+    let compute_shader = gpu::ComputeShader::new(&context.context, source).expect("Couldn't create ComputeShader.");
+    gpu::ComputeProgram::new(&context.context, &compute_shader).expect("Couldn't create ComputeProgram.")
+}
+
+fn compute(program: &gpu::ComputeProgram, dimensions: (usize, usize)) {
+    program.compute((dimensions.0, dimensions.1, 1));
+    //FIXME: How to expose it on the GPU API?
+    // Ref: https://www.khronos.org/registry/OpenGL-Refpages/gl4/html/glMemoryBarrier.xhtml
+    unsafe {
+        gl::MemoryBarrier(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+    }
+}
+
+impl LiquidSolver {
+    pub fn new(context: &Context, dimensions: (usize, usize)) -> Self {
+        let level_set_advection_program = program(context, include_str!("level_set_advection_2d.glsl"));
+        let divergence_program = program(context, include_str!("divergence_2d.glsl"));
+        let pressure_program = program(context, include_str!("pressure_2d.glsl"));
+        let gradient_program = program(context, include_str!("gradient_2d.glsl"));
+        let extrapolation_mask_program = program(context, include_str!("extrapolation_mask_2d.glsl"));
+        let extrapolation_program = program(context, include_str!("extrapolation_2d.glsl"));
+        let redistance_program = program(context, include_str!("redistance_2d.glsl"));
+
+        let scalar_format = gpu::TextureFormat::new(gpu::ColorFormat::R, gpu::Type::F32);
+        let vector_format = gpu::TextureFormat::new(gpu::ColorFormat::RG, gpu::Type::F32);
+        let divergence_field = gpu::Texture2D::allocate(&context.context, dimensions, &scalar_format);
+        let pressure_field = gpu::Texture2D::allocate(&context.context, dimensions, &scalar_format);
+        let temporary_pressure = gpu::Texture2D::allocate(&context.context, dimensions, &scalar_format);
+        let valid_field = gpu::Texture2D::allocate(&context.context, dimensions, &scalar_format);
+        let temporary_valid = gpu::Texture2D::allocate(&context.context, dimensions, &scalar_format);
+        let temporary_velocity = gpu::Texture2D::allocate(&context.context, dimensions, &vector_format);
+        let temporary_level_set = gpu::Texture2D::allocate(&context.context, dimensions, &scalar_format);
+        let initial_level_set = gpu::Texture2D::allocate(&context.context, dimensions, &scalar_format);
+
+        let projected = false;
+        let steps = 0;
+        let redistance_interval = 5;
+        let redistance_iterations = 10;
+        let pressure_iterations = 60;
+        let extrapolation_iterations = 8;
+        Self {
+            level_set_advection_program, divergence_program, pressure_program, gradient_program, extrapolation_mask_program, extrapolation_program, redistance_program,
+            divergence_field, pressure_field, temporary_pressure, valid_field, temporary_valid, temporary_velocity, temporary_level_set, initial_level_set,
+            projected, steps, redistance_interval, redistance_iterations, pressure_iterations, extrapolation_iterations
+        }
+    }
+
+    /// The pressure of the last projection, zero in the air. None until the liquid was projected.
+    pub fn pressure_field(&self) -> Option<&gpu::Texture2D> {
+        if self.projected { Some(&self.pressure_field) } else { None }
+    }
+
+    /// Projects the liquid velocity, extends it into the air and moves the surface with it.
+    pub fn step(&mut self, fluid: &mut Fluid, delta_time: f32) {
+        let level_set_field = match &mut fluid.level_set_field {
+            Some(level_set_field) => level_set_field,
+            None => return
+        };
+        self.project(&mut fluid.velocity_field, level_set_field, &fluid.obstacle_field);
+        self.extrapolate(&mut fluid.velocity_field, level_set_field, &fluid.obstacle_field);
+        self.advect(level_set_field, &fluid.velocity_field, delta_time);
+        self.steps += 1;
+        if self.redistance_interval > 0 && self.steps % self.redistance_interval == 0 {
+            self.redistance(level_set_field);
+        }
+    }
+
+    fn project(&mut self, velocity_field: &mut gpu::Texture2D, level_set_field: &gpu::Texture2D, obstacle_field: &gpu::Texture2D) {
+        const VELOCITY_FIELD_LOCATION   : usize = 0;
+        const LEVEL_SET_FIELD_LOCATION  : usize = 1;
+        const DIVERGENCE_FIELD_LOCATION : usize = 2;
+        const PRESSURE_FIELD_LOCATION   : usize = 3;
+        let dimensions = velocity_field.dimensions();
+        self.divergence_program.bind_image_2d(velocity_field, VELOCITY_FIELD_LOCATION);
+        self.divergence_program.bind_image_2d(level_set_field, LEVEL_SET_FIELD_LOCATION);
+        self.divergence_program.bind_image_2d(&self.divergence_field, DIVERGENCE_FIELD_LOCATION);
+        self.divergence_program.bind_image_2d(&self.pressure_field, PRESSURE_FIELD_LOCATION);
+        compute(&self.divergence_program, dimensions);
+
+        const OUTPUT_FIELD_LOCATION              : usize = 0;
+        const INPUT_PRESSURE_FIELD_LOCATION      : usize = 1;
+        const INPUT_DIVERGENCE_FIELD_LOCATION    : usize = 2;
+        const PRESSURE_LEVEL_SET_FIELD_LOCATION  : usize = 3;
+        const PRESSURE_OBSTACLE_FIELD_LOCATION   : usize = 4;
+        self.pressure_program.bind_image_2d(&self.divergence_field, INPUT_DIVERGENCE_FIELD_LOCATION);
+        self.pressure_program.bind_image_2d(level_set_field, PRESSURE_LEVEL_SET_FIELD_LOCATION);
+        self.pressure_program.bind_image_2d(obstacle_field, PRESSURE_OBSTACLE_FIELD_LOCATION);
+        for _ in 0 .. self.pressure_iterations {
+            self.pressure_program.bind_image_2d(&self.temporary_pressure, OUTPUT_FIELD_LOCATION);
+            self.pressure_program.bind_image_2d(&self.pressure_field, INPUT_PRESSURE_FIELD_LOCATION);
+            compute(&self.pressure_program, dimensions);
+            std::mem::swap(&mut self.temporary_pressure, &mut self.pressure_field);
+        }
+
+        const GRADIENT_PRESSURE_FIELD_LOCATION  : usize = 1;
+        const GRADIENT_LEVEL_SET_FIELD_LOCATION : usize = 2;
+        const GRADIENT_OBSTACLE_FIELD_LOCATION  : usize = 3;
+        self.gradient_program.bind_image_2d(velocity_field, VELOCITY_FIELD_LOCATION);
+        self.gradient_program.bind_image_2d(&self.pressure_field, GRADIENT_PRESSURE_FIELD_LOCATION);
+        self.gradient_program.bind_image_2d(level_set_field, GRADIENT_LEVEL_SET_FIELD_LOCATION);
+        self.gradient_program.bind_image_2d(obstacle_field, GRADIENT_OBSTACLE_FIELD_LOCATION);
+        compute(&self.gradient_program, dimensions);
+        self.projected = true;
+    }
+
+    fn extrapolate(&mut self, velocity_field: &mut gpu::Texture2D, level_set_field: &gpu::Texture2D, obstacle_field: &gpu::Texture2D) {
+        const VALID_FIELD_LOCATION     : usize = 0;
+        const LEVEL_SET_FIELD_LOCATION : usize = 1;
+        const OBSTACLE_FIELD_LOCATION  : usize = 2;
+        let dimensions = velocity_field.dimensions();
+        self.extrapolation_mask_program.bind_image_2d(&self.valid_field, VALID_FIELD_LOCATION);
+        self.extrapolation_mask_program.bind_image_2d(level_set_field, LEVEL_SET_FIELD_LOCATION);
+        self.extrapolation_mask_program.bind_image_2d(obstacle_field, OBSTACLE_FIELD_LOCATION);
+        compute(&self.extrapolation_mask_program, dimensions);
+
+        const OUTPUT_VELOCITY_FIELD_LOCATION : usize = 0;
+        const VELOCITY_FIELD_LOCATION        : usize = 1;
+        const OUTPUT_VALID_FIELD_LOCATION    : usize = 2;
+        const INPUT_VALID_FIELD_LOCATION     : usize = 3;
+        for _ in 0 .. self.extrapolation_iterations {
+            self.extrapolation_program.bind_image_2d(&self.temporary_velocity, OUTPUT_VELOCITY_FIELD_LOCATION);
+            self.extrapolation_program.bind_image_2d(velocity_field, VELOCITY_FIELD_LOCATION);
+            self.extrapolation_program.bind_image_2d(&self.temporary_valid, OUTPUT_VALID_FIELD_LOCATION);
+            self.extrapolation_program.bind_image_2d(&self.valid_field, INPUT_VALID_FIELD_LOCATION);
+            compute(&self.extrapolation_program, dimensions);
+            std::mem::swap(&mut self.temporary_velocity, velocity_field);
+            std::mem::swap(&mut self.temporary_valid, &mut self.valid_field);
+        }
+    }
+
+    fn advect(&mut self, level_set_field: &mut gpu::Texture2D, velocity_field: &gpu::Texture2D, delta_time: f32) {
+        const FIELD_LOCATION          : usize = 0;
+        const PREVIOUS_FIELD_LOCATION : usize = 1;
+        const VELOCITY_FIELD_LOCATION : usize = 2;
+        const DELTA_TIME_LOCATION     : usize = 3;
+        let dimensions = level_set_field.dimensions();
+        self.level_set_advection_program.bind_image_2d(&self.temporary_level_set, FIELD_LOCATION);
+        self.level_set_advection_program.bind_image_2d(level_set_field, PREVIOUS_FIELD_LOCATION);
+        self.level_set_advection_program.bind_image_2d(velocity_field, VELOCITY_FIELD_LOCATION);
+        self.level_set_advection_program.bind_f32(delta_time, DELTA_TIME_LOCATION);
+        compute(&self.level_set_advection_program, dimensions);
+        std::mem::swap(&mut self.temporary_level_set, level_set_field);
+    }
+
+    /// Restores the signed distance property of the level set without moving its zero contour.
+    pub fn redistance(&mut self, level_set_field: &mut gpu::Texture2D) {
+        const OUTPUT_FIELD_LOCATION            : usize = 0;
+        const LEVEL_SET_FIELD_LOCATION         : usize = 1;
+        const INITIAL_LEVEL_SET_FIELD_LOCATION : usize = 2;
+        const PSEUDO_TIME_STEP_LOCATION        : usize = 3;
+        if self.redistance_iterations == 0 {
+            return;
+        }
+        let dimensions = level_set_field.dimensions();
+        // The current level set becomes the initial one, its old storage receives the first iteration.
+        std::mem::swap(level_set_field, &mut self.initial_level_set);
+        self.redistance_program.bind_image_2d(&self.initial_level_set, INITIAL_LEVEL_SET_FIELD_LOCATION);
+        self.redistance_program.bind_f32(0.5, PSEUDO_TIME_STEP_LOCATION);
+        self.redistance_program.bind_image_2d(level_set_field, OUTPUT_FIELD_LOCATION);
+        self.redistance_program.bind_image_2d(&self.initial_level_set, LEVEL_SET_FIELD_LOCATION);
+        compute(&self.redistance_program, dimensions);
+        for _ in 1 .. self.redistance_iterations {
+            self.redistance_program.bind_image_2d(&self.temporary_level_set, OUTPUT_FIELD_LOCATION);
+            self.redistance_program.bind_image_2d(level_set_field, LEVEL_SET_FIELD_LOCATION);
+            compute(&self.redistance_program, dimensions);
+            std::mem::swap(&mut self.temporary_level_set, level_set_field);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::context::Context;
+    use crate::field::Field;
+    use crate::simulator::liquid::LiquidSolver;
+
+    fn initialize_field(context: &Context, dimensions: (usize, usize), data: &[f32], components: usize) -> gpu::Texture2D {
+        let field = Field::from_data(context, dimensions, components, data).field;
+        assert_eq!(field.data() as Vec<f32>, data);
+        field
+    }
+
+    fn assert_near(data: &[f32], expected_data: &[f32], tolerance: f32) {
+        for (value, expected) in data.iter().zip(expected_data) {
+            assert!((value - expected).abs() < tolerance, "{:?} instead of {:?}", data, expected_data);
+        }
+    }
+
+    #[test]
+    fn level_set_advection() {
+        let dimensions = (5, 5);
+        let context = Context::new(dimensions);
+        let mut solver = LiquidSolver::new(&context, dimensions);
+
+        // The surface is between the second and the third columns and moves one cell to the right.
+        let level_set_data: Vec<f32> = (0 .. 25).map(|index| (index % 5) as f32 - 2.0).collect();
+        let mut level_set_field = initialize_field(&context, dimensions, &level_set_data, 1);
+        let velocity_field = initialize_field(&context, dimensions, &[2.0, 0.0].repeat(25), 2);
+        solver.advect(&mut level_set_field, &velocity_field, 0.5);
+        // The border is clamped, so the liquid doesn't wrap around.
+        assert_eq!(level_set_field.data() as Vec<f32>, [-2.0, -2.0, -1.0, 0.0, 1.0].repeat(5));
+    }
+
+    #[test]
+    fn air_pressure_is_zero() {
+        let dimensions = (5, 5);
+        let context = Context::new(dimensions);
+        let mut solver = LiquidSolver::new(&context, dimensions);
+
+        // The liquid fills the three bottom rows.
+        let level_set_data: Vec<f32> = (0 .. 25).map(|index| (index / 5) as f32 - 2.5).collect();
+        let level_set_field = initialize_field(&context, dimensions, &level_set_data, 1);
+        let obstacle_field = initialize_field(&context, dimensions, &[0.0; 25], 1);
+        let mut velocity_data = [0.0, 0.0].repeat(25);
+        velocity_data[(5 + 2) * 2] = 1.0;
+        // An air velocity along the surface doesn't change the liquid divergence.
+        for value in velocity_data[30 ..].iter_mut().step_by(2) {
+            *value = 3.0;
+        }
+        let mut velocity_field = initialize_field(&context, dimensions, &velocity_data, 2);
+        solver.project(&mut velocity_field, &level_set_field, &obstacle_field);
+
+        // The air is a p = 0 boundary, so the pressure fades towards the surface.
+        let expected_data = vec![
+            -0.1157, -0.1050, 0.0, 0.1050, 0.1157,
+            -0.1263, -0.1993, 0.0, 0.1993, 0.1263,
+            -0.0641, -0.0658, 0.0, 0.0658, 0.0641,
+                0.0,     0.0, 0.0,    0.0,    0.0,
+                0.0,     0.0, 0.0,    0.0,    0.0
+        ];
+        let pressure_data: Vec<f32> = solver.pressure_field().expect("The liquid was projected.").data();
+        assert_near(&pressure_data, &expected_data, 1e-3);
+        assert!(pressure_data[15 ..].iter().all(|pressure| *pressure == 0.0));
+
+        // The air velocity is left to the extrapolation.
+        let data: Vec<f32> = velocity_field.data();
+        assert_eq!(data[30 ..], velocity_data[30 ..]);
+    }
+
+    #[test]
+    fn extrapolation_into_air() {
+        let dimensions = (5, 5);
+        let context = Context::new(dimensions);
+        let mut solver = LiquidSolver::new(&context, dimensions);
+
+        // The liquid fills the two left columns and moves as fast as its row index.
+        let level_set_data: Vec<f32> = (0 .. 25).map(|index| (index % 5) as f32 - 1.5).collect();
+        let level_set_field = initialize_field(&context, dimensions, &level_set_data, 1);
+        let obstacle_field = initialize_field(&context, dimensions, &[0.0; 25], 1);
+        let velocity_data: Vec<f32> = (0 .. 25).flat_map(|index| if index % 5 < 2 { vec![(index / 5) as f32, 0.0] } else { vec![9.0, 9.0] }).collect();
+        let mut velocity_field = initialize_field(&context, dimensions, &velocity_data, 2);
+
+        // Every iteration extends the liquid velocity by one cell.
+        solver.extrapolation_iterations = 1;
+        solver.extrapolate(&mut velocity_field, &level_set_field, &obstacle_field);
+        let expected_data: Vec<f32> = (0 .. 25).flat_map(|index| if index % 5 < 3 { vec![(index / 5) as f32, 0.0] } else { vec![9.0, 9.0] }).collect();
+        assert_eq!(velocity_field.data() as Vec<f32>, expected_data);
+
+        solver.extrapolation_iterations = 8;
+        solver.extrapolate(&mut velocity_field, &level_set_field, &obstacle_field);
+        let expected_data: Vec<f32> = (0 .. 25).flat_map(|index| vec![(index / 5) as f32, 0.0]).collect();
+        assert_eq!(velocity_field.data() as Vec<f32>, expected_data);
+    }
+
+    #[test]
+    fn redistance_restores_the_distance() {
+        let dimensions = (5, 5);
+        let context = Context::new(dimensions);
+        let mut solver = LiquidSolver::new(&context, dimensions);
+
+        // Three times too steep, with the surface halfway between the third and the fourth columns.
+        let level_set_data: Vec<f32> = (0 .. 25).map(|index| 3.0 * ((index % 5) as f32 - 2.5)).collect();
+        let mut level_set_field = initialize_field(&context, dimensions, &level_set_data, 1);
+        solver.redistance_iterations = 20;
+        solver.redistance(&mut level_set_field);
+
+        let data: Vec<f32> = level_set_field.data();
+        assert_near(&data, &[-2.5, -1.5, -0.5, 0.5, 1.5].repeat(5), 1e-2);
+        // |grad(phi)| = 1 and the surface doesn't move.
+        for row in data.chunks(5) {
+            assert!(row.windows(2).all(|pair| (pair[1] - pair[0] - 1.0).abs() < 1e-2));
+            assert!(row[2] < 0.0 && row[3] > 0.0);
+        }
+    }
+}
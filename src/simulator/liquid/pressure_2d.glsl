@@ -0,0 +1,45 @@
+#version 450
+
+// One Jacobi iteration of laplacian(p) = div on the liquid cells.
+// The air cells are a Dirichlet p = 0 boundary, the walls and the obstacles are a Neumann dp/dn = 0 boundary.
+layout(r32f, location = 0) uniform image2D outputField;
+layout(r32f, location = 1) uniform image2D pressureField;
+layout(r32f, location = 2) uniform image2D divergenceField;
+layout(r32f, location = 3) uniform image2D levelSetField;
+layout(r32f, location = 4) uniform image2D obstacleField;
+
+layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+#define NUMBER_OF_NEIGHBORS 4
+const ivec2 neighborsOffsets[NUMBER_OF_NEIGHBORS] = {
+    ivec2(-1,  0),
+    ivec2( 1,  0),
+    ivec2( 0,  1),
+    ivec2( 0, -1)
+};
+
+bool isSolid(ivec2 coordinate) {
+    ivec2 size = imageSize(pressureField);
+    if (any(lessThan(coordinate, ivec2(0))) || any(greaterThanEqual(coordinate, size))) return true;
+    return imageLoad(obstacleField, coordinate).x > 0.5;
+}
+
+void main() {
+    ivec2 coordinate = ivec2(gl_GlobalInvocationID.xy);
+    if (imageLoad(levelSetField, coordinate).x > 0.0 || isSolid(coordinate)) {
+        imageStore(outputField, coordinate, vec4(0.0));
+        return;
+    }
+
+    float sum = 0.0;
+    float count = 0.0;
+    for (int i = 0; i < NUMBER_OF_NEIGHBORS; i++) {
+        ivec2 neighborCoordinate = coordinate + neighborsOffsets[i];
+        if (isSolid(neighborCoordinate)) continue;
+        count += 1.0;
+        if (imageLoad(levelSetField, neighborCoordinate).x <= 0.0) sum += imageLoad(pressureField, neighborCoordinate).x;
+    }
+    float divergence = imageLoad(divergenceField, coordinate).x;
+    float pressure = count > 0.0 ? (sum - divergence) / count : 0.0;
+    imageStore(outputField, coordinate, vec4(pressure));
+}
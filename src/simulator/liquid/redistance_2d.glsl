@@ -0,0 +1,37 @@
+#version 450
+
+// One pseudo time step of phi_t + S(phi0) (|grad(phi)| - 1) = 0 with Godunov's upwind scheme (Sussman et al. 1994).
+layout(r32f, location = 0) uniform image2D outputField;
+layout(r32f, location = 1) uniform image2D levelSetField;
+layout(r32f, location = 2) uniform image2D initialLevelSetField;
+layout(location = 3) uniform float pseudoTimeStep;
+
+layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+float clampLoad(ivec2 coordinate) {
+    coordinate = clamp(coordinate, ivec2(0), imageSize(levelSetField) - ivec2(1));
+    return imageLoad(levelSetField, coordinate).x;
+}
+
+void main() {
+    ivec2 coordinate = ivec2(gl_GlobalInvocationID.xy);
+    float initialPhi = imageLoad(initialLevelSetField, coordinate).x;
+    float phi = clampLoad(coordinate);
+
+    float backwardX = phi - clampLoad(coordinate - ivec2(1, 0));
+    float forwardX  = clampLoad(coordinate + ivec2(1, 0)) - phi;
+    float backwardY = phi - clampLoad(coordinate - ivec2(0, 1));
+    float forwardY  = clampLoad(coordinate + ivec2(0, 1)) - phi;
+
+    float gradientSquared;
+    if (initialPhi > 0.0) {
+        gradientSquared = max(pow(max(backwardX, 0.0), 2.0), pow(min(forwardX, 0.0), 2.0))
+                        + max(pow(max(backwardY, 0.0), 2.0), pow(min(forwardY, 0.0), 2.0));
+    } else {
+        gradientSquared = max(pow(min(backwardX, 0.0), 2.0), pow(max(forwardX, 0.0), 2.0))
+                        + max(pow(min(backwardY, 0.0), 2.0), pow(max(forwardY, 0.0), 2.0));
+    }
+    float sign = initialPhi / sqrt(initialPhi * initialPhi + 1.0);
+    phi -= pseudoTimeStep * sign * (sqrt(gradientSquared) - 1.0);
+    imageStore(outputField, coordinate, vec4(phi));
+}
@@ -6,17 +6,58 @@ mod advector;
 mod projector;
 mod linear_solver;
 mod boundary_limiter;
+mod obstacle_limiter;
+mod force_applier;
+mod dissipator;
+mod viscosity_updater;
+mod liquid;
+mod particle_solver;
+mod emitter;
+mod reducer;
+mod cpu;
+#[cfg(test)]
+mod validation;
 
 use diffuser::Diffuser;
 use advector::Advector;
 use projector::Projector;
 use linear_solver::LinearSolver;
 use boundary_limiter::BoundaryLimiter;
+use obstacle_limiter::ObstacleLimiter;
+use force_applier::ForceApplier;
+use dissipator::Dissipator;
+use viscosity_updater::ViscosityUpdater;
+use liquid::LiquidSolver;
+use particle_solver::ParticleSolver;
+use emitter::EmitterApplier;
+
+pub use force_applier::Force;
+pub use dissipator::sponge_mask;
+pub use particle_solver::{TransferScheme, ParticleBackend, Particle};
+pub use emitter::{Emitter, EmitterKind, Region};
+pub use reducer::{Reducer, Reduction, StepStats};
+pub use cpu::{CpuFluid, CpuSimulator};
 
 pub struct Simulator {
     diffuser: Diffuser,
     advector: Advector,
-    projector: Projector
+    projector: Projector,
+    obstacle_limiter: ObstacleLimiter,
+    force_applier: ForceApplier,
+    emitter_applier: EmitterApplier,
+    dissipator: Dissipator,
+    viscosity_updater: ViscosityUpdater,
+    pub liquid_solver: LiquidSolver,
+    pub particle_solver: ParticleSolver,
+    pub reducer: Reducer,
+    pub forces: Vec<Force>,
+    pub emitters: Vec<Emitter>,
+    /// Jacobi iterations of the diffusion and the projection solves.
+    pub iterations: usize,
+    /// Makes `simulate` return the `StepStats`. Their reductions read back from the GPU, so they stall every step.
+    pub collect_stats: bool,
+    pub time: f32,
+    projection_scratch_field: gpu::Texture2D
 }
 
 impl Simulator {
@@ -24,13 +65,92 @@ impl Simulator {
         let diffuser = Diffuser::new(context, dimensions);
         let advector = Advector::new(context);
         let projector = Projector::new(context, dimensions);
-        Self { diffuser, advector, projector }
+        let obstacle_limiter = ObstacleLimiter::new(context);
+        let force_applier = ForceApplier::new(context);
+        let emitter_applier = EmitterApplier::new(context);
+        let dissipator = Dissipator::new(context);
+        let viscosity_updater = ViscosityUpdater::new(context);
+        let liquid_solver = LiquidSolver::new(context, dimensions);
+        let particle_solver = ParticleSolver::new(context, dimensions);
+        let reducer = Reducer::new(context, dimensions);
+        let forces = Vec::new();
+        let emitters = Vec::new();
+        let iterations = 30;
+        let collect_stats = false;
+        let time = 0.0;
+        let format = gpu::TextureFormat::new(gpu::ColorFormat::RG, gpu::Type::F32);
+        let projection_scratch_field = gpu::Texture2D::allocate(&context.context, dimensions, &format);
+        Self { diffuser, advector, projector, obstacle_limiter, force_applier, emitter_applier, dissipator, viscosity_updater, liquid_solver, particle_solver, reducer, forces, emitters, iterations, collect_stats, time, projection_scratch_field }
+    }
+
+    pub fn add_force(&mut self, force: Force) {
+        self.forces.push(force);
+    }
+
+    pub fn add_emitter(&mut self, emitter: Emitter) {
+        self.emitters.push(emitter);
+    }
+
+    /// The pressure of the last projection, from the liquid solver when the fluid has a level set. None before the first projection.
+    pub fn pressure_field(&self, fluid: &Fluid) -> Option<&gpu::Texture2D> {
+        match fluid.level_set_field {
+            Some(_) => self.liquid_solver.pressure_field(),
+            None    => self.projector.pressure_field()
+        }
+    }
+
+    /// Advances `fluid` by `delta_time` and returns the diagnostics of the step if `collect_stats` is set.
+    pub fn simulate(&mut self, fluid: &mut Fluid, delta_time: f32) -> Option<StepStats> {
+        if self.particle_solver.is_active() {
+            self.simulate_particles(fluid, delta_time);
+        } else {
+            self.simulate_grid(fluid, delta_time);
+        }
+        self.time += delta_time;
+        if self.collect_stats {
+            Some(self.stats(fluid, delta_time))
+        } else {
+            None
+        }
+    }
+
+    /// The diagnostics of the current fields of `fluid`, e.g. to check them once in a while instead of on every step.
+    pub fn stats(&self, fluid: &Fluid, delta_time: f32) -> StepStats {
+        self.reducer.stats(fluid, self.time, delta_time)
     }
 
-    pub fn simulate(&mut self, fluid: &mut Fluid, delta_time: f32) {
+    /// FLIP/PIC/APIC step: the particles carry the velocity, the grid is rebuilt from them on every step.
+    fn simulate_particles(&mut self, fluid: &mut Fluid, delta_time: f32) {
+        let iterations = self.iterations;
+        self.particle_solver.transfer_to_grid(fluid);
+        self.force_applier.apply(&mut fluid.velocity_field, &self.forces, self.time, delta_time);
+        self.emitter_applier.apply(&mut fluid.density_field, &mut fluid.temperature_field, &mut fluid.velocity_field, &self.emitters, self.time, delta_time);
+        self.obstacle_limiter.limit(fluid);
+        self.particle_solver.project(&mut self.projector, fluid, iterations);
+        self.particle_solver.transfer_to_particles(fluid, delta_time);
+
         std::mem::swap(&mut fluid.density_field, &mut fluid.previous_density_field);
+        self.advector.advect_scalar(&mut fluid.density_field, &fluid.previous_density_field, &fluid.velocity_field, delta_time);
+        std::mem::swap(&mut fluid.temperature_field, &mut fluid.previous_temperature_field);
+        self.advector.advect_scalar(&mut fluid.temperature_field, &fluid.previous_temperature_field, &fluid.velocity_field, delta_time);
+        self.dissipator.dissipate_scalar(&mut fluid.density_field, fluid.density_dissipation, fluid.damping_mask.as_ref(), delta_time);
+        self.dissipator.dissipate_scalar(&mut fluid.temperature_field, fluid.temperature_dissipation, fluid.damping_mask.as_ref(), delta_time);
+    }
+
+    fn simulate_grid(&mut self, fluid: &mut Fluid, delta_time: f32) {
+        std::mem::swap(&mut fluid.density_field, &mut fluid.previous_density_field);
+        std::mem::swap(&mut fluid.temperature_field, &mut fluid.previous_temperature_field);
         std::mem::swap(&mut fluid.velocity_field, &mut fluid.previous_velocity_field);
-        let iterations = 30;
+        // The external forces are the first step, so the diffusion and the projection see them.
+        self.force_applier.apply(&mut fluid.previous_velocity_field, &self.forces, self.time, delta_time);
+        // Then the emitters, so their dye, temperature and velocity are carried by this step.
+        self.emitter_applier.apply(&mut fluid.previous_density_field, &mut fluid.previous_temperature_field, &mut fluid.previous_velocity_field, &self.emitters, self.time, delta_time);
+        let iterations = self.iterations;
+        if let Some(viscosity_field) = &mut fluid.viscosity_field {
+            self.viscosity_updater.update(&fluid.viscosity_model, viscosity_field, &fluid.previous_velocity_field);
+            self.diffuser.diffuse_variable(viscosity_field, &mut fluid.velocity_field, &fluid.previous_velocity_field, delta_time, iterations);
+            std::mem::swap(&mut fluid.velocity_field, &mut fluid.previous_velocity_field);
+        }
         // self.diffuser.diffuse(fluid.viscosity, true, &mut fluid.previous_velocity_field, &fluid.velocity_field, delta_time, iterations);
         // self.projector.project(&mut fluid.previous_velocity_field, &mut fluid.velocity_field, iterations);
         // self.advector.advect_vector_with_boundaries(true, &mut fluid.velocity_field, &fluid.previous_velocity_field, &fluid.previous_velocity_field, delta_time);
@@ -38,6 +158,16 @@ impl Simulator {
         // self.diffuser.diffuse(fluid.diffusion, false, &mut fluid.previous_density_field, &fluid.density_field, delta_time, iterations);
         //self.advector.advect_vector_with_boundaries(true, &mut fluid.velocity_field, &fluid.previous_velocity_field, &fluid.previous_velocity_field, delta_time);
         self.advector.advect_vector(&mut fluid.velocity_field, &fluid.previous_velocity_field, &fluid.previous_velocity_field, delta_time);
+        // The liquid solver projects with the free surface, otherwise the whole field is fluid.
+        if fluid.level_set_field.is_none() {
+            self.projector.project(&mut fluid.velocity_field, &mut self.projection_scratch_field, iterations);
+        }
+        self.liquid_solver.step(fluid, delta_time);
         self.advector.advect_scalar(&mut fluid.density_field, &fluid.previous_density_field, &fluid.velocity_field, delta_time);
+        self.advector.advect_scalar(&mut fluid.temperature_field, &fluid.previous_temperature_field, &fluid.velocity_field, delta_time);
+        self.dissipator.dissipate_vector(&mut fluid.velocity_field, fluid.velocity_dissipation, fluid.damping_mask.as_ref(), delta_time);
+        self.dissipator.dissipate_scalar(&mut fluid.density_field, fluid.density_dissipation, fluid.damping_mask.as_ref(), delta_time);
+        self.dissipator.dissipate_scalar(&mut fluid.temperature_field, fluid.temperature_dissipation, fluid.damping_mask.as_ref(), delta_time);
+        self.obstacle_limiter.limit(fluid);
     }
 }
\ No newline at end of file
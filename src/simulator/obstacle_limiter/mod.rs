@@ -0,0 +1,31 @@
+use crate::context::Context;
+use crate::fluid::Fluid;
+
+/// Keeps the fluid out of the cells marked on the `obstacle_field`.
+pub struct ObstacleLimiter {
+    compute_program: gpu::ComputeProgram
+}
+
+impl ObstacleLimiter {
+    pub fn new(context: &Context) -> Self {
+        let compute_shader = gpu::ComputeShader::new(&context.context, include_str!("obstacle_2d.glsl")).expect("Couldn't create ComputeShader.");
+        let compute_program = gpu::ComputeProgram::new(&context.context, &compute_shader).expect("Couldn't create ComputeProgram.");
+        Self { compute_program }
+    }
+
+    pub fn limit(&self, fluid: &mut Fluid) {
+        const VELOCITY_FIELD_LOCATION : usize = 0;
+        const DENSITY_FIELD_LOCATION  : usize = 1;
+        const OBSTACLE_FIELD_LOCATION : usize = 2;
+        let dimensions = (fluid.dimensions.0, fluid.dimensions.1, 1);
+        self.compute_program.bind_image_2d(&fluid.velocity_field, VELOCITY_FIELD_LOCATION);
+        self.compute_program.bind_image_2d(&fluid.density_field, DENSITY_FIELD_LOCATION);
+        self.compute_program.bind_image_2d(&fluid.obstacle_field, OBSTACLE_FIELD_LOCATION);
+        self.compute_program.compute(dimensions);
+        //FIXME: How to expose it on the GPU API?
+        // Ref: https://www.khronos.org/registry/OpenGL-Refpages/gl4/html/glMemoryBarrier.xhtml
+        unsafe {
+            gl::MemoryBarrier(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+        }
+    }
+}
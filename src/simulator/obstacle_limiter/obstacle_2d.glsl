@@ -0,0 +1,15 @@
+#version 450
+
+layout(rg32f, location = 0) uniform image2D velocityField;
+layout(r32f, location = 1) uniform image2D densityField;
+layout(r32f, location = 2) uniform image2D obstacleField;
+
+layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+void main() {
+    ivec2 coordinate = ivec2(gl_GlobalInvocationID.xy);
+    if (imageLoad(obstacleField, coordinate).x > 0.5) {
+        imageStore(velocityField, coordinate, vec4(0.0));
+        imageStore(densityField, coordinate, vec4(0.0));
+    }
+}
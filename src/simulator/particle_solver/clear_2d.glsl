@@ -0,0 +1,14 @@
+#version 450
+
+layout(r32i, location = 0) uniform iimage2D momentumXField;
+layout(r32i, location = 1) uniform iimage2D momentumYField;
+layout(r32i, location = 2) uniform iimage2D weightField;
+
+layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+void main() {
+    ivec2 coordinate = ivec2(gl_GlobalInvocationID.xy);
+    imageStore(momentumXField, coordinate, ivec4(0));
+    imageStore(momentumYField, coordinate, ivec4(0));
+    imageStore(weightField, coordinate, ivec4(0));
+}
@@ -0,0 +1,200 @@
+//! CPU versions of the particle-grid transfers, used when the GPU backend isn't available.
+//! The grids are interleaved (x, y) velocities, row by row, like `gpu::Texture2D::data`.
+
+use super::{Particle, TransferScheme};
+
+/// The four grid nodes around `position`, with their bilinear weights and the weight gradients.
+/// The nodes can be outside of the grid, like in the shaders.
+fn nodes(position: (f32, f32)) -> Vec<((isize, isize), (f32, f32), f32, (f32, f32))> {
+    let base = (position.0.floor() as isize, position.1.floor() as isize);
+    let mut nodes = Vec::with_capacity(4);
+    for y in 0 .. 2 {
+        for x in 0 .. 2 {
+            let node = (base.0 + x, base.1 + y);
+            let offset = (node.0 as f32 - position.0, node.1 as f32 - position.1);
+            let weights = (1.0 - offset.0.abs(), 1.0 - offset.1.abs());
+            let gradient = (sign(offset.0) * weights.1, sign(offset.1) * weights.0);
+            nodes.push((node, offset, weights.0 * weights.1, gradient));
+        }
+    }
+    nodes
+}
+
+fn is_inside(node: (isize, isize), dimensions: (usize, usize)) -> bool {
+    node.0 >= 0 && node.1 >= 0 && node.0 < dimensions.0 as isize && node.1 < dimensions.1 as isize
+}
+
+// GLSL's sign, which is zero at zero, unlike f32::signum.
+fn sign(value: f32) -> f32 {
+    if value > 0.0 { 1.0 } else if value < 0.0 { -1.0 } else { 0.0 }
+}
+
+fn velocity_at(grid: &[f32], dimensions: (usize, usize), coordinate: (isize, isize)) -> (f32, f32) {
+    let x = coordinate.0.max(0).min(dimensions.0 as isize - 1) as usize;
+    let y = coordinate.1.max(0).min(dimensions.1 as isize - 1) as usize;
+    let index = (y * dimensions.0 + x) * 2;
+    (grid[index], grid[index + 1])
+}
+
+/// Bilinearly samples the `grid`, clamping on the borders.
+pub fn sample(grid: &[f32], dimensions: (usize, usize), position: (f32, f32)) -> (f32, f32) {
+    let base = (position.0.floor() as isize, position.1.floor() as isize);
+    let interpolation = (position.0 - position.0.floor(), position.1 - position.1.floor());
+    let mix = |a: (f32, f32), b: (f32, f32), t: f32| (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t);
+    let bottom = mix(velocity_at(grid, dimensions, base), velocity_at(grid, dimensions, (base.0 + 1, base.1)), interpolation.0);
+    let top = mix(velocity_at(grid, dimensions, (base.0, base.1 + 1)), velocity_at(grid, dimensions, (base.0 + 1, base.1 + 1)), interpolation.0);
+    mix(bottom, top, interpolation.1)
+}
+
+/// Splats the particles momentum to the grid and normalizes it by the splatted weights.
+pub fn particles_to_grid(particles: &[Particle], dimensions: (usize, usize), scheme: &TransferScheme) -> Vec<f32> {
+    let mut momentum = vec![0.0; dimensions.0 * dimensions.1 * 2];
+    let mut weights = vec![0.0; dimensions.0 * dimensions.1];
+    for particle in particles {
+        let c = particle.affine;
+        for (node, offset, weight, _) in nodes(particle.position) {
+            if !is_inside(node, dimensions) {
+                continue;
+            }
+            let mut velocity = particle.velocity;
+            if let TransferScheme::Apic = scheme {
+                velocity.0 += c[0] * offset.0 + c[1] * offset.1;
+                velocity.1 += c[2] * offset.0 + c[3] * offset.1;
+            }
+            let index = node.1 as usize * dimensions.0 + node.0 as usize;
+            momentum[index * 2]     += weight * velocity.0;
+            momentum[index * 2 + 1] += weight * velocity.1;
+            weights[index]          += weight;
+        }
+    }
+    for (index, weight) in weights.iter().enumerate() {
+        if *weight > 0.0 {
+            momentum[index * 2]     /= weight;
+            momentum[index * 2 + 1] /= weight;
+        }
+    }
+    momentum
+}
+
+/// Updates the particles velocity from the projected `grid` and moves them through it.
+/// `previous_grid` is the grid before the forces and the projection, used by the FLIP update.
+pub fn grid_to_particles(particles: &mut [Particle], grid: &[f32], previous_grid: &[f32], dimensions: (usize, usize), scheme: &TransferScheme, delta_time: f32) {
+    let max_position = (dimensions.0.saturating_sub(2) as f32, dimensions.1.saturating_sub(2) as f32);
+    for particle in particles.iter_mut() {
+        let position = particle.position;
+        let grid_velocity = sample(grid, dimensions, position);
+        match scheme {
+            TransferScheme::Apic => {
+                let mut affine = [0.0; 4];
+                // Like the shader, the nodes outside of the grid take the border velocity.
+                for (node, _, _, gradient) in nodes(position) {
+                    let velocity = velocity_at(grid, dimensions, node);
+                    affine[0] += velocity.0 * gradient.0;
+                    affine[1] += velocity.0 * gradient.1;
+                    affine[2] += velocity.1 * gradient.0;
+                    affine[3] += velocity.1 * gradient.1;
+                }
+                particle.velocity = grid_velocity;
+                particle.affine = affine;
+            },
+            TransferScheme::PicFlip { flip_ratio } => {
+                let previous_velocity = sample(previous_grid, dimensions, position);
+                let flip_velocity = (particle.velocity.0 + grid_velocity.0 - previous_velocity.0, particle.velocity.1 + grid_velocity.1 - previous_velocity.1);
+                particle.velocity = (
+                    grid_velocity.0 + (flip_velocity.0 - grid_velocity.0) * flip_ratio,
+                    grid_velocity.1 + (flip_velocity.1 - grid_velocity.1) * flip_ratio
+                );
+                particle.affine = [0.0; 4];
+            }
+        }
+
+        let midpoint = (position.0 + 0.5 * delta_time * grid_velocity.0, position.1 + 0.5 * delta_time * grid_velocity.1);
+        let velocity = sample(grid, dimensions, midpoint);
+        particle.position = (
+            (position.0 + delta_time * velocity.0).max(1.0).min(max_position.0),
+            (position.1 + delta_time * velocity.1).max(1.0).min(max_position.1)
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn particle(position: (f32, f32), velocity: (f32, f32)) -> Particle {
+        Particle { position, velocity, affine: [0.0; 4] }
+    }
+
+    #[test]
+    fn particle_on_node() {
+        let dimensions = (3, 3);
+        let particles = vec![particle((1.0, 1.0), (2.0, -1.0))];
+        let grid = particles_to_grid(&particles, dimensions, &TransferScheme::PicFlip { flip_ratio: 0.0 });
+        let expected_grid = vec![
+            0.0, 0.0, /**/ 0.0,  0.0, /**/ 0.0, 0.0,
+            0.0, 0.0, /**/ 2.0, -1.0, /**/ 0.0, 0.0,
+            0.0, 0.0, /**/ 0.0,  0.0, /**/ 0.0, 0.0
+        ];
+        assert_eq!(grid, expected_grid);
+    }
+
+    #[test]
+    fn weighted_average() {
+        let dimensions = (2, 1);
+        let particles = vec![particle((0.0, 0.0), (1.0, 0.0)), particle((0.5, 0.0), (4.0, 0.0))];
+        let grid = particles_to_grid(&particles, dimensions, &TransferScheme::PicFlip { flip_ratio: 0.0 });
+        // The first node gets 1.0 * 1.0 + 0.5 * 4.0 over 1.5, the second node only sees the second particle.
+        assert_eq!(grid, vec![2.0, 0.0, /**/ 4.0, 0.0]);
+    }
+
+    #[test]
+    fn pic_and_flip_blend() {
+        let dimensions = (4, 4);
+        let previous_grid = vec![1.0; 32];
+        let grid = vec![3.0; 32];
+        let mut pic = vec![particle((1.5, 1.5), (10.0, 10.0))];
+        let mut flip = pic.clone();
+        grid_to_particles(&mut pic, &grid, &previous_grid, dimensions, &TransferScheme::PicFlip { flip_ratio: 0.0 }, 0.0);
+        grid_to_particles(&mut flip, &grid, &previous_grid, dimensions, &TransferScheme::PicFlip { flip_ratio: 1.0 }, 0.0);
+        assert_eq!(pic[0].velocity, (3.0, 3.0));
+        assert_eq!(flip[0].velocity, (12.0, 12.0));
+    }
+
+    #[test]
+    fn apic_captures_shear() {
+        let dimensions = (4, 4);
+        // u = y, v = 0.
+        let mut grid = vec![0.0; 32];
+        for y in 0 .. 4 {
+            for x in 0 .. 4 {
+                grid[(y * 4 + x) * 2] = y as f32;
+            }
+        }
+        let mut particles = vec![particle((1.5, 1.5), (0.0, 0.0))];
+        grid_to_particles(&mut particles, &grid, &grid, dimensions, &TransferScheme::Apic, 0.0);
+        assert_eq!(particles[0].velocity, (1.5, 0.0));
+        assert_eq!(particles[0].affine, [0.0, 1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn apic_clamps_on_the_border() {
+        let dimensions = (2, 2);
+        // u = x, so the clamped grid is uniform to the right of the last column.
+        let grid = vec![0.0, 0.0, /**/ 1.0, 0.0, /**/ 0.0, 0.0, /**/ 1.0, 0.0];
+        let mut particles = vec![particle((1.5, 0.5), (0.0, 0.0))];
+        grid_to_particles(&mut particles, &grid, &grid, dimensions, &TransferScheme::Apic, 0.0);
+        assert_eq!(particles[0].velocity, (1.0, 0.0));
+        assert_eq!(particles[0].affine, [0.0; 4]);
+    }
+
+    #[test]
+    fn thin_grid() {
+        // Narrower than the two border cells, the particles end up on the first cell.
+        let dimensions = (1, 1);
+        let grid = vec![1.0, 0.0];
+        let mut particles = vec![particle((0.0, 0.0), (0.0, 0.0))];
+        grid_to_particles(&mut particles, &grid, &grid, dimensions, &TransferScheme::Apic, 1.0);
+        assert_eq!(particles[0].position, (0.0, 0.0));
+        assert_eq!(particles[0].velocity, (1.0, 0.0));
+    }
+}
@@ -0,0 +1,77 @@
+#version 450
+
+layout(rgba32f, location = 0) uniform image2D particleField;
+layout(rgba32f, location = 1) uniform image2D affineField;
+layout(rg32f, location = 2) uniform image2D velocityField;
+layout(rg32f, location = 3) uniform image2D previousVelocityField;
+layout(location = 4) uniform int numberOfParticles;
+layout(location = 5) uniform bool useAffine;
+layout(location = 6) uniform float flipRatio;
+layout(location = 7) uniform float deltaTime;
+
+layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+vec2 velocityAt(ivec2 coordinate) {
+    coordinate = clamp(coordinate, ivec2(0), imageSize(velocityField) - ivec2(1));
+    return imageLoad(velocityField, coordinate).xy;
+}
+
+vec2 previousVelocityAt(ivec2 coordinate) {
+    coordinate = clamp(coordinate, ivec2(0), imageSize(previousVelocityField) - ivec2(1));
+    return imageLoad(previousVelocityField, coordinate).xy;
+}
+
+vec2 bilinearVelocity(vec2 coordinate) {
+    vec2 interpolation = fract(coordinate);
+    ivec2 leftBottom = ivec2(floor(coordinate));
+    vec2 bottom = mix(velocityAt(leftBottom), velocityAt(leftBottom + ivec2(1, 0)), interpolation.x);
+    vec2 top    = mix(velocityAt(leftBottom + ivec2(0, 1)), velocityAt(leftBottom + ivec2(1, 1)), interpolation.x);
+    return mix(bottom, top, interpolation.y);
+}
+
+vec2 bilinearPreviousVelocity(vec2 coordinate) {
+    vec2 interpolation = fract(coordinate);
+    ivec2 leftBottom = ivec2(floor(coordinate));
+    vec2 bottom = mix(previousVelocityAt(leftBottom), previousVelocityAt(leftBottom + ivec2(1, 0)), interpolation.x);
+    vec2 top    = mix(previousVelocityAt(leftBottom + ivec2(0, 1)), previousVelocityAt(leftBottom + ivec2(1, 1)), interpolation.x);
+    return mix(bottom, top, interpolation.y);
+}
+
+void main() {
+    ivec2 particleCoordinate = ivec2(gl_GlobalInvocationID.xy);
+    int index = particleCoordinate.y * imageSize(particleField).x + particleCoordinate.x;
+    if (index >= numberOfParticles) return;
+
+    vec4 particle = imageLoad(particleField, particleCoordinate);
+    vec2 position = particle.xy;
+    vec2 gridVelocity = bilinearVelocity(position);
+
+    vec2 velocity;
+    vec4 affine = vec4(0.0);
+    if (useAffine) {
+        // C = sum(v_i * grad(w_i)^T), the velocity gradient of the bilinear interpolation.
+        velocity = gridVelocity;
+        ivec2 base = ivec2(floor(position));
+        for (int y = 0; y < 2; y++) {
+            for (int x = 0; x < 2; x++) {
+                ivec2 node = base + ivec2(x, y);
+                vec2 offset = vec2(node) - position;
+                vec2 weights = vec2(1.0) - abs(offset);
+                vec2 gradient = sign(offset) * weights.yx;
+                vec2 nodeVelocity = velocityAt(node);
+                affine += vec4(nodeVelocity.x * gradient, nodeVelocity.y * gradient);
+            }
+        }
+    } else {
+        vec2 flipVelocity = particle.zw + gridVelocity - bilinearPreviousVelocity(position);
+        velocity = mix(gridVelocity, flipVelocity, flipRatio);
+    }
+
+    // Midpoint integration through the divergence free grid velocity.
+    vec2 midpoint = position + 0.5 * deltaTime * gridVelocity;
+    position += deltaTime * bilinearVelocity(midpoint);
+    position = clamp(position, vec2(1.0), vec2(imageSize(velocityField) - ivec2(2)));
+
+    imageStore(particleField, particleCoordinate, vec4(position, velocity));
+    imageStore(affineField, particleCoordinate, affine);
+}
@@ -0,0 +1,261 @@
+mod cpu;
+
+use crate::context::Context;
+use crate::fluid::Fluid;
+use crate::simulator::projector::Projector;
+
+/// How the velocity goes back and forth between the particles and the grid.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransferScheme {
+    /// Blends PIC (`flip_ratio = 0`, dissipative) and FLIP (`flip_ratio = 1`, noisy) updates.
+    PicFlip { flip_ratio: f32 },
+    /// Affine particle-in-cell: the particles also carry the local velocity gradient.
+    Apic
+}
+
+/// Where the particles live and where the transfers run.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParticleBackend {
+    Gpu,
+    Cpu
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Particle {
+    pub position: (f32, f32),
+    pub velocity: (f32, f32),
+    /// Row major velocity gradient, only used by `TransferScheme::Apic`.
+    pub affine: [f32; 4]
+}
+
+enum Particles {
+    /// One particle per texel: (position, velocity) and the affine matrix.
+    Gpu { particle_field: gpu::Texture2D, affine_field: gpu::Texture2D, count: usize },
+    Cpu(Vec<Particle>)
+}
+
+/// Hybrid particle-grid solver. The particles carry the velocity and the grid makes it divergence free.
+pub struct ParticleSolver {
+    clear_program: gpu::ComputeProgram,
+    particles_to_grid_program: gpu::ComputeProgram,
+    normalize_program: gpu::ComputeProgram,
+    grid_to_particles_program: gpu::ComputeProgram,
+    momentum_x_field: gpu::Texture2D,
+    momentum_y_field: gpu::Texture2D,
+    weight_field: gpu::Texture2D,
+    projection_scratch_field: gpu::Texture2D,
+    particles: Option<Particles>,
+    pub scheme: TransferScheme
+}
+
+impl ParticleSolver {
+    const PARTICLE_TEXTURE_WIDTH : usize = 1024;
+    const FIXED_POINT_SCALE      : f32   = 4096.0;
+
+    pub fn new(context: &Context, dimensions: (usize, usize)) -> Self {
+        let clear_shader = gpu::ComputeShader::new(&context.context, include_str!("clear_2d.glsl")).expect("Couldn't create clear_shader.");
+        let clear_program = gpu::ComputeProgram::new(&context.context, &clear_shader).expect("Couldn't create clear_program.");
+        let particles_to_grid_shader = gpu::ComputeShader::new(&context.context, include_str!("particles_to_grid_2d.glsl")).expect("Couldn't create particles_to_grid_shader.");
+        let particles_to_grid_program = gpu::ComputeProgram::new(&context.context, &particles_to_grid_shader).expect("Couldn't create particles_to_grid_program.");
+        let normalize_shader = gpu::ComputeShader::new(&context.context, include_str!("normalize_2d.glsl")).expect("Couldn't create normalize_shader.");
+        let normalize_program = gpu::ComputeProgram::new(&context.context, &normalize_shader).expect("Couldn't create normalize_program.");
+        let grid_to_particles_shader = gpu::ComputeShader::new(&context.context, include_str!("grid_to_particles_2d.glsl")).expect("Couldn't create grid_to_particles_shader.");
+        let grid_to_particles_program = gpu::ComputeProgram::new(&context.context, &grid_to_particles_shader).expect("Couldn't create grid_to_particles_program.");
+
+        let accumulator_format = gpu::TextureFormat::new(gpu::ColorFormat::R, gpu::Type::I32);
+        let momentum_x_field = gpu::Texture2D::allocate(&context.context, dimensions, &accumulator_format);
+        let momentum_y_field = gpu::Texture2D::allocate(&context.context, dimensions, &accumulator_format);
+        let weight_field = gpu::Texture2D::allocate(&context.context, dimensions, &accumulator_format);
+        let vector_format = gpu::TextureFormat::new(gpu::ColorFormat::RG, gpu::Type::F32);
+        let projection_scratch_field = gpu::Texture2D::allocate(&context.context, dimensions, &vector_format);
+
+        let particles = None;
+        let scheme = TransferScheme::PicFlip { flip_ratio: 0.95 };
+        Self { clear_program, particles_to_grid_program, normalize_program, grid_to_particles_program, momentum_x_field, momentum_y_field, weight_field, projection_scratch_field, particles, scheme }
+    }
+
+    /// Whether there are particles, i.e. if the simulator should run in particle mode.
+    pub fn is_active(&self) -> bool {
+        self.particles.is_some()
+    }
+
+    /// Seeds `particles_per_axis`² jittered particles in every cell whose center is `inside`, with the `velocity` at their position.
+    pub fn seed(&mut self, context: &Context, backend: ParticleBackend, dimensions: (usize, usize), particles_per_axis: usize, inside: impl Fn(f32, f32) -> bool, velocity: impl Fn(f32, f32) -> (f32, f32)) {
+        let mut particles = Vec::new();
+        let spacing = 1.0 / particles_per_axis as f32;
+        // Only the inner cells, there are none if a side is shorter than 3.
+        for y in 1 .. dimensions.1.saturating_sub(1) {
+            for x in 1 .. dimensions.0.saturating_sub(1) {
+                if !inside(x as f32, y as f32) {
+                    continue;
+                }
+                for j in 0 .. particles_per_axis {
+                    for i in 0 .. particles_per_axis {
+                        let jitter = jitter(particles.len());
+                        let position = (
+                            x as f32 - 0.5 + (i as f32 + 0.5 + jitter.0 * 0.5) * spacing,
+                            y as f32 - 0.5 + (j as f32 + 0.5 + jitter.1 * 0.5) * spacing
+                        );
+                        let velocity = velocity(position.0, position.1);
+                        particles.push(Particle { position, velocity, affine: [0.0; 4] });
+                    }
+                }
+            }
+        }
+        self.set_particles(context, backend, particles);
+    }
+
+    pub fn set_particles(&mut self, context: &Context, backend: ParticleBackend, particles: Vec<Particle>) {
+        self.particles = match backend {
+            ParticleBackend::Cpu => Some(Particles::Cpu(particles)),
+            ParticleBackend::Gpu => {
+                let count = particles.len();
+                let dimensions = (Self::PARTICLE_TEXTURE_WIDTH, (count + Self::PARTICLE_TEXTURE_WIDTH - 1) / Self::PARTICLE_TEXTURE_WIDTH);
+                let dimensions = (dimensions.0, dimensions.1.max(1));
+                let mut particle_data = vec![0.0; dimensions.0 * dimensions.1 * 4];
+                let mut affine_data = vec![0.0; dimensions.0 * dimensions.1 * 4];
+                for (index, particle) in particles.iter().enumerate() {
+                    particle_data[index * 4 .. index * 4 + 4].copy_from_slice(&[particle.position.0, particle.position.1, particle.velocity.0, particle.velocity.1]);
+                    affine_data[index * 4 .. index * 4 + 4].copy_from_slice(&particle.affine);
+                }
+                let format = gpu::TextureFormat::new(gpu::ColorFormat::components(4), gpu::Type::F32);
+                let particle_field = gpu::Texture2D::from_data(&context.context, dimensions, &format, &particle_data, &format);
+                let affine_field = gpu::Texture2D::from_data(&context.context, dimensions, &format, &affine_data, &format);
+                Some(Particles::Gpu { particle_field, affine_field, count })
+            }
+        };
+    }
+
+    /// Reads the particles back, wherever they live.
+    pub fn particles(&self) -> Vec<Particle> {
+        match &self.particles {
+            None => Vec::new(),
+            Some(Particles::Cpu(particles)) => particles.clone(),
+            Some(Particles::Gpu { particle_field, affine_field, count }) => {
+                let particle_data: Vec<f32> = particle_field.data();
+                let affine_data: Vec<f32> = affine_field.data();
+                (0 .. *count).map(|index| {
+                    let particle = &particle_data[index * 4 .. index * 4 + 4];
+                    let affine = &affine_data[index * 4 .. index * 4 + 4];
+                    Particle { position: (particle[0], particle[1]), velocity: (particle[2], particle[3]), affine: [affine[0], affine[1], affine[2], affine[3]] }
+                }).collect()
+            }
+        }
+    }
+
+    /// Writes the particles velocity to `fluid.velocity_field` and keeps a copy in `fluid.previous_velocity_field`.
+    pub fn transfer_to_grid(&mut self, fluid: &mut Fluid) {
+        let use_affine = self.scheme == TransferScheme::Apic;
+        match &self.particles {
+            None => (),
+            Some(Particles::Cpu(particles)) => {
+                let grid = cpu::particles_to_grid(particles, fluid.dimensions, &self.scheme);
+                let format = gpu::TextureFormat::new(gpu::ColorFormat::RG, gpu::Type::F32);
+                fluid.velocity_field.set_data(fluid.dimensions, &format, &grid, &format);
+                fluid.previous_velocity_field.set_data(fluid.dimensions, &format, &grid, &format);
+            },
+            Some(Particles::Gpu { particle_field, affine_field, count }) => {
+                const MOMENTUM_X_FIELD_LOCATION : usize = 0;
+                const MOMENTUM_Y_FIELD_LOCATION : usize = 1;
+                const WEIGHT_FIELD_LOCATION     : usize = 2;
+                self.clear_program.bind_image_2d(&self.momentum_x_field, MOMENTUM_X_FIELD_LOCATION);
+                self.clear_program.bind_image_2d(&self.momentum_y_field, MOMENTUM_Y_FIELD_LOCATION);
+                self.clear_program.bind_image_2d(&self.weight_field, WEIGHT_FIELD_LOCATION);
+                compute(&self.clear_program, fluid.dimensions);
+
+                const PARTICLE_FIELD_LOCATION           : usize = 0;
+                const AFFINE_FIELD_LOCATION             : usize = 1;
+                const SPLAT_MOMENTUM_X_FIELD_LOCATION   : usize = 2;
+                const SPLAT_MOMENTUM_Y_FIELD_LOCATION   : usize = 3;
+                const SPLAT_WEIGHT_FIELD_LOCATION       : usize = 4;
+                const NUMBER_OF_PARTICLES_LOCATION      : usize = 5;
+                const USE_AFFINE_LOCATION               : usize = 6;
+                const FIXED_POINT_SCALE_LOCATION        : usize = 7;
+                let program = &self.particles_to_grid_program;
+                program.bind_image_2d(particle_field, PARTICLE_FIELD_LOCATION);
+                program.bind_image_2d(affine_field, AFFINE_FIELD_LOCATION);
+                program.bind_image_2d(&self.momentum_x_field, SPLAT_MOMENTUM_X_FIELD_LOCATION);
+                program.bind_image_2d(&self.momentum_y_field, SPLAT_MOMENTUM_Y_FIELD_LOCATION);
+                program.bind_image_2d(&self.weight_field, SPLAT_WEIGHT_FIELD_LOCATION);
+                program.bind_i32(*count as i32, NUMBER_OF_PARTICLES_LOCATION);
+                program.bind_bool(use_affine, USE_AFFINE_LOCATION);
+                program.bind_f32(Self::FIXED_POINT_SCALE, FIXED_POINT_SCALE_LOCATION);
+                compute(program, particle_field.dimensions());
+
+                const VELOCITY_FIELD_LOCATION           : usize = 0;
+                const PREVIOUS_VELOCITY_FIELD_LOCATION  : usize = 1;
+                let program = &self.normalize_program;
+                program.bind_image_2d(&fluid.velocity_field, VELOCITY_FIELD_LOCATION);
+                program.bind_image_2d(&fluid.previous_velocity_field, PREVIOUS_VELOCITY_FIELD_LOCATION);
+                program.bind_image_2d(&self.momentum_x_field, SPLAT_MOMENTUM_X_FIELD_LOCATION);
+                program.bind_image_2d(&self.momentum_y_field, SPLAT_MOMENTUM_Y_FIELD_LOCATION);
+                program.bind_image_2d(&self.weight_field, SPLAT_WEIGHT_FIELD_LOCATION);
+                compute(program, fluid.dimensions);
+            }
+        }
+    }
+
+    /// Makes `fluid.velocity_field` divergence free with the `projector`.
+    pub fn project(&mut self, projector: &mut Projector, fluid: &mut Fluid, iterations: usize) {
+        projector.project(&mut fluid.velocity_field, &mut self.projection_scratch_field, iterations);
+    }
+
+    /// Updates the particles from the change of the grid velocity and moves them.
+    pub fn transfer_to_particles(&mut self, fluid: &Fluid, delta_time: f32) {
+        let use_affine = self.scheme == TransferScheme::Apic;
+        let flip_ratio = match self.scheme {
+            TransferScheme::PicFlip { flip_ratio } => flip_ratio,
+            TransferScheme::Apic => 0.0
+        };
+        match &mut self.particles {
+            None => (),
+            Some(Particles::Cpu(particles)) => {
+                let grid: Vec<f32> = fluid.velocity_field.data();
+                let previous_grid: Vec<f32> = fluid.previous_velocity_field.data();
+                cpu::grid_to_particles(particles, &grid, &previous_grid, fluid.dimensions, &self.scheme, delta_time);
+            },
+            Some(Particles::Gpu { particle_field, affine_field, count }) => {
+                const PARTICLE_FIELD_LOCATION          : usize = 0;
+                const AFFINE_FIELD_LOCATION            : usize = 1;
+                const VELOCITY_FIELD_LOCATION          : usize = 2;
+                const PREVIOUS_VELOCITY_FIELD_LOCATION : usize = 3;
+                const NUMBER_OF_PARTICLES_LOCATION     : usize = 4;
+                const USE_AFFINE_LOCATION              : usize = 5;
+                const FLIP_RATIO_LOCATION              : usize = 6;
+                const DELTA_TIME_LOCATION              : usize = 7;
+                let program = &self.grid_to_particles_program;
+                program.bind_image_2d(particle_field, PARTICLE_FIELD_LOCATION);
+                program.bind_image_2d(affine_field, AFFINE_FIELD_LOCATION);
+                program.bind_image_2d(&fluid.velocity_field, VELOCITY_FIELD_LOCATION);
+                program.bind_image_2d(&fluid.previous_velocity_field, PREVIOUS_VELOCITY_FIELD_LOCATION);
+                program.bind_i32(*count as i32, NUMBER_OF_PARTICLES_LOCATION);
+                program.bind_bool(use_affine, USE_AFFINE_LOCATION);
+                program.bind_f32(flip_ratio, FLIP_RATIO_LOCATION);
+                program.bind_f32(delta_time, DELTA_TIME_LOCATION);
+                compute(program, particle_field.dimensions());
+            }
+        }
+    }
+}
+
+fn compute(program: &gpu::ComputeProgram, dimensions: (usize, usize)) {
+    program.compute((dimensions.0, dimensions.1, 1));
+    //FIXME: How to expose it on the GPU API?
+    // Ref: https://www.khronos.org/registry/OpenGL-Refpages/gl4/html/glMemoryBarrier.xhtml
+    unsafe {
+        gl::MemoryBarrier(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+    }
+}
+
+/// A deterministic pseudo random offset in [-1, 1]², so the seeding is reproducible.
+pub(crate) fn jitter(index: usize) -> (f32, f32) {
+    let hash = |mut value: u32| {
+        value ^= value >> 16;
+        value = value.wrapping_mul(0x7feb_352d);
+        value ^= value >> 15;
+        value = value.wrapping_mul(0x846c_a68b);
+        value ^= value >> 16;
+        value as f32 / std::u32::MAX as f32 * 2.0 - 1.0
+    };
+    (hash(index as u32 * 2), hash(index as u32 * 2 + 1))
+}
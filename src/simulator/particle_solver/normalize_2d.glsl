@@ -0,0 +1,19 @@
+#version 450
+
+// Turns the accumulated momentum into velocity and keeps a copy of it for the FLIP update.
+layout(rg32f, location = 0) uniform image2D velocityField;
+layout(rg32f, location = 1) uniform image2D previousVelocityField;
+layout(r32i, location = 2) uniform iimage2D momentumXField;
+layout(r32i, location = 3) uniform iimage2D momentumYField;
+layout(r32i, location = 4) uniform iimage2D weightField;
+
+layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+void main() {
+    ivec2 coordinate = ivec2(gl_GlobalInvocationID.xy);
+    float weight = float(imageLoad(weightField, coordinate).x);
+    vec2 momentum = vec2(imageLoad(momentumXField, coordinate).x, imageLoad(momentumYField, coordinate).x);
+    vec2 velocity = weight > 0.0 ? momentum / weight : vec2(0.0);
+    imageStore(velocityField, coordinate, vec4(velocity, 0.0, 0.0));
+    imageStore(previousVelocityField, coordinate, vec4(velocity, 0.0, 0.0));
+}
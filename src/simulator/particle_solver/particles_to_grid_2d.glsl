@@ -0,0 +1,41 @@
+#version 450
+
+// Each texel is a particle: (position.xy, velocity.xy) and its affine velocity matrix (c00, c01, c10, c11).
+layout(rgba32f, location = 0) uniform image2D particleField;
+layout(rgba32f, location = 1) uniform image2D affineField;
+// There are no portable float atomics, so the sums are accumulated in fixed point.
+layout(r32i, location = 2) uniform iimage2D momentumXField;
+layout(r32i, location = 3) uniform iimage2D momentumYField;
+layout(r32i, location = 4) uniform iimage2D weightField;
+layout(location = 5) uniform int numberOfParticles;
+layout(location = 6) uniform bool useAffine;
+layout(location = 7) uniform float fixedPointScale;
+
+layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+void main() {
+    ivec2 particleCoordinate = ivec2(gl_GlobalInvocationID.xy);
+    int index = particleCoordinate.y * imageSize(particleField).x + particleCoordinate.x;
+    if (index >= numberOfParticles) return;
+
+    vec4 particle = imageLoad(particleField, particleCoordinate);
+    vec2 position = particle.xy;
+    vec2 velocity = particle.zw;
+    vec4 affine = imageLoad(affineField, particleCoordinate);
+    mat2 C = mat2(affine.x, affine.z, affine.y, affine.w);
+
+    ivec2 size = imageSize(weightField);
+    ivec2 base = ivec2(floor(position));
+    for (int y = 0; y < 2; y++) {
+        for (int x = 0; x < 2; x++) {
+            ivec2 node = base + ivec2(x, y);
+            if (any(lessThan(node, ivec2(0))) || any(greaterThanEqual(node, size))) continue;
+            vec2 offset = vec2(node) - position;
+            float weight = (1.0 - abs(offset.x)) * (1.0 - abs(offset.y));
+            vec2 nodeVelocity = useAffine ? velocity + C * offset : velocity;
+            imageAtomicAdd(momentumXField, node, int(round(weight * nodeVelocity.x * fixedPointScale)));
+            imageAtomicAdd(momentumYField, node, int(round(weight * nodeVelocity.y * fixedPointScale)));
+            imageAtomicAdd(weightField, node, int(round(weight * fixedPointScale)));
+        }
+    }
+}
@@ -1,6 +1,6 @@
 #version 450
 
-layout(rgba32f, location = 0) uniform image2D velocityField;
+layout(rg32f, location = 0) uniform image2D velocityField;
 layout(r32f, location = 1) uniform image2D pField;
 layout(r32f, location = 2) uniform image2D divField;
 
@@ -32,8 +32,8 @@ void main() {
         ivec2 neighborCoordinate = coordinate + neighborsOffsets[i];
         div += imageLoad(velocityField, neighborCoordinate)[component] * neighborsFactors[i];
     }
-    // FIXME: It assumes the field size is squared, what happens if we use rectangular sizes?
-    div = -0.5 * div / float(imageSize(velocityField).x);
+    // In cell units, so the velocity doesn't need to scale the pressure gradient back by N.
+    div = -0.5 * div;
     imageStore(divField, coordinate, div);
 }
 
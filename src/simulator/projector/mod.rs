@@ -16,7 +16,8 @@ pub struct Projector {
     boundary_limiter: BoundaryLimiter,
     linear_solver: LinearSolver,
     gradient_program: Gradient,
-    divergence_program: Divergence
+    divergence_program: Divergence,
+    projected: bool
 }
 
 impl Projector {
@@ -41,7 +42,13 @@ impl Projector {
 
         let gradient_program = Gradient::new();
         let divergence_program = Divergence::new();
-        Self { initialize_program, gradient_program, divergence_program, div_field, p_field, boundary_limiter, linear_solver, velocity_program, previous_velocity_program }
+        let projected = false;
+        Self { initialize_program, gradient_program, divergence_program, div_field, p_field, boundary_limiter, linear_solver, velocity_program, previous_velocity_program, projected }
+    }
+
+    /// The pressure of the last projection. None until a field was projected.
+    pub fn pressure_field(&self) -> Option<&gpu::Texture2D> {
+        if self.projected { Some(&self.p_field) } else { None }
     }
 
     pub fn project(&mut self, velocity_field: &mut gpu::Texture2D, previous_velocity_field: &mut gpu::Texture2D, iterations: usize) {
@@ -55,16 +62,26 @@ impl Projector {
         let dimensions = velocity_field.dimensions();
         let dimensions = (dimensions.0, dimensions.1, 1);
         self.initialize_program.compute(dimensions);
+        //FIXME: How to expose it on the GPU API?
+        // Ref: https://www.khronos.org/registry/OpenGL-Refpages/gl4/html/glMemoryBarrier.xhtml
+        unsafe {
+            gl::MemoryBarrier(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+        }
 
         // self.boundary_limiter.limit(&mut self.div_field, false);
         // self.boundary_limiter.limit(&mut self.p_field, false);
-        self.linear_solver.solve(false, &mut self.p_field, &self.div_field, 1.0, 6.0, iterations);
+        self.linear_solver.solve(false, &mut self.p_field, &self.div_field, 1.0, 4.0, iterations);
 
         let offset = (1, 1);
         self.velocity_program.bind_image_2d(velocity_field, VELOCITY_FIELD_LOCATION);
         self.velocity_program.bind_image_2d(&self.p_field, P_FIELD_LOCATION);
         self.velocity_program.bind_ivec2(offset, OFFSET_LOCATION);
         self.velocity_program.compute((dimensions.0 - 2, dimensions.1 - 2, 1));
+        //FIXME: How to expose it on the GPU API?
+        // Ref: https://www.khronos.org/registry/OpenGL-Refpages/gl4/html/glMemoryBarrier.xhtml
+        unsafe {
+            gl::MemoryBarrier(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+        }
 
         // self.boundary_limiter.limit(velocity_field, true);
 
@@ -72,5 +89,52 @@ impl Projector {
         self.previous_velocity_program.bind_image_2d(&self.div_field, DIV_FIELD_LOCATION);
         self.previous_velocity_program.bind_image_2d(&self.p_field, P_FIELD_LOCATION);
         self.previous_velocity_program.compute(dimensions);
+        self.projected = true;
+    }
+}
+#[cfg(test)]
+mod test {
+    use crate::context::Context;
+    use crate::field::Field;
+    use crate::numpy;
+    use crate::simulator::projector::Projector;
+
+    /// Sum of the absolute central divergences of the inner cells.
+    fn divergence(data: &[f32], dimensions: (usize, usize)) -> f32 {
+        let divergence = numpy::divergence(data, dimensions);
+        let mut sum = 0.0;
+        for y in 1 .. dimensions.1 - 1 {
+            for x in 1 .. dimensions.0 - 1 {
+                sum += divergence[y * dimensions.0 + x].abs();
+            }
+        }
+        sum
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn project_removes_divergence() {
+        // Not squared, so the divergence and the gradient must be in the same cell units.
+        let dimensions = (8, 5);
+        let context = Context::new(dimensions);
+        let mut projector = Projector::new(&context, dimensions);
+
+        // A source in the middle of the inner cells.
+        let center = ((dimensions.0 - 1) as f32 / 2.0, (dimensions.1 - 1) as f32 / 2.0);
+        let mut data = vec![0.0; dimensions.0 * dimensions.1 * 2];
+        for y in 1 .. dimensions.1 - 1 {
+            for x in 1 .. dimensions.0 - 1 {
+                let index = (y * dimensions.0 + x) * 2;
+                data[index] = x as f32 - center.0;
+                data[index + 1] = y as f32 - center.1;
+            }
+        }
+        let mut velocity_field = Field::from_data(&context, dimensions, 2, &data).field;
+        let format = gpu::TextureFormat::new(gpu::ColorFormat::RG, gpu::Type::F32);
+        let mut previous_velocity_field = gpu::Texture2D::allocate(&context.context, dimensions, &format);
+
+        let initial_divergence = divergence(&data, dimensions);
+        projector.project(&mut velocity_field, &mut previous_velocity_field, 60);
+        let data: Vec<f32> = velocity_field.data();
+        assert!(divergence(&data, dimensions) < 0.5 * initial_divergence, "{} from {}", divergence(&data, dimensions), initial_divergence);
+    }
+}
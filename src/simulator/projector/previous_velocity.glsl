@@ -1,6 +1,6 @@
 #version 450
 
-layout(rgba32f, location = 0) uniform image2D velocityField;
+layout(rg32f, location = 0) uniform image2D velocityField;
 layout(r32f, location = 1) uniform image2D pField;
 layout(r32f, location = 2) uniform image2D divField;
 
@@ -1,6 +1,6 @@
 #version 450
 
-layout(rgba32f, location = 0) uniform image2D velocityField;
+layout(rg32f, location = 0) uniform image2D velocityField;
 layout(r32f, location = 1) uniform image2D pField;
 layout(location = 2) uniform ivec2 offset;
 
@@ -18,13 +18,9 @@ void main() {
 
     for (int i = 0; i < NUMBER_OF_COMPONENTS; i++) {
         ivec2 offset = offsets[i];
-        velocity[i] -= imageLoad(pField, coordinate + offset)[i] - imageLoad(pField, coordinate - offset)[i];
+        velocity[i] -= 0.5 * (imageLoad(pField, coordinate + offset).x - imageLoad(pField, coordinate - offset).x);
     }
 
-    // Considering imageSize.x == imageSize.y, what happens if imageSize is rectangular?
-    float N = float(imageSize(velocityField).x) * 0.5;
-    velocity *= N;
-
     imageStore(velocityField, coordinate, velocity);
 }
 
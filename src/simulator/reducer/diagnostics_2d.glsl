@@ -0,0 +1,44 @@
+#version 450
+
+layout(rg32f, location = 0) uniform image2D velocityField;
+layout(r32f,  location = 1) uniform image2D densityField;
+layout(r32f,  location = 2) uniform image2D outputField;
+layout(location = 3) uniform int quantity;
+
+layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+#define MASS           0
+#define KINETIC_ENERGY 1
+#define ENSTROPHY      2
+#define DIVERGENCE     3
+
+vec2 velocity(ivec2 coordinate) {
+    return imageLoad(velocityField, coordinate).xy;
+}
+
+// Per cell densities over the inner cells, the sides are the boundary.
+void main() {
+    ivec2 coordinate = ivec2(gl_GlobalInvocationID.xy);
+    ivec2 dimensions = imageSize(velocityField);
+    if (any(lessThan(coordinate, ivec2(1))) || any(greaterThanEqual(coordinate, dimensions - 1))) {
+        imageStore(outputField, coordinate, vec4(0.0));
+        return;
+    }
+    vec2 center = velocity(coordinate);
+    vec2 left   = velocity(coordinate - ivec2(1, 0));
+    vec2 right  = velocity(coordinate + ivec2(1, 0));
+    vec2 bottom = velocity(coordinate - ivec2(0, 1));
+    vec2 top    = velocity(coordinate + ivec2(0, 1));
+    float value;
+    if (quantity == MASS) {
+        value = imageLoad(densityField, coordinate).x;
+    } else if (quantity == KINETIC_ENERGY) {
+        value = 0.5 * dot(center, center);
+    } else if (quantity == ENSTROPHY) {
+        float vorticity = 0.5 * (right.y - left.y) - 0.5 * (top.x - bottom.x);
+        value = 0.5 * vorticity * vorticity;
+    } else {
+        value = abs(0.5 * (right.x - left.x) + 0.5 * (top.y - bottom.y));
+    }
+    imageStore(outputField, coordinate, vec4(value));
+}
@@ -0,0 +1,185 @@
+use crate::context::Context;
+use crate::fluid::Fluid;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Reduction {
+    Sum,
+    Min,
+    Max,
+    /// `sqrt(sum(value²))`.
+    L2Norm
+}
+
+/// Health checks of a simulation step, over the inner cells and in cell units.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct StepStats {
+    /// The simulation time at the end of the step.
+    pub time: f32,
+    pub delta_time: f32,
+    /// Total dye.
+    pub mass: f32,
+    /// `sum(|u|² / 2)`.
+    pub kinetic_energy: f32,
+    /// `sum(ω² / 2)`.
+    pub enstrophy: f32,
+    /// Max |div u| at the end of the step, after the projection. In liquid mode the air cells count too.
+    pub max_divergence: f32,
+    pub max_speed: f32,
+    /// `max_speed * delta_time`, the number of cells the fastest fluid crosses in a step.
+    pub cfl: f32
+}
+
+/// Multi-pass reductions of fields to a single value: each pass reduces blocks of `BLOCK_SIZE`² texels.
+pub struct Reducer {
+    scalar_map_program: gpu::ComputeProgram,
+    vector_map_program: gpu::ComputeProgram,
+    diagnostics_program: gpu::ComputeProgram,
+    reduce_program: gpu::ComputeProgram,
+    mapped_field: gpu::Texture2D,
+    levels: Vec<gpu::Texture2D>,
+    dimensions: (usize, usize)
+}
+
+impl Reducer {
+    const BLOCK_SIZE : usize = 8;
+
+    pub fn new(context: &Context, dimensions: (usize, usize)) -> Self {
+        let scalar_map_shader = gpu::ComputeShader::new(&context.context, include_str!("scalar_map_2d.glsl")).expect("Couldn't create scalar_map_shader.");
+        let scalar_map_program = gpu::ComputeProgram::new(&context.context, &scalar_map_shader).expect("Couldn't create scalar_map_program.");
+        let vector_map_shader = gpu::ComputeShader::new(&context.context, include_str!("vector_map_2d.glsl")).expect("Couldn't create vector_map_shader.");
+        let vector_map_program = gpu::ComputeProgram::new(&context.context, &vector_map_shader).expect("Couldn't create vector_map_program.");
+        let diagnostics_shader = gpu::ComputeShader::new(&context.context, include_str!("diagnostics_2d.glsl")).expect("Couldn't create diagnostics_shader.");
+        let diagnostics_program = gpu::ComputeProgram::new(&context.context, &diagnostics_shader).expect("Couldn't create diagnostics_program.");
+        let reduce_shader = gpu::ComputeShader::new(&context.context, include_str!("reduce_2d.glsl")).expect("Couldn't create reduce_shader.");
+        let reduce_program = gpu::ComputeProgram::new(&context.context, &reduce_shader).expect("Couldn't create reduce_program.");
+
+        let format = gpu::TextureFormat::new(gpu::ColorFormat::R, gpu::Type::F32);
+        let mapped_field = gpu::Texture2D::allocate(&context.context, dimensions, &format);
+        let mut levels = Vec::new();
+        let mut level_dimensions = dimensions;
+        while level_dimensions != (1, 1) {
+            level_dimensions = (level_dimensions.0.div_ceil(Self::BLOCK_SIZE), level_dimensions.1.div_ceil(Self::BLOCK_SIZE));
+            levels.push(gpu::Texture2D::allocate(&context.context, level_dimensions, &format));
+        }
+        Self { scalar_map_program, vector_map_program, diagnostics_program, reduce_program, mapped_field, levels, dimensions }
+    }
+
+    fn memory_barrier() {
+        //FIXME: How to expose it on the GPU API?
+        // Ref: https://www.khronos.org/registry/OpenGL-Refpages/gl4/html/glMemoryBarrier.xhtml
+        unsafe {
+            gl::MemoryBarrier(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+        }
+    }
+
+    fn map(&self, program: &gpu::ComputeProgram, field: &gpu::Texture2D, square: bool) {
+        const FIELD_LOCATION        : usize = 0;
+        const OUTPUT_FIELD_LOCATION : usize = 1;
+        const SQUARE_LOCATION       : usize = 2;
+        assert_eq!(field.dimensions(), self.dimensions, "The reducer and the field dimensions must match.");
+        program.bind_image_2d(field, FIELD_LOCATION);
+        program.bind_image_2d(&self.mapped_field, OUTPUT_FIELD_LOCATION);
+        program.bind_bool(square, SQUARE_LOCATION);
+        program.compute((self.dimensions.0, self.dimensions.1, 1));
+        Self::memory_barrier();
+    }
+
+    /// Reduces `mapped_field` down to one texel and reads it back.
+    fn reduce_mapped(&self, reduction: Reduction) -> f32 {
+        const INPUT_FIELD_LOCATION      : usize = 0;
+        const OUTPUT_FIELD_LOCATION     : usize = 1;
+        const OPERATION_LOCATION        : usize = 2;
+        const INPUT_DIMENSIONS_LOCATION : usize = 3;
+        let operation = match reduction {
+            Reduction::Sum | Reduction::L2Norm => 0,
+            Reduction::Min                     => 1,
+            Reduction::Max                     => 2
+        };
+        let mut input = &self.mapped_field;
+        for level in &self.levels {
+            let input_dimensions = input.dimensions();
+            let dimensions = level.dimensions();
+            self.reduce_program.bind_image_2d(input, INPUT_FIELD_LOCATION);
+            self.reduce_program.bind_image_2d(level, OUTPUT_FIELD_LOCATION);
+            self.reduce_program.bind_i32(operation, OPERATION_LOCATION);
+            self.reduce_program.bind_ivec2((input_dimensions.0 as i32, input_dimensions.1 as i32), INPUT_DIMENSIONS_LOCATION);
+            self.reduce_program.compute((dimensions.0, dimensions.1, 1));
+            Self::memory_barrier();
+            input = level;
+        }
+        let data: Vec<f32> = input.data();
+        match reduction {
+            Reduction::L2Norm => data[0].sqrt(),
+            _                 => data[0]
+        }
+    }
+
+    /// Reduces a R field.
+    pub fn reduce_scalar(&self, field: &gpu::Texture2D, reduction: Reduction) -> f32 {
+        self.map(&self.scalar_map_program, field, reduction == Reduction::L2Norm);
+        self.reduce_mapped(reduction)
+    }
+
+    /// Reduces the magnitudes of a RG field.
+    pub fn reduce_vector(&self, field: &gpu::Texture2D, reduction: Reduction) -> f32 {
+        self.map(&self.vector_map_program, field, reduction == Reduction::L2Norm);
+        self.reduce_mapped(reduction)
+    }
+
+    fn diagnostic(&self, fluid: &Fluid, quantity: i32, reduction: Reduction) -> f32 {
+        const VELOCITY_FIELD_LOCATION : usize = 0;
+        const DENSITY_FIELD_LOCATION  : usize = 1;
+        const OUTPUT_FIELD_LOCATION   : usize = 2;
+        const QUANTITY_LOCATION       : usize = 3;
+        self.diagnostics_program.bind_image_2d(&fluid.velocity_field, VELOCITY_FIELD_LOCATION);
+        self.diagnostics_program.bind_image_2d(&fluid.density_field, DENSITY_FIELD_LOCATION);
+        self.diagnostics_program.bind_image_2d(&self.mapped_field, OUTPUT_FIELD_LOCATION);
+        self.diagnostics_program.bind_i32(quantity, QUANTITY_LOCATION);
+        self.diagnostics_program.compute((self.dimensions.0, self.dimensions.1, 1));
+        Self::memory_barrier();
+        self.reduce_mapped(reduction)
+    }
+
+    /// The diagnostics of the current fields of `fluid`.
+    pub fn stats(&self, fluid: &Fluid, time: f32, delta_time: f32) -> StepStats {
+        const MASS           : i32 = 0;
+        const KINETIC_ENERGY : i32 = 1;
+        const ENSTROPHY      : i32 = 2;
+        const DIVERGENCE     : i32 = 3;
+        let mass = self.diagnostic(fluid, MASS, Reduction::Sum);
+        let kinetic_energy = self.diagnostic(fluid, KINETIC_ENERGY, Reduction::Sum);
+        let enstrophy = self.diagnostic(fluid, ENSTROPHY, Reduction::Sum);
+        let max_divergence = self.diagnostic(fluid, DIVERGENCE, Reduction::Max);
+        let max_speed = self.reduce_vector(&fluid.velocity_field, Reduction::Max);
+        let cfl = max_speed * delta_time;
+        StepStats { time, delta_time, mass, kinetic_energy, enstrophy, max_divergence, max_speed, cfl }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::context::Context;
+    use crate::field::Field;
+    use crate::simulator::reducer::{Reducer, Reduction};
+
+    #[test]
+    fn reductions() {
+        // Not a multiple of the block size, so the blocks on the sides are partial.
+        let dimensions = (19, 5);
+        let context = Context::new(dimensions);
+        let reducer = Reducer::new(&context, dimensions);
+        let data: Vec<f32> = (0 .. dimensions.0 * dimensions.1).map(|index| index as f32 - 10.0).collect();
+        let field = Field::from_data(&context, dimensions, 1, &data).field;
+        assert_eq!(reducer.reduce_scalar(&field, Reduction::Sum), data.iter().sum());
+        assert_eq!(reducer.reduce_scalar(&field, Reduction::Min), -10.0);
+        assert_eq!(reducer.reduce_scalar(&field, Reduction::Max), 84.0);
+        let l2_norm = data.iter().map(|value| value * value).sum::<f32>().sqrt();
+        assert!((reducer.reduce_scalar(&field, Reduction::L2Norm) - l2_norm).abs() < 1e-3);
+
+        let field = Field::from_fn(&context, dimensions, 2, |x, y| {
+            let index = (y * dimensions.0 + x) as f32;
+            [3.0 * index, 4.0 * index]
+        }).field;
+        assert_eq!(reducer.reduce_vector(&field, Reduction::Max), 5.0 * 94.0);
+    }
+}
@@ -0,0 +1,35 @@
+#version 450
+
+layout(r32f, location = 0) uniform image2D inputField;
+layout(r32f, location = 1) uniform image2D outputField;
+layout(location = 2) uniform int operation;
+layout(location = 3) uniform ivec2 inputDimensions;
+
+layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+#define SUM 0
+#define MIN 1
+#define MAX 2
+
+#define BLOCK_SIZE 8
+
+// Reduces a block of the input to one texel of the output.
+void main() {
+    ivec2 coordinate = ivec2(gl_GlobalInvocationID.xy);
+    ivec2 start = coordinate * BLOCK_SIZE;
+    ivec2 end = min(start + BLOCK_SIZE, inputDimensions);
+    float result = operation == SUM ? 0.0 : imageLoad(inputField, start).x;
+    for (int y = start.y; y < end.y; y++) {
+        for (int x = start.x; x < end.x; x++) {
+            float value = imageLoad(inputField, ivec2(x, y)).x;
+            if (operation == SUM) {
+                result += value;
+            } else if (operation == MIN) {
+                result = min(result, value);
+            } else {
+                result = max(result, value);
+            }
+        }
+    }
+    imageStore(outputField, coordinate, vec4(result));
+}
@@ -0,0 +1,14 @@
+#version 450
+
+layout(rg32f, location = 0) uniform image2D field;
+layout(r32f,  location = 1) uniform image2D outputField;
+layout(location = 2) uniform bool square;
+
+layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+void main() {
+    ivec2 coordinate = ivec2(gl_GlobalInvocationID.xy);
+    vec2 value = imageLoad(field, coordinate).xy;
+    float squaredMagnitude = dot(value, value);
+    imageStore(outputField, coordinate, vec4(square ? squaredMagnitude : sqrt(squaredMagnitude)));
+}
@@ -0,0 +1,93 @@
+use crate::context::Context;
+use crate::fluid::ViscosityModel;
+
+/// Recomputes the viscosity field from the velocity gradient for the non-Newtonian models.
+pub struct ViscosityUpdater {
+    compute_program: gpu::ComputeProgram
+}
+
+impl ViscosityUpdater {
+    pub fn new(context: &Context) -> Self {
+        let compute_shader = gpu::ComputeShader::new(&context.context, include_str!("strain_rate_viscosity_2d.glsl")).expect("Couldn't create ComputeShader.");
+        let compute_program = gpu::ComputeProgram::new(&context.context, &compute_shader).expect("Couldn't create ComputeProgram.");
+        Self { compute_program }
+    }
+
+    pub fn update(&self, model: &ViscosityModel, viscosity_field: &mut gpu::Texture2D, velocity_field: &gpu::Texture2D) {
+        const VISCOSITY_FIELD_LOCATION : usize = 0;
+        const VELOCITY_FIELD_LOCATION  : usize = 1;
+        const MODEL_LOCATION           : usize = 2;
+        const PARAMETERS_LOCATION      : usize = 3;
+        const VISCOSITY_RANGE_LOCATION : usize = 4;
+        let (model, parameters, viscosity_range) = match *model {
+            ViscosityModel::Newtonian => return,
+            ViscosityModel::PowerLaw { consistency, flow_index, min_viscosity, max_viscosity } => (1, (consistency, flow_index), (min_viscosity, max_viscosity)),
+            ViscosityModel::Bingham { plastic_viscosity, yield_stress, max_viscosity } => (2, (plastic_viscosity, yield_stress), (plastic_viscosity, max_viscosity))
+        };
+        let dimensions = viscosity_field.dimensions();
+        let dimensions = (dimensions.0, dimensions.1, 1);
+        self.compute_program.bind_image_2d(viscosity_field, VISCOSITY_FIELD_LOCATION);
+        self.compute_program.bind_image_2d(velocity_field, VELOCITY_FIELD_LOCATION);
+        self.compute_program.bind_i32(model, MODEL_LOCATION);
+        self.compute_program.bind_vec2(parameters, PARAMETERS_LOCATION);
+        self.compute_program.bind_vec2(viscosity_range, VISCOSITY_RANGE_LOCATION);
+        self.compute_program.compute(dimensions);
+        //FIXME: How to expose it on the GPU API?
+        // Ref: https://www.khronos.org/registry/OpenGL-Refpages/gl4/html/glMemoryBarrier.xhtml
+        unsafe {
+            gl::MemoryBarrier(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::context::Context;
+    use crate::field::Field;
+    use crate::fluid::ViscosityModel;
+    use crate::simulator::viscosity_updater::ViscosityUpdater;
+
+    fn field(context: &Context, dimensions: (usize, usize), data: &[f32], components: usize) -> gpu::Texture2D {
+        Field::from_data(context, dimensions, components, data).field
+    }
+
+    /// The viscosities of the inner rows of a simple shear flow, u = shear_rate * y.
+    fn shear_viscosities(model: &ViscosityModel, shear_rate: f32) -> Vec<f32> {
+        let dimensions = (5, 5);
+        let context = Context::new(dimensions);
+        let updater = ViscosityUpdater::new(&context);
+        let velocity_data: Vec<f32> = (0 .. dimensions.0 * dimensions.1).flat_map(|index| vec![shear_rate * (index / dimensions.0) as f32, 0.0]).collect();
+        let velocity_field = field(&context, dimensions, &velocity_data, 2);
+        let mut viscosity_field = field(&context, dimensions, &[-1.0; 25], 1);
+        updater.update(model, &mut viscosity_field, &velocity_field);
+        let data: Vec<f32> = viscosity_field.data();
+        data[dimensions.0 .. dimensions.0 * (dimensions.1 - 1)].to_vec()
+    }
+
+    #[test]
+    fn power_law() {
+        // 2 * 4^(0.5 - 1) = 1.
+        let model = ViscosityModel::PowerLaw { consistency: 2.0, flow_index: 0.5, min_viscosity: 0.1, max_viscosity: 10.0 };
+        assert!(shear_viscosities(&model, 4.0).iter().all(|viscosity| (viscosity - 1.0).abs() < 1e-5));
+        // Shear-thinning, so a fluid at rest is as thick as allowed.
+        assert!(shear_viscosities(&model, 0.0).iter().all(|viscosity| *viscosity == 10.0));
+        let model = ViscosityModel::PowerLaw { consistency: 2.0, flow_index: 2.0, min_viscosity: 0.1, max_viscosity: 10.0 };
+        assert!(shear_viscosities(&model, 4.0).iter().all(|viscosity| (viscosity - 8.0).abs() < 1e-5));
+        assert!(shear_viscosities(&model, 0.0).iter().all(|viscosity| *viscosity == 0.1));
+    }
+
+    #[test]
+    fn bingham() {
+        // 0.5 + 2 / 4 = 1.
+        let model = ViscosityModel::Bingham { plastic_viscosity: 0.5, yield_stress: 2.0, max_viscosity: 10.0 };
+        assert!(shear_viscosities(&model, 4.0).iter().all(|viscosity| (viscosity - 1.0).abs() < 1e-5));
+        // Under the yield stress it behaves like a solid.
+        assert!(shear_viscosities(&model, 0.0).iter().all(|viscosity| *viscosity == 10.0));
+        assert!(shear_viscosities(&model, 1000.0).iter().all(|viscosity| (viscosity - 0.502).abs() < 1e-5));
+    }
+
+    #[test]
+    fn newtonian_keeps_the_field() {
+        assert!(shear_viscosities(&ViscosityModel::Newtonian, 4.0).iter().all(|viscosity| *viscosity == -1.0));
+    }
+}
@@ -0,0 +1,36 @@
+#version 450
+
+layout(r32f, location = 0) uniform image2D viscosityField;
+layout(rg32f, location = 1) uniform image2D velocityField;
+layout(location = 2) uniform int model;
+// Power law: (consistency, flowIndex). Bingham: (plasticViscosity, yieldStress).
+layout(location = 3) uniform vec2 parameters;
+layout(location = 4) uniform vec2 viscosityRange;
+
+layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+#define POWER_LAW_MODEL 1
+#define BINGHAM_MODEL 2
+
+vec2 clampedLoad(ivec2 coordinate) {
+    coordinate = clamp(coordinate, ivec2(0), imageSize(velocityField) - ivec2(1));
+    return imageLoad(velocityField, coordinate).xy;
+}
+
+void main() {
+    ivec2 coordinate = ivec2(gl_GlobalInvocationID.xy);
+    vec2 dx = (clampedLoad(coordinate + ivec2(1, 0)) - clampedLoad(coordinate - ivec2(1, 0))) * 0.5;
+    vec2 dy = (clampedLoad(coordinate + ivec2(0, 1)) - clampedLoad(coordinate - ivec2(0, 1))) * 0.5;
+    // Magnitude of the strain rate tensor: sqrt(2 (du/dx^2 + dv/dy^2) + (du/dy + dv/dx)^2).
+    float shear = dy.x + dx.y;
+    float strainRate = sqrt(2.0 * (dx.x * dx.x + dy.y * dy.y) + shear * shear);
+
+    float viscosity;
+    if (model == POWER_LAW_MODEL) {
+        viscosity = parameters.x * pow(max(strainRate, 1e-6), parameters.y - 1.0);
+    } else {
+        viscosity = parameters.x + parameters.y / max(strainRate, 1e-6);
+    }
+    viscosity = clamp(viscosity, viscosityRange.x, viscosityRange.y);
+    imageStore(viscosityField, coordinate, vec4(viscosity));
+}
@@ -0,0 +1,297 @@
+//! Versioned binary snapshots of the full `Fluid` state.
+//!
+//! Layout, little-endian: the magic `FLUIDSNP`, the format version, the dimensions, the parameters,
+//! then the fields as a name, a component count and their zlib compressed `f32` data.
+
+use crate::context::Context;
+use crate::fluid::{Fluid, ViscosityModel};
+use std::io::{Error, ErrorKind, Read, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 8] = b"FLUIDSNP";
+pub const VERSION: u32 = 1;
+
+/// A field read back from its texture, interleaved and bottom row first.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldData {
+    pub name: String,
+    pub components: usize,
+    pub data: Vec<f32>
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Parameters {
+    pub viscosity: f32,
+    pub diffusion: f32,
+    pub density_dissipation: f32,
+    pub velocity_dissipation: f32,
+    pub temperature_dissipation: f32,
+    pub viscosity_model: ViscosityModel
+}
+
+/// The CPU side of a snapshot.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    pub dimensions: (usize, usize),
+    pub parameters: Parameters,
+    pub fields: Vec<FieldData>
+}
+
+fn invalid(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+fn read_u32(reader: &mut impl Read) -> std::io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(reader: &mut impl Read) -> std::io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_f32(reader: &mut impl Read) -> std::io::Result<f32> {
+    Ok(f32::from_bits(read_u32(reader)?))
+}
+
+/// Reads `length` bytes. The lengths come from the file, so the buffer only grows with the bytes actually read.
+fn read_bytes(reader: &mut impl Read, length: u64) -> std::io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    reader.take(length).read_to_end(&mut bytes)?;
+    if (bytes.len() as u64) < length {
+        return Err(invalid(format!("The snapshot is truncated, {} bytes are missing.", length - bytes.len() as u64)));
+    }
+    Ok(bytes)
+}
+
+fn write_viscosity_model(writer: &mut impl Write, model: &ViscosityModel) -> std::io::Result<()> {
+    let (tag, parameters) = match *model {
+        ViscosityModel::Newtonian => (0u32, [0.0; 4]),
+        ViscosityModel::PowerLaw { consistency, flow_index, min_viscosity, max_viscosity } => (1, [consistency, flow_index, min_viscosity, max_viscosity]),
+        ViscosityModel::Bingham { plastic_viscosity, yield_stress, max_viscosity } => (2, [plastic_viscosity, yield_stress, max_viscosity, 0.0])
+    };
+    writer.write_all(&tag.to_le_bytes())?;
+    for parameter in &parameters {
+        writer.write_all(&parameter.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn read_viscosity_model(reader: &mut impl Read) -> std::io::Result<ViscosityModel> {
+    let tag = read_u32(reader)?;
+    let mut p = [0.0; 4];
+    for parameter in &mut p {
+        *parameter = read_f32(reader)?;
+    }
+    match tag {
+        0 => Ok(ViscosityModel::Newtonian),
+        1 => Ok(ViscosityModel::PowerLaw { consistency: p[0], flow_index: p[1], min_viscosity: p[2], max_viscosity: p[3] }),
+        2 => Ok(ViscosityModel::Bingham { plastic_viscosity: p[0], yield_stress: p[1], max_viscosity: p[2] }),
+        _ => Err(invalid(format!("Unknown viscosity model {}.", tag)))
+    }
+}
+
+impl Snapshot {
+    /// Reads the fields back from the GPU.
+    pub fn from_fluid(fluid: &Fluid) -> Self {
+        let field = |name: &str, components: usize, texture: &gpu::Texture2D| FieldData { name: name.to_string(), components, data: texture.data() };
+        let mut fields = vec![
+            field("velocity", 2, &fluid.velocity_field),
+            field("previous_velocity", 2, &fluid.previous_velocity_field),
+            field("density", 1, &fluid.density_field),
+            field("previous_density", 1, &fluid.previous_density_field),
+            field("temperature", 1, &fluid.temperature_field),
+            field("previous_temperature", 1, &fluid.previous_temperature_field),
+            field("obstacle", 1, &fluid.obstacle_field)
+        ];
+        let optional_fields = [("damping_mask", &fluid.damping_mask), ("viscosity", &fluid.viscosity_field), ("level_set", &fluid.level_set_field)];
+        for (name, texture) in optional_fields.iter() {
+            if let Some(texture) = texture {
+                fields.push(field(name, 1, texture));
+            }
+        }
+        let parameters = Parameters {
+            viscosity: fluid.viscosity,
+            diffusion: fluid.diffusion,
+            density_dissipation: fluid.density_dissipation,
+            velocity_dissipation: fluid.velocity_dissipation,
+            temperature_dissipation: fluid.temperature_dissipation,
+            viscosity_model: fluid.viscosity_model
+        };
+        Self { dimensions: fluid.dimensions, parameters, fields }
+    }
+
+    pub fn field(&self, name: &str) -> Option<&FieldData> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    /// Uploads the snapshot to a new `Fluid`.
+    pub fn to_fluid(&self, context: &Context) -> std::io::Result<Fluid> {
+        let upload = |name: &str, components: usize| -> std::io::Result<Option<gpu::Texture2D>> {
+            let field = match self.field(name) {
+                Some(field) => field,
+                None => return Ok(None)
+            };
+            if field.components != components {
+                return Err(invalid(format!("The {} field has {} components instead of {}.", name, field.components, components)));
+            }
+            let format = gpu::TextureFormat::new(gpu::ColorFormat::components(components), gpu::Type::F32);
+            Ok(Some(gpu::Texture2D::from_data(&context.context, self.dimensions, &format, &field.data, &format)))
+        };
+        let required = |name: &str, components: usize| upload(name, components)?.ok_or_else(|| invalid(format!("The {} field is missing.", name)));
+        let mut fluid = Fluid::new(context, self.dimensions, self.parameters.diffusion, self.parameters.viscosity);
+        fluid.velocity_field = required("velocity", 2)?;
+        fluid.previous_velocity_field = required("previous_velocity", 2)?;
+        fluid.density_field = required("density", 1)?;
+        fluid.previous_density_field = required("previous_density", 1)?;
+        fluid.obstacle_field = required("obstacle", 1)?;
+        fluid.temperature_field = required("temperature", 1)?;
+        fluid.previous_temperature_field = required("previous_temperature", 1)?;
+        fluid.damping_mask = upload("damping_mask", 1)?;
+        fluid.viscosity_field = upload("viscosity", 1)?;
+        fluid.level_set_field = upload("level_set", 1)?;
+        fluid.density_dissipation = self.parameters.density_dissipation;
+        fluid.velocity_dissipation = self.parameters.velocity_dissipation;
+        fluid.temperature_dissipation = self.parameters.temperature_dissipation;
+        fluid.viscosity_model = self.parameters.viscosity_model;
+        Ok(fluid)
+    }
+
+    pub fn write(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&(self.dimensions.0 as u32).to_le_bytes())?;
+        writer.write_all(&(self.dimensions.1 as u32).to_le_bytes())?;
+        let parameters = &self.parameters;
+        for parameter in &[parameters.viscosity, parameters.diffusion, parameters.density_dissipation, parameters.velocity_dissipation, parameters.temperature_dissipation] {
+            writer.write_all(&parameter.to_le_bytes())?;
+        }
+        write_viscosity_model(writer, &parameters.viscosity_model)?;
+        writer.write_all(&(self.fields.len() as u32).to_le_bytes())?;
+        for field in &self.fields {
+            writer.write_all(&(field.name.len() as u32).to_le_bytes())?;
+            writer.write_all(field.name.as_bytes())?;
+            writer.write_all(&(field.components as u32).to_le_bytes())?;
+            let bytes: Vec<u8> = field.data.iter().flat_map(|value| value.to_le_bytes().to_vec()).collect();
+            let compressed = miniz_oxide::deflate::compress_to_vec_zlib(&bytes, 6);
+            writer.write_all(&(compressed.len() as u64).to_le_bytes())?;
+            writer.write_all(&compressed)?;
+        }
+        Ok(())
+    }
+
+    pub fn read(reader: &mut impl Read) -> std::io::Result<Self> {
+        if &read_bytes(reader, MAGIC.len() as u64)?[..] != MAGIC {
+            return Err(invalid("Not a fluid snapshot.".to_string()));
+        }
+        let version = read_u32(reader)?;
+        if version != VERSION {
+            return Err(invalid(format!("Unsupported snapshot version {}, expected {}.", version, VERSION)));
+        }
+        let dimensions = (read_u32(reader)? as usize, read_u32(reader)? as usize);
+        let viscosity = read_f32(reader)?;
+        let diffusion = read_f32(reader)?;
+        let density_dissipation = read_f32(reader)?;
+        let velocity_dissipation = read_f32(reader)?;
+        let temperature_dissipation = read_f32(reader)?;
+        let viscosity_model = read_viscosity_model(reader)?;
+        let parameters = Parameters { viscosity, diffusion, density_dissipation, velocity_dissipation, temperature_dissipation, viscosity_model };
+        let count = read_u32(reader)?;
+        let mut fields = Vec::new();
+        for _ in 0 .. count {
+            let name_length = read_u32(reader)?;
+            let name = String::from_utf8(read_bytes(reader, name_length as u64)?).map_err(|_| invalid("A field name isn't UTF-8.".to_string()))?;
+            let components = read_u32(reader)? as usize;
+            let length = dimensions.0.checked_mul(dimensions.1).and_then(|cells| cells.checked_mul(components)).and_then(|values| values.checked_mul(4))
+                .ok_or_else(|| invalid(format!("The {} field is too large.", name)))?;
+            let compressed_length = read_u64(reader)?;
+            let compressed = read_bytes(reader, compressed_length)?;
+            // The limit stops the decompression of a corrupted field at the expected size.
+            let bytes = miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(&compressed, length).map_err(|_| invalid(format!("The {} field is corrupted or doesn't match the dimensions.", name)))?;
+            if bytes.len() != length {
+                return Err(invalid(format!("The {} field doesn't match the dimensions.", name)));
+            }
+            let data = bytes.chunks(4).map(|value| f32::from_le_bytes([value[0], value[1], value[2], value[3]])).collect();
+            fields.push(FieldData { name, components, data });
+        }
+        Ok(Self { dimensions, parameters, fields })
+    }
+}
+
+pub fn save(fluid: &Fluid, path: impl AsRef<Path>) -> std::io::Result<()> {
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+    Snapshot::from_fluid(fluid).write(&mut writer)?;
+    writer.flush()
+}
+
+pub fn load(context: &Context, path: impl AsRef<Path>) -> std::io::Result<Fluid> {
+    let mut reader = std::io::BufReader::new(std::fs::File::open(path)?);
+    Snapshot::read(&mut reader)?.to_fluid(context)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn snapshot() -> Snapshot {
+        let parameters = Parameters {
+            viscosity: 0.1,
+            diffusion: 1.0,
+            density_dissipation: 0.5,
+            velocity_dissipation: 0.0,
+            temperature_dissipation: 0.25,
+            viscosity_model: ViscosityModel::Bingham { plastic_viscosity: 1.0, yield_stress: 2.0, max_viscosity: 3.0 }
+        };
+        let fields = vec![
+            FieldData { name: "velocity".to_string(), components: 2, data: (0 .. 12).map(|value| value as f32 * 0.5).collect() },
+            FieldData { name: "density".to_string(), components: 1, data: vec![1.0, -2.0, 3.5, std::f32::MAX, 0.0, -0.0] }
+        ];
+        Snapshot { dimensions: (3, 2), parameters, fields }
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut bytes = Vec::new();
+        snapshot().write(&mut bytes).unwrap();
+        assert_eq!(&bytes[.. 8], MAGIC);
+        let read = Snapshot::read(&mut &bytes[..]).unwrap();
+        assert_eq!(read, snapshot());
+    }
+
+    #[test]
+    fn version_mismatch() {
+        let mut bytes = Vec::new();
+        snapshot().write(&mut bytes).unwrap();
+        bytes[8] = 99;
+        let error = Snapshot::read(&mut &bytes[..]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn corrupted_lengths() {
+        let mut bytes = Vec::new();
+        snapshot().write(&mut bytes).unwrap();
+        // The field count, then the name length, the name and the components of the velocity, then its compressed length.
+        let (count, compressed_length) = (60, 80);
+        assert_eq!(&bytes[68 .. 76], b"velocity");
+
+        let mut corrupted = bytes.clone();
+        corrupted[count .. count + 4].copy_from_slice(&std::u32::MAX.to_le_bytes());
+        assert!(Snapshot::read(&mut &corrupted[..]).is_err());
+
+        let mut corrupted = bytes.clone();
+        corrupted[compressed_length .. compressed_length + 8].copy_from_slice(&std::u64::MAX.to_le_bytes());
+        assert_eq!(Snapshot::read(&mut &corrupted[..]).unwrap_err().kind(), ErrorKind::InvalidData);
+
+        // The fields can't be larger than the dimensions.
+        let mut corrupted = bytes.clone();
+        corrupted[12 .. 20].copy_from_slice(&[0xff; 8]);
+        assert_eq!(Snapshot::read(&mut &corrupted[..]).unwrap_err().kind(), ErrorKind::InvalidData);
+        let mut corrupted = bytes;
+        corrupted[12 .. 16].copy_from_slice(&1u32.to_le_bytes());
+        assert_eq!(Snapshot::read(&mut &corrupted[..]).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,437 @@
+//! Streamlines of a velocity field and pathlines through recorded velocity fields.
+//! The fields are read back from the GPU and integrated on the CPU with an adaptive Dormand-Prince RK45.
+
+use std::io::Write;
+
+/// A velocity field read back from a `gpu::Texture2D`, sampled bilinearly. Cell centers are on integer coordinates.
+pub struct VelocitySampler {
+    data: Vec<f32>,
+    dimensions: (usize, usize)
+}
+
+impl VelocitySampler {
+    /// `data` is interleaved (x, y) velocities, row by row.
+    pub fn new(data: Vec<f32>, dimensions: (usize, usize)) -> Self {
+        assert_eq!(data.len(), dimensions.0 * dimensions.1 * 2, "The velocity data must have two components per cell.");
+        Self { data, dimensions }
+    }
+
+    pub fn from_field(velocity_field: &gpu::Texture2D) -> Self {
+        Self::new(velocity_field.data(), velocity_field.dimensions())
+    }
+
+    pub fn dimensions(&self) -> (usize, usize) {
+        self.dimensions
+    }
+
+    pub fn is_inside(&self, position: (f32, f32)) -> bool {
+        position.0 >= 0.0 && position.1 >= 0.0 && position.0 <= (self.dimensions.0 - 1) as f32 && position.1 <= (self.dimensions.1 - 1) as f32
+    }
+
+    fn at(&self, x: isize, y: isize) -> (f32, f32) {
+        let x = x.max(0).min(self.dimensions.0 as isize - 1) as usize;
+        let y = y.max(0).min(self.dimensions.1 as isize - 1) as usize;
+        let index = (y * self.dimensions.0 + x) * 2;
+        (self.data[index], self.data[index + 1])
+    }
+
+    pub fn sample(&self, position: (f32, f32)) -> (f32, f32) {
+        let (x, y) = (position.0.floor(), position.1.floor());
+        let (tx, ty) = (position.0 - x, position.1 - y);
+        let (x, y) = (x as isize, y as isize);
+        let lerp = |a: (f32, f32), b: (f32, f32), t: f32| (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t);
+        let bottom = lerp(self.at(x, y), self.at(x + 1, y), tx);
+        let top = lerp(self.at(x, y + 1), self.at(x + 1, y + 1), tx);
+        lerp(bottom, top, ty)
+    }
+}
+
+/// Velocity fields recorded over time, for the pathlines.
+#[derive(Default)]
+pub struct VelocityHistory {
+    frames: Vec<(f32, VelocitySampler)>
+}
+
+impl VelocityHistory {
+    pub fn new() -> Self {
+        Self { frames: Vec::new() }
+    }
+
+    /// Records the velocity at `time`. The frames must be recorded in increasing time.
+    pub fn record(&mut self, time: f32, velocity_field: &gpu::Texture2D) {
+        self.push(time, VelocitySampler::from_field(velocity_field));
+    }
+
+    pub fn push(&mut self, time: f32, sampler: VelocitySampler) {
+        if let Some((last_time, _)) = self.frames.last() {
+            assert!(time > *last_time, "The frames must be recorded in increasing time.");
+        }
+        self.frames.push((time, sampler));
+    }
+
+    pub fn time_range(&self) -> Option<(f32, f32)> {
+        Some((self.frames.first()?.0, self.frames.last()?.0))
+    }
+
+    pub fn is_inside(&self, position: (f32, f32)) -> bool {
+        self.frames.first().map(|(_, sampler)| sampler.is_inside(position)).unwrap_or(false)
+    }
+
+    /// Samples the velocity at `position`, linearly interpolated between the frames around `time`.
+    /// An empty history has no velocity.
+    pub fn sample(&self, time: f32, position: (f32, f32)) -> (f32, f32) {
+        if self.frames.is_empty() {
+            return (0.0, 0.0);
+        }
+        let next = self.frames.iter().position(|(frame_time, _)| *frame_time >= time).unwrap_or(self.frames.len() - 1);
+        if next == 0 {
+            return self.frames[0].1.sample(position);
+        }
+        let (previous_time, previous) = &self.frames[next - 1];
+        let (next_time, next) = &self.frames[next];
+        let t = ((time - previous_time) / (next_time - previous_time)).clamp(0.0, 1.0);
+        let (a, b) = (previous.sample(position), next.sample(position));
+        (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    Forward,
+    Backward,
+    Both
+}
+
+/// Why an integration stopped.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StopReason {
+    LeftDomain,
+    Stagnation,
+    MaxLength,
+    MaxSteps,
+    EndOfHistory
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct IntegrationOptions {
+    /// Maximum local error per step, in cells.
+    pub tolerance: f32,
+    pub initial_step: f32,
+    pub min_step: f32,
+    pub max_step: f32,
+    /// Maximum polyline length, in cells. With `Direction::Both` the two halves share it, the backward one first.
+    pub max_length: f32,
+    pub max_steps: usize,
+    /// Speeds under it stop the streamlines.
+    pub stagnation_speed: f32,
+    pub direction: Direction
+}
+
+impl Default for IntegrationOptions {
+    fn default() -> Self {
+        Self {
+            tolerance: 1e-3,
+            initial_step: 0.1,
+            min_step: 1e-4,
+            max_step: 10.0,
+            max_length: 1000.0,
+            max_steps: 10000,
+            stagnation_speed: 1e-4,
+            direction: Direction::Forward
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Polyline {
+    /// Positions in cell coordinates.
+    pub points: Vec<(f32, f32)>,
+    /// Integration times of the points. Pseudo times for the streamlines, simulation times for the pathlines.
+    pub times: Vec<f32>,
+    pub stop_reason: StopReason
+}
+
+impl Polyline {
+    pub fn length(&self) -> f32 {
+        self.points.windows(2).map(|segment| distance(segment[0], segment[1])).sum()
+    }
+
+    /// Writes the points as `time,x,y` lines.
+    pub fn write_csv(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        writeln!(writer, "time,x,y")?;
+        for (time, point) in self.times.iter().zip(&self.points) {
+            writeln!(writer, "{},{},{}", time, point.0, point.1)?;
+        }
+        Ok(())
+    }
+}
+
+fn distance(a: (f32, f32), b: (f32, f32)) -> f32 {
+    ((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2)).sqrt()
+}
+
+// Dormand-Prince 5(4) tableau.
+const C: [f32; 7] = [0.0, 1.0 / 5.0, 3.0 / 10.0, 4.0 / 5.0, 8.0 / 9.0, 1.0, 1.0];
+const A: [[f32; 6]; 7] = [
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [1.0 / 5.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [3.0 / 40.0, 9.0 / 40.0, 0.0, 0.0, 0.0, 0.0],
+    [44.0 / 45.0, -56.0 / 15.0, 32.0 / 9.0, 0.0, 0.0, 0.0],
+    [19372.0 / 6561.0, -25360.0 / 2187.0, 64448.0 / 6561.0, -212.0 / 729.0, 0.0, 0.0],
+    [9017.0 / 3168.0, -355.0 / 33.0, 46732.0 / 5247.0, 49.0 / 176.0, -5103.0 / 18656.0, 0.0],
+    [35.0 / 384.0, 0.0, 500.0 / 1113.0, 125.0 / 192.0, -2187.0 / 6784.0, 11.0 / 84.0]
+];
+const B5: [f32; 7] = [35.0 / 384.0, 0.0, 500.0 / 1113.0, 125.0 / 192.0, -2187.0 / 6784.0, 11.0 / 84.0, 0.0];
+const B4: [f32; 7] = [5179.0 / 57600.0, 0.0, 7571.0 / 16695.0, 393.0 / 640.0, -92097.0 / 339200.0, 187.0 / 2100.0, 1.0 / 40.0];
+
+/// One Dormand-Prince step. Returns the fifth order solution and the error estimate.
+fn rk45_step(velocity: &impl Fn(f32, (f32, f32)) -> (f32, f32), time: f32, position: (f32, f32), step: f32) -> ((f32, f32), f32) {
+    let mut k = [(0.0, 0.0); 7];
+    for stage in 0 .. 7 {
+        let mut point = position;
+        for previous in 0 .. stage {
+            point.0 += step * A[stage][previous] * k[previous].0;
+            point.1 += step * A[stage][previous] * k[previous].1;
+        }
+        k[stage] = velocity(time + C[stage] * step, point);
+    }
+    let (mut fifth, mut fourth) = (position, position);
+    for stage in 0 .. 7 {
+        fifth.0 += step * B5[stage] * k[stage].0;
+        fifth.1 += step * B5[stage] * k[stage].1;
+        fourth.0 += step * B4[stage] * k[stage].0;
+        fourth.1 += step * B4[stage] * k[stage].1;
+    }
+    (fifth, distance(fifth, fourth))
+}
+
+/// The point where the segment from `inside` to `outside` crosses the border of a field of `dimensions` cells, and its fraction of the segment.
+fn clip(inside: (f32, f32), outside: (f32, f32), dimensions: (usize, usize)) -> ((f32, f32), f32) {
+    let maximum = ((dimensions.0 - 1) as f32, (dimensions.1 - 1) as f32);
+    // The fraction of the segment before each coordinate leaves the field.
+    let fraction = |from: f32, to: f32, maximum: f32| if to < 0.0 { from / (from - to) } else if to > maximum { (maximum - from) / (to - from) } else { 1.0 };
+    let fractions = (fraction(inside.0, outside.0, maximum.0), fraction(inside.1, outside.1, maximum.1));
+    let t = fractions.0.min(fractions.1);
+    let mut point = (inside.0 + (outside.0 - inside.0) * t, inside.1 + (outside.1 - inside.1) * t);
+    // The crossed border is set exactly, without the rounding errors.
+    if fractions.0 <= fractions.1 {
+        point.0 = if outside.0 < 0.0 { 0.0 } else { maximum.0 };
+    } else {
+        point.1 = if outside.1 < 0.0 { 0.0 } else { maximum.1 };
+    }
+    ((point.0.max(0.0).min(maximum.0), point.1.max(0.0).min(maximum.1)), t)
+}
+
+/// Integrates `dp/dt = velocity(t, p)` from `seed` at `start_time`, backwards in time for `Direction::Backward`, until a stop criterion is met.
+/// The polylines leaving the field of `dimensions` cells end on its border.
+/// The forward steps are shortened to land on the `breakpoints`, where the velocity isn't smooth in time.
+fn integrate(velocity: impl Fn(f32, (f32, f32)) -> (f32, f32), is_inside: impl Fn(f32, (f32, f32)) -> Option<StopReason>, dimensions: (usize, usize), seed: (f32, f32), start_time: f32, breakpoints: &[f32], options: &IntegrationOptions) -> Polyline {
+    let sign = if options.direction == Direction::Backward { -1.0 } else { 1.0 };
+    let mut points = vec![seed];
+    let mut times = vec![start_time];
+    let mut position = seed;
+    let mut time = start_time;
+    let mut step = options.initial_step;
+    let mut length = 0.0;
+    for _ in 0 .. options.max_steps {
+        if let Some(stop_reason) = is_inside(time, position) {
+            return Polyline { points, times, stop_reason };
+        }
+        let speed = { let velocity = velocity(time, position); (velocity.0 * velocity.0 + velocity.1 * velocity.1).sqrt() };
+        if speed < options.stagnation_speed {
+            return Polyline { points, times, stop_reason: StopReason::Stagnation };
+        }
+        if let Some(breakpoint) = breakpoints.iter().find(|breakpoint| **breakpoint > time) {
+            step = step.min(breakpoint - time);
+        }
+        // The sign is only applied here: a negative step goes back in time and against the velocity.
+        let (next, error) = rk45_step(&velocity, time, position, step * sign);
+        // Standard step size controller, with the error of a fourth order method.
+        let factor = (0.9 * (options.tolerance / error.max(1e-12)).powf(0.2)).clamp(0.2, 5.0);
+        if error > options.tolerance && step > options.min_step {
+            step = (step * factor).max(options.min_step);
+            continue;
+        }
+        let left_domain = is_inside(time + step * sign, next) == Some(StopReason::LeftDomain);
+        let (next, fraction) = if left_domain { clip(position, next, dimensions) } else { (next, 1.0) };
+        let segment = distance(position, next);
+        if length + segment > options.max_length {
+            // The last point is cut so the polyline has exactly max_length.
+            let t = (options.max_length - length) / segment;
+            points.push((position.0 + (next.0 - position.0) * t, position.1 + (next.1 - position.1) * t));
+            times.push(time + step * sign * fraction * t);
+            return Polyline { points, times, stop_reason: StopReason::MaxLength };
+        }
+        length += segment;
+        position = next;
+        time += step * sign * fraction;
+        points.push(position);
+        times.push(time);
+        if left_domain {
+            return Polyline { points, times, stop_reason: StopReason::LeftDomain };
+        }
+        step = (step * factor).clamp(options.min_step, options.max_step);
+    }
+    Polyline { points, times, stop_reason: StopReason::MaxSteps }
+}
+
+fn join(backward: Polyline, forward: Polyline) -> Polyline {
+    let mut points: Vec<(f32, f32)> = backward.points.into_iter().rev().collect();
+    let mut times: Vec<f32> = backward.times.into_iter().rev().collect();
+    points.extend(forward.points.into_iter().skip(1));
+    times.extend(forward.times.into_iter().skip(1));
+    Polyline { points, times, stop_reason: forward.stop_reason }
+}
+
+/// Integrates the streamline through `seed` of the frozen `sampler` velocity.
+pub fn streamline(sampler: &VelocitySampler, seed: (f32, f32), options: &IntegrationOptions) -> Polyline {
+    let velocity = |_: f32, position: (f32, f32)| sampler.sample(position);
+    let is_inside = |_: f32, position: (f32, f32)| if sampler.is_inside(position) { None } else { Some(StopReason::LeftDomain) };
+    let dimensions = sampler.dimensions();
+    match options.direction {
+        Direction::Forward | Direction::Backward => integrate(velocity, is_inside, dimensions, seed, 0.0, &[], options),
+        Direction::Both => {
+            let backward = integrate(velocity, is_inside, dimensions, seed, 0.0, &[], &IntegrationOptions { direction: Direction::Backward, ..*options });
+            let forward = if backward.stop_reason == StopReason::MaxLength {
+                Polyline { points: vec![seed], times: vec![0.0], stop_reason: StopReason::MaxLength }
+            } else {
+                let max_length = options.max_length - backward.length();
+                integrate(velocity, is_inside, dimensions, seed, 0.0, &[], &IntegrationOptions { direction: Direction::Forward, max_length, ..*options })
+            };
+            join(backward, forward)
+        }
+    }
+}
+
+pub fn streamlines(sampler: &VelocitySampler, seeds: &[(f32, f32)], options: &IntegrationOptions) -> Vec<Polyline> {
+    seeds.iter().map(|seed| streamline(sampler, *seed, options)).collect()
+}
+
+/// Integrates the path of a particle released at `seed` on `start_time` through the recorded `history`.
+/// The stagnation criterion doesn't apply, a particle can wait for the flow to change.
+pub fn pathline(history: &VelocityHistory, seed: (f32, f32), start_time: f32, options: &IntegrationOptions) -> Polyline {
+    let (first_time, last_time) = match history.time_range() {
+        Some(range) => range,
+        None => return Polyline { points: vec![seed], times: vec![start_time], stop_reason: StopReason::EndOfHistory }
+    };
+    let velocity = |time: f32, position: (f32, f32)| history.sample(time, position);
+    let is_inside = |time: f32, position: (f32, f32)| {
+        if !history.is_inside(position) { Some(StopReason::LeftDomain) }
+        else if time >= last_time || time < first_time { Some(StopReason::EndOfHistory) }
+        else { None }
+    };
+    let breakpoints: Vec<f32> = history.frames.iter().map(|(time, _)| *time).collect();
+    let dimensions = history.frames[0].1.dimensions();
+    let options = IntegrationOptions { stagnation_speed: 0.0, direction: Direction::Forward, ..*options };
+    integrate(velocity, is_inside, dimensions, seed, start_time, &breakpoints, &options)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn uniform(dimensions: (usize, usize), velocity: (f32, f32)) -> VelocitySampler {
+        let data = (0 .. dimensions.0 * dimensions.1).flat_map(|_| vec![velocity.0, velocity.1]).collect();
+        VelocitySampler::new(data, dimensions)
+    }
+
+    fn rotation(dimensions: (usize, usize), center: (f32, f32)) -> VelocitySampler {
+        let mut data = Vec::new();
+        for y in 0 .. dimensions.1 {
+            for x in 0 .. dimensions.0 {
+                data.push(-(y as f32 - center.1));
+                data.push(x as f32 - center.0);
+            }
+        }
+        VelocitySampler::new(data, dimensions)
+    }
+
+    #[test]
+    fn uniform_flow_leaves_the_domain() {
+        let sampler = uniform((16, 16), (1.0, 0.0));
+        let line = streamline(&sampler, (2.0, 8.0), &IntegrationOptions::default());
+        assert_eq!(line.stop_reason, StopReason::LeftDomain);
+        assert!(line.points.iter().all(|point| (point.1 - 8.0).abs() < 1e-4));
+        assert_eq!(line.points.last().unwrap().0, 15.0);
+    }
+
+    #[test]
+    fn backward_goes_upstream() {
+        let sampler = uniform((64, 64), (0.0, 2.0));
+        let options = IntegrationOptions { direction: Direction::Backward, ..Default::default() };
+        let line = streamline(&sampler, (32.0, 32.0), &options);
+        assert_eq!(line.stop_reason, StopReason::LeftDomain);
+        assert!(line.points.windows(2).all(|segment| segment[1].1 < segment[0].1));
+        // The last point is clipped to the border.
+        assert_eq!(*line.points.last().unwrap(), (32.0, 0.0));
+        assert!(line.times.last().unwrap() < &0.0);
+
+        // Both directions are joined upstream to downstream.
+        let options = IntegrationOptions { direction: Direction::Both, ..Default::default() };
+        let line = streamline(&sampler, (32.0, 32.0), &options);
+        assert_eq!((line.points[0], *line.points.last().unwrap()), ((32.0, 0.0), (32.0, 63.0)));
+        assert!(line.points.windows(2).all(|segment| segment[1].1 > segment[0].1));
+    }
+
+    #[test]
+    fn stagnation() {
+        let sampler = uniform((8, 8), (0.0, 0.0));
+        let line = streamline(&sampler, (4.0, 4.0), &IntegrationOptions::default());
+        assert_eq!(line.stop_reason, StopReason::Stagnation);
+        assert_eq!(line.points, vec![(4.0, 4.0)]);
+    }
+
+    #[test]
+    fn max_length() {
+        let sampler = uniform((64, 64), (0.0, 2.0));
+        let options = IntegrationOptions { max_length: 10.0, direction: Direction::Both, ..Default::default() };
+        let line = streamline(&sampler, (32.0, 32.0), &options);
+        assert_eq!(line.stop_reason, StopReason::MaxLength);
+        assert!((line.length() - 10.0).abs() < 1e-3);
+
+        // The backward half reaches the border after 4 cells, the forward half has the 6 left.
+        let line = streamline(&sampler, (32.0, 4.0), &options);
+        assert_eq!(line.stop_reason, StopReason::MaxLength);
+        assert!((line.length() - 10.0).abs() < 1e-3);
+        assert_eq!(line.points[0], (32.0, 0.0));
+        assert!((line.points[line.points.len() - 1].1 - 10.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn rotation_is_a_circle() {
+        // The bilinear interpolation of a linear field is exact, so the streamline is a circle.
+        let center = (16.0, 16.0);
+        let sampler = rotation((33, 33), center);
+        let options = IntegrationOptions { tolerance: 1e-5, max_steps: 200, ..Default::default() };
+        let line = streamline(&sampler, (24.0, 16.0), &options);
+        assert_eq!(line.stop_reason, StopReason::MaxSteps);
+        for point in &line.points {
+            assert!((distance(*point, center) - 8.0).abs() < 1e-2);
+        }
+        assert!(line.points.iter().any(|point| point.0 < 8.5));
+        assert!(line.points.iter().any(|point| point.1 > 23.5));
+    }
+
+    #[test]
+    fn pathline_follows_the_changing_flow() {
+        let mut history = VelocityHistory::new();
+        history.push(0.0, uniform((32, 32), (1.0, 0.0)));
+        history.push(4.0, uniform((32, 32), (1.0, 0.0)));
+        history.push(4.5, uniform((32, 32), (0.0, 1.0)));
+        history.push(8.0, uniform((32, 32), (0.0, 1.0)));
+        let line = pathline(&history, (4.0, 4.0), 0.0, &IntegrationOptions::default());
+        assert_eq!(line.stop_reason, StopReason::EndOfHistory);
+        let last = *line.points.last().unwrap();
+        // 4 cells right, the blend moves a quarter cell in each direction, then 3.5 cells up.
+        assert!(distance(last, (8.25, 7.75)) < 1e-2, "{:?}", last);
+    }
+
+    #[test]
+    fn empty_history() {
+        let history = VelocityHistory::new();
+        assert_eq!(history.sample(0.0, (4.0, 4.0)), (0.0, 0.0));
+        let line = pathline(&history, (4.0, 4.0), 0.0, &IntegrationOptions::default());
+        assert_eq!(line.stop_reason, StopReason::EndOfHistory);
+        assert_eq!(line.points, vec![(4.0, 4.0)]);
+    }
+}
@@ -0,0 +1,82 @@
+#version 450
+
+// One texel per tracer, row by row: (position.xy, age, alive).
+layout(rgba32f, location = 0) uniform image2D tracerField;
+// One block of tracer rows per trail point, laid out like the tracers. The block `head` has the latest positions.
+layout(rg32f, location = 1) uniform image2D trailField;
+layout(rg32f, location = 2) uniform image2D velocityField;
+layout(r32f, location = 3) uniform image2D obstacleField;
+// One texel per emitter: (minimum.xy, maximum.xy).
+layout(rgba32f, location = 4) uniform image2D emitterField;
+layout(location = 5) uniform int numberOfEmitters;
+layout(location = 6) uniform int head;
+layout(location = 7) uniform float deltaTime;
+layout(location = 8) uniform float maxAge;
+layout(location = 9) uniform int step;
+layout(location = 10) uniform int numberOfTracers;
+
+layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+float hash(uint value) {
+    value ^= value >> 16;
+    value *= 0x7feb352du;
+    value ^= value >> 15;
+    value *= 0x846ca68bu;
+    value ^= value >> 16;
+    return float(value) / 4294967295.0;
+}
+
+vec2 velocityAt(ivec2 coordinate) {
+    coordinate = clamp(coordinate, ivec2(0), imageSize(velocityField) - ivec2(1));
+    return imageLoad(velocityField, coordinate).xy;
+}
+
+vec2 bilinearVelocity(vec2 coordinate) {
+    vec2 interpolation = fract(coordinate);
+    ivec2 leftBottom = ivec2(floor(coordinate));
+    vec2 bottom = mix(velocityAt(leftBottom), velocityAt(leftBottom + ivec2(1, 0)), interpolation.x);
+    vec2 top    = mix(velocityAt(leftBottom + ivec2(0, 1)), velocityAt(leftBottom + ivec2(1, 1)), interpolation.x);
+    return mix(bottom, top, interpolation.y);
+}
+
+bool isInside(vec2 position) {
+    vec2 size = vec2(imageSize(velocityField) - ivec2(1));
+    if (any(lessThan(position, vec2(0.0))) || any(greaterThan(position, size))) return false;
+    return imageLoad(obstacleField, ivec2(round(position))).x <= 0.5;
+}
+
+void main() {
+    ivec2 tracerCoordinate = ivec2(gl_GlobalInvocationID.xy);
+    int index = tracerCoordinate.y * imageSize(tracerField).x + tracerCoordinate.x;
+    if (index >= numberOfTracers) return;
+
+    vec4 tracer = imageLoad(tracerField, tracerCoordinate);
+    vec2 position = tracer.xy;
+    float age = tracer.z;
+    bool alive = tracer.w > 0.5;
+    int rows = imageSize(tracerField).y;
+    int trailLength = imageSize(trailField).y / rows;
+
+    if (alive) {
+        // Midpoint (RK2) integration.
+        vec2 midpoint = position + 0.5 * deltaTime * bilinearVelocity(position);
+        position += deltaTime * bilinearVelocity(midpoint);
+        age += deltaTime;
+        alive = isInside(position) && (maxAge <= 0.0 || age < maxAge);
+        imageStore(trailField, tracerCoordinate + ivec2(0, head * rows), vec4(position, 0.0, 0.0));
+    }
+
+    if (!alive && numberOfEmitters > 0) {
+        uint seed = uint(index) * 3u + uint(step) * 7919u;
+        vec4 emitter = imageLoad(emitterField, ivec2(int(hash(seed) * float(numberOfEmitters)) % numberOfEmitters, 0));
+        position = mix(emitter.xy, emitter.zw, vec2(hash(seed + 1u), hash(seed + 2u)));
+        age = 0.0;
+        alive = true;
+        // A respawned tracer has no trail yet.
+        for (int row = 0; row < trailLength; row++) {
+            imageStore(trailField, tracerCoordinate + ivec2(0, row * rows), vec4(position, 0.0, 0.0));
+        }
+    }
+
+    imageStore(tracerField, tracerCoordinate, vec4(position, age, alive ? 1.0 : 0.0));
+}
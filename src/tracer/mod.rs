@@ -0,0 +1,238 @@
+use crate::context::Context;
+use crate::fluid::Fluid;
+
+/// A rectangular region where the tracers are respawned, e.g. an inflow.
+#[derive(Clone, Copy, Debug)]
+pub struct Emitter {
+    pub minimum: (f32, f32),
+    pub maximum: (f32, f32)
+}
+
+/// Massless particles moved by the velocity field, for visualization and measurement.
+/// The tracers leaving the domain, entering an obstacle or older than `max_age` are respawned on the emitters.
+pub struct Tracers {
+    advect_program: gpu::ComputeProgram,
+    pub tracer_field: gpu::Texture2D,
+    pub trail_field: gpu::Texture2D,
+    emitter_field: gpu::Texture2D,
+    number_of_emitters: usize,
+    pub count: usize,
+    pub trail_length: usize,
+    /// The trail row with the latest positions.
+    pub head: usize,
+    step: usize,
+    /// Zero means the tracers live until they leave the domain.
+    pub max_age: f32
+}
+
+impl Tracers {
+    const FIELD_LOCATION              : usize = 0;
+    const TRAIL_FIELD_LOCATION        : usize = 1;
+    const VELOCITY_FIELD_LOCATION     : usize = 2;
+    const OBSTACLE_FIELD_LOCATION     : usize = 3;
+    const EMITTER_FIELD_LOCATION      : usize = 4;
+    const NUMBER_OF_EMITTERS_LOCATION : usize = 5;
+    const HEAD_LOCATION               : usize = 6;
+    const DELTA_TIME_LOCATION         : usize = 7;
+    const MAX_AGE_LOCATION            : usize = 8;
+    const STEP_LOCATION               : usize = 9;
+    const NUMBER_OF_TRACERS_LOCATION  : usize = 10;
+    /// The tracers are wrapped in rows of this width, the textures can't be as wide as their count.
+    const TRACER_TEXTURE_WIDTH        : usize = 1024;
+
+    /// Creates `count` dead tracers with trails of `trail_length` points. They are spawned by `seed_region` or by the emitters.
+    pub fn new(context: &Context, count: usize, trail_length: usize) -> Self {
+        let compute_shader = gpu::ComputeShader::new(&context.context, include_str!("advect.glsl")).expect("Couldn't create ComputeShader.");
+        let advect_program = gpu::ComputeProgram::new(&context.context, &compute_shader).expect("Couldn't create ComputeProgram.");
+        let trail_length = trail_length.max(2);
+        let tracer_field = Self::tracer_field(context, &vec![0.0; count * 4]);
+        let trail_field = Self::trail_field(context, trail_length, &vec![(0.0, 0.0); count]);
+        let emitter_field = Self::emitter_field(context, &[]);
+        let number_of_emitters = 0;
+        let head = 0;
+        let step = 0;
+        let max_age = 0.0;
+        Self { advect_program, tracer_field, trail_field, emitter_field, number_of_emitters, count, trail_length, head, step, max_age }
+    }
+
+    /// The dimensions of a texture with one texel per tracer, row by row. There is always at least one texel.
+    fn texture_dimensions(count: usize) -> (usize, usize) {
+        let rows = (count + Self::TRACER_TEXTURE_WIDTH - 1) / Self::TRACER_TEXTURE_WIDTH;
+        (Self::TRACER_TEXTURE_WIDTH, rows.max(1))
+    }
+
+    fn tracer_field(context: &Context, data: &[f32]) -> gpu::Texture2D {
+        let dimensions = Self::texture_dimensions(data.len() / 4);
+        // The padding texels are dead tracers.
+        let mut data = data.to_vec();
+        data.resize(dimensions.0 * dimensions.1 * 4, 0.0);
+        let format = gpu::TextureFormat::new(gpu::ColorFormat::components(4), gpu::Type::F32);
+        gpu::Texture2D::from_data(&context.context, dimensions, &format, &data, &format)
+    }
+
+    /// The trail points are blocks of tracer rows, one block per trail point.
+    fn trail_field(context: &Context, trail_length: usize, positions: &[(f32, f32)]) -> gpu::Texture2D {
+        let dimensions = Self::texture_dimensions(positions.len());
+        let mut data = Vec::with_capacity(dimensions.0 * dimensions.1 * trail_length * 2);
+        for _ in 0 .. trail_length {
+            for position in positions {
+                data.push(position.0);
+                data.push(position.1);
+            }
+            data.resize(data.len() + (dimensions.0 * dimensions.1 - positions.len()) * 2, 0.0);
+        }
+        let format = gpu::TextureFormat::new(gpu::ColorFormat::RG, gpu::Type::F32);
+        gpu::Texture2D::from_data(&context.context, (dimensions.0, dimensions.1 * trail_length), &format, &data, &format)
+    }
+
+    fn emitter_field(context: &Context, emitters: &[Emitter]) -> gpu::Texture2D {
+        // An empty texture isn't valid, so there is always at least one texel.
+        let mut data = vec![0.0; emitters.len().max(1) * 4];
+        for (index, emitter) in emitters.iter().enumerate() {
+            data[index * 4 .. index * 4 + 4].copy_from_slice(&[emitter.minimum.0, emitter.minimum.1, emitter.maximum.0, emitter.maximum.1]);
+        }
+        let format = gpu::TextureFormat::new(gpu::ColorFormat::components(4), gpu::Type::F32);
+        gpu::Texture2D::from_data(&context.context, (data.len() / 4, 1), &format, &data, &format)
+    }
+
+    /// Places all the tracers on a jittered grid covering the rectangle from `minimum` to `maximum`.
+    pub fn seed_region(&mut self, context: &Context, minimum: (f32, f32), maximum: (f32, f32)) {
+        let size = (maximum.0 - minimum.0, maximum.1 - minimum.1);
+        let columns = ((self.count as f32 * size.0 / size.1.max(std::f32::EPSILON)).sqrt().ceil() as usize).max(1);
+        let rows = (self.count + columns - 1) / columns;
+        let positions: Vec<(f32, f32)> = (0 .. self.count).map(|index| {
+            let (column, row) = (index % columns, index / columns);
+            (minimum.0 + (column as f32 + 0.5) / columns as f32 * size.0, minimum.1 + (row as f32 + 0.5) / rows as f32 * size.1)
+        }).collect();
+        self.seed(context, &positions);
+    }
+
+    /// Places the tracers on `positions`. There must be one position per tracer.
+    pub fn seed(&mut self, context: &Context, positions: &[(f32, f32)]) {
+        assert_eq!(positions.len(), self.count, "There must be one position per tracer.");
+        let data: Vec<f32> = positions.iter().flat_map(|position| vec![position.0, position.1, 0.0, 1.0]).collect();
+        self.tracer_field = Self::tracer_field(context, &data);
+        self.trail_field = Self::trail_field(context, self.trail_length, positions);
+    }
+
+    pub fn set_emitters(&mut self, context: &Context, emitters: &[Emitter]) {
+        self.emitter_field = Self::emitter_field(context, emitters);
+        self.number_of_emitters = emitters.len();
+    }
+
+    /// Moves the tracers through `fluid.velocity_field`.
+    pub fn advect(&mut self, fluid: &Fluid, delta_time: f32) {
+        self.head = (self.head + 1) % self.trail_length;
+        self.step += 1;
+        let program = &self.advect_program;
+        program.bind_image_2d(&self.tracer_field, Self::FIELD_LOCATION);
+        program.bind_image_2d(&self.trail_field, Self::TRAIL_FIELD_LOCATION);
+        program.bind_image_2d(&fluid.velocity_field, Self::VELOCITY_FIELD_LOCATION);
+        program.bind_image_2d(&fluid.obstacle_field, Self::OBSTACLE_FIELD_LOCATION);
+        program.bind_image_2d(&self.emitter_field, Self::EMITTER_FIELD_LOCATION);
+        program.bind_i32(self.number_of_emitters as i32, Self::NUMBER_OF_EMITTERS_LOCATION);
+        program.bind_i32(self.head as i32, Self::HEAD_LOCATION);
+        program.bind_f32(delta_time, Self::DELTA_TIME_LOCATION);
+        program.bind_f32(self.max_age, Self::MAX_AGE_LOCATION);
+        program.bind_i32(self.step as i32, Self::STEP_LOCATION);
+        program.bind_i32(self.count as i32, Self::NUMBER_OF_TRACERS_LOCATION);
+        let dimensions = self.tracer_field.dimensions();
+        program.compute((dimensions.0, dimensions.1, 1));
+        //FIXME: How to expose it on the GPU API?
+        // Ref: https://www.khronos.org/registry/OpenGL-Refpages/gl4/html/glMemoryBarrier.xhtml
+        unsafe {
+            gl::MemoryBarrier(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+        }
+    }
+
+    /// Reads back the positions of the living tracers.
+    pub fn positions(&self) -> Vec<(f32, f32)> {
+        let data: Vec<f32> = self.tracer_field.data();
+        data.chunks(4).take(self.count).filter(|tracer| tracer[3] > 0.5).map(|tracer| (tracer[0], tracer[1])).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::context::Context;
+    use crate::fluid::Fluid;
+    use crate::tracer::{Emitter, Tracers};
+
+    /// A fluid flowing uniformly with `velocity`, without obstacles.
+    fn uniform_flow(context: &Context, dimensions: (usize, usize), velocity: (f32, f32)) -> Fluid {
+        let mut fluid = Fluid::new(context, dimensions, 0.0, 0.0);
+        let format = gpu::TextureFormat::new(gpu::ColorFormat::RG, gpu::Type::F32);
+        let data = [velocity.0, velocity.1].repeat(dimensions.0 * dimensions.1);
+        fluid.velocity_field = gpu::Texture2D::from_data(&context.context, dimensions, &format, &data, &format);
+        let format = gpu::TextureFormat::new(gpu::ColorFormat::R, gpu::Type::F32);
+        fluid.obstacle_field = gpu::Texture2D::from_data(&context.context, dimensions, &format, &vec![0.0; dimensions.0 * dimensions.1], &format);
+        fluid
+    }
+
+    #[test]
+    fn uniform_advection() {
+        let dimensions = (8, 8);
+        let context = Context::new(dimensions);
+        let fluid = uniform_flow(&context, dimensions, (1.0, 0.5));
+        let mut tracers = Tracers::new(&context, 2, 3);
+        tracers.seed(&context, &[(1.0, 1.0), (2.5, 4.25)]);
+        tracers.advect(&fluid, 1.0);
+        tracers.advect(&fluid, 1.0);
+        assert_eq!(tracers.positions(), vec![(3.0, 2.0), (4.5, 5.25)]);
+
+        // The trail keeps the last positions, from the head backwards.
+        let trail: Vec<f32> = tracers.trail_field.data();
+        let (width, rows) = tracers.tracer_field.dimensions();
+        let point = |row: usize| (trail[row * rows * width * 2], trail[row * rows * width * 2 + 1]);
+        assert_eq!(tracers.head, 2);
+        assert_eq!((point(2), point(1), point(0)), ((3.0, 2.0), (2.0, 1.5), (1.0, 1.0)));
+    }
+
+    #[test]
+    fn more_tracers_than_a_texture_row() {
+        let dimensions = (8, 8);
+        let context = Context::new(dimensions);
+        let fluid = uniform_flow(&context, dimensions, (0.0, 1.0));
+        let count = Tracers::TRACER_TEXTURE_WIDTH + 10;
+        let mut tracers = Tracers::new(&context, count, 2);
+        tracers.seed_region(&context, (1.0, 1.0), (3.0, 3.0));
+        let positions = tracers.positions();
+        tracers.advect(&fluid, 1.0);
+        let expected_positions: Vec<(f32, f32)> = positions.iter().map(|position| (position.0, position.1 + 1.0)).collect();
+        assert_eq!(tracers.positions(), expected_positions);
+    }
+
+    #[test]
+    fn respawn() {
+        let dimensions = (8, 8);
+        let context = Context::new(dimensions);
+        let fluid = uniform_flow(&context, dimensions, (4.0, 0.0));
+
+        // Without emitters, the tracers leaving the domain die.
+        let mut tracers = Tracers::new(&context, 2, 2);
+        tracers.seed(&context, &[(1.0, 3.0), (5.0, 3.0)]);
+        tracers.advect(&fluid, 1.0);
+        assert_eq!(tracers.positions(), vec![(5.0, 3.0)]);
+
+        // With an emitter, they are respawned in it.
+        let emitter = Emitter { minimum: (1.0, 1.0), maximum: (2.0, 2.0) };
+        tracers.set_emitters(&context, &[emitter]);
+        tracers.seed(&context, &[(1.0, 3.0), (5.0, 3.0)]);
+        tracers.advect(&fluid, 1.0);
+        let positions = tracers.positions();
+        assert_eq!(positions.len(), 2);
+        assert_eq!(positions[0], (5.0, 3.0));
+        assert!(positions[1].0 >= 1.0 && positions[1].0 <= 2.0 && positions[1].1 >= 1.0 && positions[1].1 <= 2.0);
+        let data: Vec<f32> = tracers.tracer_field.data();
+        assert_eq!(data[4 + 2], 0.0, "A respawned tracer is new.");
+
+        // And so are the tracers older than max_age.
+        let fluid = uniform_flow(&context, dimensions, (0.0, 0.0));
+        tracers.max_age = 1.5;
+        tracers.seed(&context, &[(6.0, 6.0), (6.0, 6.0)]);
+        tracers.advect(&fluid, 1.0);
+        assert_eq!(tracers.positions(), vec![(6.0, 6.0); 2]);
+        tracers.advect(&fluid, 1.0);
+        assert!(tracers.positions().iter().all(|position| position.0 <= 2.0 && position.1 <= 2.0));
+    }
+}
@@ -1,4 +1,9 @@
+#version 460
+
+in vec3 arrowColor;
+
+out vec4 color;
 
 void main(void) {
-    gl_FragColor = vec4(1.0, 0.0, 0.0, 1.0);
-}
\ No newline at end of file
+    color = vec4(arrowColor, 1.0);
+}
@@ -1,33 +1,52 @@
 use crate::context::Context;
+use crate::presenter::Presenter;
 
+/// Draws a grid of velocity arrows over the `Presenter` output.
 pub struct VelocityDebugger {
     raster: gpu::RasterProgram,
     vertex_array_object: gpu::VertexArrayObject,
-    buffer: gpu::Buffer,
-    framebuffer: gpu::Framebuffer
+    /// Cells between the arrows.
+    pub spacing: usize,
+    /// Cells per unit of speed, or the arrow length in spacings when `normalize` is set.
+    pub scale: f32,
+    /// Draws all the arrows with the same length, only their colour shows the magnitude.
+    pub normalize: bool,
+    /// The speed at the top of the colour ramp.
+    pub color_speed: f32
 }
 
 impl VelocityDebugger {
     pub fn new(context: &Context) -> Self {
-        let data: Vec<f32> = vec![
-             -1.0,  0.25,
-              1.0,  0.00,
-             -1.0, -0.25
-        ];
-        let buffer = gpu::Buffer::from_data(&context.context, &data);
-        let mut vertex_array_object = gpu::VertexArrayObject::new(&context.context);
-        vertex_array_object.set_vertex_buffer(&buffer, 0, 2);
-        vertex_array_object.set_vertices(3);
+        let vertex_array_object = gpu::VertexArrayObject::new(&context.context);
         let vertex = gpu::VertexShader::new(&context.context, include_str!("vertex.glsl")).expect("Couldn't create fragment.");
         let fragment = gpu::FragmentShader::new(&context.context, include_str!("fragment.glsl")).expect("Couldn't create fragment.");
         let raster = gpu::RasterProgram::new(&context.context, &fragment, &vertex).expect("Couldn't create program.");
-        let framebuffer = gpu::Framebuffer::default(&context.context);
-        Self { buffer, vertex_array_object, raster, framebuffer }
+        let spacing = 16;
+        let scale = 0.1;
+        let normalize = false;
+        let color_speed = 100.0;
+        Self { vertex_array_object, raster, spacing, scale, normalize, color_speed }
     }
 
-    pub fn debug(&self, velocity_field: &gpu::Texture2D) {
-        self.raster.bind_vec2((0.0, 0.0), 0);
-        self.raster.program.bind_image_2d(velocity_field, 1);
-        self.raster.raster(&self.framebuffer, &self.vertex_array_object, gpu::RasterGeometry::Triangles, self.vertex_array_object.get_vertices());
+    pub fn debug(&self, context: &Context, presenter: &Presenter, velocity_field: &gpu::Texture2D) {
+        const VELOCITY_FIELD_LOCATION   : usize = 0;
+        const FIELD_DIMENSIONS_LOCATION : usize = 1;
+        const SPACING_LOCATION          : usize = 2;
+        const ARROW_SCALE_LOCATION      : usize = 3;
+        const NORMALIZED_LOCATION       : usize = 4;
+        const COLOR_SPEED_LOCATION      : usize = 5;
+        const SCALE_LOCATION            : usize = 6;
+        const VERTICES_PER_ARROW        : usize = 9;
+        let dimensions = velocity_field.dimensions();
+        let spacing = self.spacing.max(1);
+        let arrows = ((dimensions.0 + spacing - 1) / spacing) * ((dimensions.1 + spacing - 1) / spacing);
+        self.raster.bind_image_2d(velocity_field, VELOCITY_FIELD_LOCATION);
+        self.raster.bind_vec2((dimensions.0 as f32, dimensions.1 as f32), FIELD_DIMENSIONS_LOCATION);
+        self.raster.bind_i32(spacing as i32, SPACING_LOCATION);
+        self.raster.bind_f32(self.scale, ARROW_SCALE_LOCATION);
+        self.raster.bind_bool(self.normalize, NORMALIZED_LOCATION);
+        self.raster.bind_f32(self.color_speed, COLOR_SPEED_LOCATION);
+        self.raster.bind_vec2(presenter.scale(context, dimensions), SCALE_LOCATION);
+        self.raster.raster(&context.framebuffer, &self.vertex_array_object, gpu::RasterGeometry::Triangles, arrows * VERTICES_PER_ARROW);
     }
-}
\ No newline at end of file
+}
@@ -1,18 +1,43 @@
 #version 460
 
-layout(location = 0) in vec2 inVertex;
-layout(location = 0) uniform vec2 position;
-layout(rg32f, location = 1) uniform image2D velocityField;
+layout(rg32f, location = 0) uniform image2D velocityField;
+layout(location = 1) uniform vec2 fieldResolution;
+layout(location = 2) uniform int spacing;
+// Cells per unit of speed, or the arrow length in spacings when normalized.
+layout(location = 3) uniform float arrowScale;
+layout(location = 4) uniform bool normalized;
+// The speed at the top of the colour ramp.
+layout(location = 5) uniform float colorSpeed;
+// The size of the field in clip space, from the Presenter fit.
+layout(location = 6) uniform vec2 scale;
 
-mat2 rotate(float a) {
-    return mat2( cos(a), -sin(a),
-                 sin(a),  cos(a));
-}
+out vec3 arrowColor;
+
+// An arrow along +x from the origin to one, as a shaft of two triangles and a head.
+const int VERTICES_PER_ARROW = 9;
+const vec2 arrow[VERTICES_PER_ARROW] = vec2[](
+    vec2(0.0, -0.04), vec2(0.7, -0.04), vec2(0.7,  0.04),
+    vec2(0.0, -0.04), vec2(0.7,  0.04), vec2(0.0,  0.04),
+    vec2(0.7, -0.15), vec2(1.0,  0.0 ), vec2(0.7,  0.15)
+);
 
 void main(void) {
-    float scale = imageSize(velocityField).x / 50.0;
-    vec2 velocity = imageLoad(velocityField, ivec2(position)).xy;
-    float a = atan(velocity.y, velocity.x);
-    vec2 vertex = inVertex * vec2(length(velocity) / 100.0, 1.0) * rotate(a) + position;
-    gl_Position = vec4(vertex / scale, 0.0, 1.0);
+    // The GPU API has no instanced draws, so the instance is derived from the vertex index.
+    int instance = gl_VertexID / VERTICES_PER_ARROW;
+    int columns = (int(fieldResolution.x) + spacing - 1) / spacing;
+    ivec2 cell = ivec2(instance % columns, instance / columns) * spacing + spacing / 2;
+    cell = min(cell, ivec2(fieldResolution) - 1);
+
+    vec2 velocity = imageLoad(velocityField, cell).xy;
+    float speed = length(velocity);
+    float arrowLength = normalized ? arrowScale * float(spacing) : arrowScale * speed;
+    vec2 direction = speed > 0.0 ? velocity / speed : vec2(1.0, 0.0);
+    vec2 glyph = arrow[gl_VertexID % VERTICES_PER_ARROW] * arrowLength;
+    vec2 position = vec2(cell) + direction * glyph.x + vec2(-direction.y, direction.x) * glyph.y;
+
+    float t = clamp(speed / max(colorSpeed, 1e-20), 0.0, 1.0);
+    arrowColor = mix(vec3(0.2, 0.4, 1.0), vec3(1.0, 0.2, 0.1), t);
+    // Cell centers are on integer coordinates, so the field covers [-0.5, resolution - 0.5].
+    vec2 ndc = (position + 0.5) / fieldResolution * 2.0 - 1.0;
+    gl_Position = vec4(ndc * scale, 0.0, 1.0);
 }
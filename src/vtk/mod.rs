@@ -0,0 +1,186 @@
+//! VTK XML ImageData (`.vti`) frames and ParaView collections (`.pvd`).
+
+use crate::fluid::Fluid;
+use crate::numpy::vorticity;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let triple = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for index in 0 .. 4 {
+            if index <= chunk.len() {
+                encoded.push(BASE64[(triple >> (18 - 6 * index) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// A point data array, interleaved like the textures.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DataArray {
+    pub name: String,
+    pub components: usize,
+    pub data: Vec<f32>
+}
+
+/// A frame on a uniform grid. The points are the cell centers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImageData {
+    pub dimensions: (usize, usize),
+    /// Physical size of a cell.
+    pub spacing: (f64, f64),
+    /// Physical position of the lower left corner of the domain.
+    pub origin: (f64, f64),
+    pub arrays: Vec<DataArray>
+}
+
+impl ImageData {
+    pub fn new(dimensions: (usize, usize), spacing: (f64, f64), origin: (f64, f64)) -> Self {
+        Self { dimensions, spacing, origin, arrays: Vec::new() }
+    }
+
+    pub fn add_array(&mut self, name: &str, components: usize, data: Vec<f32>) {
+        assert_eq!(data.len(), self.dimensions.0 * self.dimensions.1 * components, "The {} array doesn't match the dimensions.", name);
+        self.arrays.push(DataArray { name: name.to_string(), components, data });
+    }
+
+    /// Reads back the density, the velocity, the vorticity and the optional `pressure_field`.
+    /// The velocity is scaled from cells to physical units per second, the vorticity is per second in both.
+    pub fn from_fluid(fluid: &Fluid, pressure_field: Option<&gpu::Texture2D>, spacing: (f64, f64), origin: (f64, f64)) -> Self {
+        let mut image = Self::new(fluid.dimensions, spacing, origin);
+        image.add_array("density", 1, fluid.density_field.data());
+        let velocity: Vec<f32> = fluid.velocity_field.data();
+        let vorticity = vorticity(&velocity, fluid.dimensions);
+        let physical_velocity = velocity.chunks(2).flat_map(|v| vec![v[0] * spacing.0 as f32, v[1] * spacing.1 as f32]).collect();
+        image.add_array("velocity", 2, physical_velocity);
+        image.add_array("vorticity", 1, vorticity);
+        if let Some(pressure_field) = pressure_field {
+            image.add_array("pressure", 1, pressure_field.data());
+        }
+        image
+    }
+
+    pub fn write(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        let extent = format!("0 {} 0 {} 0 0", self.dimensions.0 - 1, self.dimensions.1 - 1);
+        let origin = (self.origin.0 + 0.5 * self.spacing.0, self.origin.1 + 0.5 * self.spacing.1);
+        writeln!(writer, "<?xml version=\"1.0\"?>")?;
+        writeln!(writer, "<VTKFile type=\"ImageData\" version=\"1.0\" byte_order=\"LittleEndian\" header_type=\"UInt32\">")?;
+        writeln!(writer, "  <ImageData WholeExtent=\"{}\" Origin=\"{} {} 0\" Spacing=\"{} {} 1\">", extent, origin.0, origin.1, self.spacing.0, self.spacing.1)?;
+        writeln!(writer, "    <Piece Extent=\"{}\">", extent)?;
+        let scalars = self.arrays.iter().find(|array| array.components == 1).map(|array| format!(" Scalars=\"{}\"", array.name)).unwrap_or_default();
+        let vectors = self.arrays.iter().find(|array| array.components > 1).map(|array| format!(" Vectors=\"{}\"", array.name)).unwrap_or_default();
+        writeln!(writer, "      <PointData{}{}>", scalars, vectors)?;
+        for array in &self.arrays {
+            // VTK vectors have three components, so the 2D ones get a zero z.
+            let components = if array.components == 2 { 3 } else { array.components };
+            let mut bytes = Vec::with_capacity(4 + array.data.len() / array.components * components * 4);
+            bytes.extend_from_slice(&((array.data.len() / array.components * components * 4) as u32).to_le_bytes());
+            for value in array.data.chunks(array.components) {
+                for component in 0 .. components {
+                    bytes.extend_from_slice(&value.get(component).copied().unwrap_or(0.0).to_le_bytes());
+                }
+            }
+            writeln!(writer, "        <DataArray type=\"Float32\" Name=\"{}\" NumberOfComponents=\"{}\" format=\"binary\">", array.name, components)?;
+            writeln!(writer, "          {}", base64(&bytes))?;
+            writeln!(writer, "        </DataArray>")?;
+        }
+        writeln!(writer, "      </PointData>")?;
+        writeln!(writer, "    </Piece>")?;
+        writeln!(writer, "  </ImageData>")?;
+        writeln!(writer, "</VTKFile>")
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()
+    }
+}
+
+/// Writes the frames of a run as `<prefix>_<frame>.vti`, listed with their times in `<prefix>.pvd`.
+pub struct VtkSeries {
+    pub directory: PathBuf,
+    pub prefix: String,
+    frames: Vec<(f32, String)>
+}
+
+impl VtkSeries {
+    pub fn new(directory: impl Into<PathBuf>, prefix: &str) -> Self {
+        Self { directory: directory.into(), prefix: prefix.to_string(), frames: Vec::new() }
+    }
+
+    /// Saves `image` at `time` and rewrites the collection, so an interrupted run can still be opened.
+    pub fn write_frame(&mut self, time: f32, image: &ImageData) -> std::io::Result<PathBuf> {
+        std::fs::create_dir_all(&self.directory)?;
+        let name = format!("{}_{:06}.vti", self.prefix, self.frames.len());
+        let path = self.directory.join(&name);
+        image.save(&path)?;
+        self.frames.push((time, name));
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(self.directory.join(format!("{}.pvd", self.prefix)))?);
+        self.write_collection(&mut writer)?;
+        writer.flush()?;
+        Ok(path)
+    }
+
+    pub fn write_collection(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        writeln!(writer, "<?xml version=\"1.0\"?>")?;
+        writeln!(writer, "<VTKFile type=\"Collection\" version=\"0.1\" byte_order=\"LittleEndian\">")?;
+        writeln!(writer, "  <Collection>")?;
+        for (time, name) in &self.frames {
+            writeln!(writer, "    <DataSet timestep=\"{}\" group=\"\" part=\"0\" file=\"{}\"/>", time, name)?;
+        }
+        writeln!(writer, "  </Collection>")?;
+        writeln!(writer, "</VTKFile>")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn base64_padding() {
+        assert_eq!(base64(b"Man"), "TWFu");
+        assert_eq!(base64(b"Ma"), "TWE=");
+        assert_eq!(base64(b"M"), "TQ==");
+        assert_eq!(base64(b""), "");
+    }
+
+    #[test]
+    fn image_data() {
+        let mut image = ImageData::new((2, 1), (0.5, 0.5), (1.0, 0.0));
+        image.add_array("density", 1, vec![1.0, 2.0]);
+        image.add_array("velocity", 2, vec![1.0, 2.0, 3.0, 4.0]);
+        let mut bytes = Vec::new();
+        image.write(&mut bytes).unwrap();
+        let xml = String::from_utf8(bytes).unwrap();
+        assert!(xml.contains("<ImageData WholeExtent=\"0 1 0 0 0 0\" Origin=\"1.25 0.25 0\" Spacing=\"0.5 0.5 1\">"));
+        assert!(xml.contains("<PointData Scalars=\"density\" Vectors=\"velocity\">"));
+        assert!(xml.contains("Name=\"velocity\" NumberOfComponents=\"3\""));
+        // 24 bytes of data after the length header.
+        let mut velocity = 24u32.to_le_bytes().to_vec();
+        for value in &[1.0f32, 2.0, 0.0, 3.0, 4.0, 0.0] {
+            velocity.extend_from_slice(&value.to_le_bytes());
+        }
+        assert!(xml.contains(&base64(&velocity)));
+    }
+
+    #[test]
+    fn collection() {
+        let mut series = VtkSeries::new("unused", "run");
+        series.frames.push((0.0, "run_000000.vti".to_string()));
+        series.frames.push((0.5, "run_000001.vti".to_string()));
+        let mut bytes = Vec::new();
+        series.write_collection(&mut bytes).unwrap();
+        let xml = String::from_utf8(bytes).unwrap();
+        assert!(xml.contains("<DataSet timestep=\"0.5\" group=\"\" part=\"0\" file=\"run_000001.vti\"/>"));
+    }
+}